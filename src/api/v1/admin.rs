@@ -1,5 +1,5 @@
 use async_stream::stream;
-use axum::http::{HeaderMap, StatusCode};
+use axum::http::{HeaderMap, StatusCode, header};
 use axum::response::sse::Event;
 use axum::{
     Json, Router,
@@ -10,19 +10,34 @@ use axum::{
 use futures::{Stream, StreamExt};
 use serde::Deserialize;
 use serde_json::{Value as JsonValue, json};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::Infallible;
 
-use crate::core::auth::{verify_api_key, verify_app_key, verify_stream_api_key};
+use crate::core::auth::{admin_actor, verify_api_key, verify_app_key, verify_stream_api_key};
 use crate::core::batch_tasks::{create_task, expire_task, get_task};
-use crate::core::config::{get_all_config, update_config};
+use crate::core::config::history::diff as config_diff;
+use crate::core::config::{
+    ConfigError, config_history, config_schema, get_all_config, invalid_config_fields, path,
+    readonly_keys, rollback_config, secrets, stored_config, update_config,
+};
 use crate::core::exceptions::ApiError;
+use crate::core::redact::{fingerprint, is_fingerprint};
 use crate::core::static_assets;
-use crate::core::storage::{Storage, get_storage};
+use crate::core::storage::migrate::{API_TARGETS_ENV, api_allows, migrate_storage};
+use crate::core::storage::{Storage, StorageSpec, get_storage};
 use crate::services::grok::assets::{DeleteService, DownloadService, ListService};
 use crate::services::grok::batch::{OnItem, ShouldCancel, run_in_batches};
 use crate::services::grok::nsfw::NsfwService;
-use crate::services::token::get_token_manager;
+use crate::services::grok::wreq_client::is_known_emulation;
+use crate::services::proxy::checker::probe_all;
+use crate::services::proxy::{get_proxy_pool, mask_proxy_url};
+use crate::services::token::health::ErrorClass;
+use crate::services::token::manager::{TokenManager, flush_tokens};
+use crate::services::token::models::SelectionStrategy;
+use crate::services::token::nsfw::{enable_and_record, enable_pending_nsfw};
+use crate::services::token::tier::detect_pending_tiers;
+use crate::services::token::transfer::{export_csv, export_json, mask_token, parse_import};
+use crate::services::token::{TagFilter, TokenInfo, get_token_manager};
 
 pub fn router() -> Router {
     Router::new()
@@ -37,17 +52,44 @@ pub fn router() -> Router {
             "/api/v1/admin/config",
             get(get_config_api).post(update_config_api),
         )
+        .route("/api/v1/admin/config/schema", get(get_config_schema_api))
+        .route(
+            "/api/v1/admin/config/versions",
+            get(list_config_versions_api),
+        )
+        .route(
+            "/api/v1/admin/config/versions/:version",
+            get(get_config_version_api),
+        )
+        .route("/api/v1/admin/config/rollback", post(rollback_config_api))
         .route("/api/v1/admin/storage", get(get_storage_api))
+        .route("/api/v1/admin/storage/migrate", post(migrate_storage_api))
         .route(
             "/api/v1/admin/tokens",
             get(get_tokens_api).post(update_tokens_api),
         )
+        .route("/api/v1/admin/tokens/add", post(add_tokens_api))
+        .route("/api/v1/admin/tokens/remove", post(remove_token_api))
+        .route("/api/v1/admin/tokens/status", post(set_token_status_api))
+        .route("/api/v1/admin/tokens/note", post(set_token_note_api))
+        .route("/api/v1/admin/tokens/tags", post(set_token_tags_api))
+        .route("/api/v1/admin/tokens/reset", post(reset_token_api))
+        .route("/api/v1/admin/tokens/move", post(move_token_api))
+        .route("/api/v1/admin/tokens/binding", post(set_token_binding_api))
+        .route("/api/v1/admin/tokens/shared", get(get_shared_accounts_api))
+        .route("/api/v1/admin/secrets/reveal", post(reveal_secrets_api))
+        .route("/api/v1/admin/proxies", get(get_proxies_api))
+        .route("/api/v1/admin/proxies/check", post(check_proxies_api))
+        .route("/api/v1/admin/proxies/reset", post(reset_proxy_api))
+        .route("/api/v1/admin/tokens/import", post(import_tokens_api))
+        .route("/api/v1/admin/tokens/export", get(export_tokens_api))
         .route("/api/v1/admin/tokens/refresh", post(refresh_tokens_api))
         .route(
             "/api/v1/admin/tokens/refresh/async",
             post(refresh_tokens_api_async),
         )
         .route("/api/v1/admin/tokens/nsfw/enable", post(enable_nsfw_api))
+        .route("/api/v1/admin/tokens/nsfw/verify", post(verify_nsfw_api))
         .route(
             "/api/v1/admin/tokens/nsfw/enable/async",
             post(enable_nsfw_api_async),
@@ -114,56 +156,883 @@ async fn admin_login_api(headers: HeaderMap) -> Result<Response, ApiError> {
     Ok(Json(json!({"status": "success", "api_key": api_key})).into_response())
 }
 
+const READONLY_HEADER: &str = "x-config-readonly";
+const INVALID_HEADER: &str = "x-config-invalid";
+
 async fn get_config_api(headers: HeaderMap) -> Result<Response, ApiError> {
     verify_api_key(&headers).await?;
-    let cfg = get_all_config().await;
-    Ok(Json(cfg).into_response())
+    let mut cfg = get_all_config().await;
+    secrets::mask(&mut cfg);
+    let mut response = Json(cfg).into_response();
+    // Keys pinned by environment/CLI overrides; the UI shows them read-only.
+    if let Ok(value) = header::HeaderValue::from_str(&readonly_keys().join(",")) {
+        response.headers_mut().insert(READONLY_HEADER, value);
+    }
+    // Stored values that failed validation and are running on their defaults.
+    let invalid: Vec<String> = invalid_config_fields()
+        .await
+        .into_iter()
+        .map(|error| error.field)
+        .collect();
+    if let Ok(value) = header::HeaderValue::from_str(&invalid.join(",")) {
+        response.headers_mut().insert(INVALID_HEADER, value);
+    }
+    Ok(response)
 }
 
 async fn update_config_api(
     headers: HeaderMap,
-    Json(data): Json<JsonValue>,
+    Json(mut data): Json<JsonValue>,
+) -> Result<Response, ApiError> {
+    verify_api_key(&headers).await?;
+    secrets::restore(&mut data, &get_all_config().await);
+    let version = match update_config(&data, &admin_actor(&headers)).await {
+        Ok(version) => version,
+        Err(err) => return config_error_response(err),
+    };
+    let message = if version.is_some() {
+        "配置已更新"
+    } else {
+        "配置未变化"
+    };
+    Ok(Json(json!({
+        "status": "success",
+        "message": message,
+        "version": version.map(|v| v.version),
+    }))
+    .into_response())
+}
+
+fn config_error_response(err: ConfigError) -> Result<Response, ApiError> {
+    match err {
+        ConfigError::Invalid(errors) => {
+            let body = json!({"status": "error", "message": "配置校验失败", "errors": errors});
+            Ok((StatusCode::BAD_REQUEST, Json(body)).into_response())
+        }
+        ConfigError::UnknownVersion(version) => Err(ApiError::not_found(format!(
+            "Config version {version} not found"
+        ))),
+        ConfigError::Storage(err) => Err(ApiError::server(err.to_string())),
+    }
+}
+
+async fn list_config_versions_api(headers: HeaderMap) -> Result<Response, ApiError> {
+    verify_api_key(&headers).await?;
+    let versions = config_history()
+        .await
+        .map_err(|e| ApiError::server(e.to_string()))?;
+    let summaries: Vec<JsonValue> = versions.iter().rev().map(|v| v.summary()).collect();
+    Ok(Json(json!({"status": "success", "versions": summaries})).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+struct ConfigVersionQuery {
+    /// Diff against this version instead of the one recorded before it.
+    against: Option<u64>,
+}
+
+async fn get_config_version_api(
+    headers: HeaderMap,
+    Path(version): Path<u64>,
+    Query(query): Query<ConfigVersionQuery>,
 ) -> Result<Response, ApiError> {
     verify_api_key(&headers).await?;
-    update_config(&data)
+    let versions = config_history()
         .await
         .map_err(|e| ApiError::server(e.to_string()))?;
-    Ok(Json(json!({"status": "success", "message": "配置已更新"})).into_response())
+    let find = |wanted: u64| {
+        versions
+            .iter()
+            .find(|v| v.version == wanted)
+            .ok_or_else(|| ApiError::not_found(format!("Config version {wanted} not found")))
+    };
+    let entry = find(version)?;
+    let diff = match query.against {
+        Some(against) => config_diff(&find(against)?.snapshot, &entry.snapshot),
+        None => entry.diff.clone(),
+    };
+    let stored = stored_config()
+        .await
+        .map_err(|e| ApiError::server(e.to_string()))?;
+    let current = config_diff(&entry.snapshot, &stored);
+    let mut body = json!({
+        "status": "success",
+        "version": entry,
+        "diff": diff,
+        "diff_to_current": current,
+    });
+    secrets::mask(&mut body["version"]["snapshot"]);
+    secrets::mask_diff(&mut body["version"]["diff"]);
+    secrets::mask_diff(&mut body["diff"]);
+    secrets::mask_diff(&mut body["diff_to_current"]);
+    Ok(Json(body).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+struct ConfigRollbackRequest {
+    version: u64,
+}
+
+async fn rollback_config_api(
+    headers: HeaderMap,
+    Json(data): Json<ConfigRollbackRequest>,
+) -> Result<Response, ApiError> {
+    verify_api_key(&headers).await?;
+    match rollback_config(data.version, &admin_actor(&headers)).await {
+        Ok(version) => Ok(Json(json!({
+            "status": "success",
+            "message": format!("已回滚到版本 {}", data.version),
+            "version": version.summary(),
+        }))
+        .into_response()),
+        Err(err) => config_error_response(err),
+    }
+}
+
+async fn get_config_schema_api(headers: HeaderMap) -> Result<Response, ApiError> {
+    verify_api_key(&headers).await?;
+    Ok(Json(config_schema()).into_response())
 }
 
 async fn get_storage_api(headers: HeaderMap) -> Result<Response, ApiError> {
     verify_api_key(&headers).await?;
-    let storage_type = std::env::var("SERVER_STORAGE_TYPE").unwrap_or_else(|_| "local".to_string());
-    Ok(Json(json!({"type": storage_type.to_lowercase()})).into_response())
+    Ok(Json(json!({"type": get_storage().kind()})).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+struct StorageMigrateRequest {
+    /// Defaults to the backend this instance runs on.
+    from: Option<StorageSpec>,
+    to: StorageSpec,
+    #[serde(default)]
+    dry_run: bool,
 }
 
-async fn get_tokens_api(headers: HeaderMap) -> Result<Response, ApiError> {
+async fn migrate_storage_api(
+    headers: HeaderMap,
+    Json(data): Json<StorageMigrateRequest>,
+) -> Result<Response, ApiError> {
     verify_api_key(&headers).await?;
-    let storage = get_storage();
-    let tokens = storage
-        .load_tokens()
-        .await
-        .unwrap_or(JsonValue::Object(Default::default()));
+    if let Some(spec) = data.from.iter().chain([&data.to]).find(|s| !api_allows(s)) {
+        return Err(ApiError::permission(format!(
+            "Storage {} is not listed in {API_TARGETS_ENV}",
+            describe_spec(spec)
+        )));
+    }
+    tracing::warn!(
+        "[Audit] {} migrating storage: from={} to={} dry_run={}",
+        admin_actor(&headers),
+        data.from
+            .as_ref()
+            .map_or_else(|| get_storage().kind().to_string(), describe_spec),
+        describe_spec(&data.to),
+        data.dry_run
+    );
+    let target = data
+        .to
+        .open()
+        .map_err(|e| ApiError::invalid_request(e.to_string()))?;
+    let report = match data.from {
+        Some(spec) => {
+            let source = spec
+                .open()
+                .map_err(|e| ApiError::invalid_request(e.to_string()))?;
+            migrate_storage(&source, &target, data.dry_run).await
+        }
+        None => {
+            flush_tokens()
+                .await
+                .map_err(|e| ApiError::server(e.to_string()))?;
+            migrate_storage(&get_storage(), &target, data.dry_run).await
+        }
+    }
+    .map_err(|e| ApiError::server(e.to_string()))?;
+    Ok(Json(json!({
+        "status": "success",
+        "verified": report.verified(),
+        "report": report,
+    }))
+    .into_response())
+}
+
+fn describe_spec(spec: &StorageSpec) -> String {
+    match spec.url.as_deref().map(str::trim).filter(|u| !u.is_empty()) {
+        Some(url) => format!("{}({})", spec.kind, mask_proxy_url(url)),
+        None => spec.kind.clone(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenListQuery {
+    expires_within_hours: Option<f64>,
+}
+
+async fn get_tokens_api(
+    headers: HeaderMap,
+    Query(query): Query<TokenListQuery>,
+) -> Result<Response, ApiError> {
+    verify_api_key(&headers).await?;
+    let mgr = get_token_manager().await;
+    let mut tokens = mgr.snapshot();
+    let shared: HashSet<String> = mgr.shared_accounts().into_keys().collect();
+    let now = chrono::Utc::now().timestamp_millis();
+    if let Some(pools) = tokens.as_object_mut() {
+        for list in pools.values_mut().filter_map(|v| v.as_array_mut()) {
+            if let Some(hours) = query.expires_within_hours {
+                let window_ms = (hours * 3600.0 * 1000.0) as i64;
+                list.retain(|item| {
+                    item.get("expires_at")
+                        .and_then(|v| v.as_i64())
+                        .is_some_and(|at| at > now && at <= now + window_ms)
+                });
+            }
+            for item in list.iter_mut() {
+                item["shared_account"] = json!(
+                    item.get("account_id")
+                        .and_then(|v| v.as_str())
+                        .is_some_and(|id| shared.contains(id))
+                );
+                if let Some(token) = item.get("token").and_then(|v| v.as_str()) {
+                    let health = mgr.get_health(token).unwrap_or_default();
+                    let mut value = serde_json::to_value(&health).unwrap_or(JsonValue::Null);
+                    value["score"] = json!(health.score());
+                    item["health"] = value;
+                }
+                mask_token_value(item);
+            }
+        }
+    }
     Ok(Json(tokens).into_response())
 }
 
+async fn get_shared_accounts_api(headers: HeaderMap) -> Result<Response, ApiError> {
+    verify_api_key(&headers).await?;
+    let mgr = get_token_manager().await;
+    let accounts: HashMap<String, Vec<String>> = mgr
+        .shared_accounts()
+        .into_iter()
+        .map(|(account, tokens)| (account, tokens.iter().map(|t| mask_token(t)).collect()))
+        .collect();
+    Ok(Json(json!({"status": "success", "accounts": accounts})).into_response())
+}
+
 async fn update_tokens_api(
     headers: HeaderMap,
-    Json(data): Json<JsonValue>,
+    Json(mut data): Json<JsonValue>,
 ) -> Result<Response, ApiError> {
     verify_api_key(&headers).await?;
-    let storage = get_storage();
-    storage
-        .with_lock("tokens_save", 10, || async {
-            storage.save_tokens(&data).await
-        })
+    let mgr = get_token_manager().await;
+    unmask_tokens(&mgr, &mut data);
+    mgr.replace_all(&data);
+    flush_tokens()
         .await
         .map_err(|e| ApiError::server(e.to_string()))?;
-    let mgr = get_token_manager().await;
-    mgr.lock().await.reload().await;
+    tokio::spawn(async {
+        detect_pending_tiers().await;
+        enable_pending_nsfw().await;
+    });
     Ok(Json(json!({"status": "success", "message": "Token 已更新"})).into_response())
 }
 
+#[derive(Debug, Deserialize)]
+struct TokenAddRequest {
+    token: Option<String>,
+    tokens: Option<Vec<String>>,
+    pool: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenEditRequest {
+    token: String,
+    pool: Option<String>,
+    status: Option<String>,
+    note: Option<String>,
+    tags: Option<Vec<String>>,
+}
+
+/// Omitted fields keep their binding; `null` or `""` clears it.
+#[derive(Debug, Deserialize)]
+struct TokenBindingRequest {
+    token: String,
+    #[serde(default, deserialize_with = "present")]
+    proxy_url: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    emulation: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    cf_clearance: Option<Option<String>>,
+}
+
+fn present<'de, D>(deserializer: D) -> Result<Option<Option<String>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = Option::<String>::deserialize(deserializer)?;
+    Ok(Some(
+        value
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty()),
+    ))
+}
+
+fn token_response(info: Option<TokenInfo>) -> Result<Response, ApiError> {
+    let info =
+        info.ok_or_else(|| ApiError::not_found("Token not found").with_code("token_not_found"))?;
+    Ok(Json(json!({"status": "success", "token": masked_token(&info)})).into_response())
+}
+
+/// Serializes a token with its credentials replaced by fingerprints; the raw
+/// values are only available through `/api/v1/admin/secrets/reveal`.
+fn masked_token(info: &TokenInfo) -> JsonValue {
+    let mut value = serde_json::to_value(info).unwrap_or(JsonValue::Null);
+    mask_token_value(&mut value);
+    value
+}
+
+fn mask_token_value(item: &mut JsonValue) {
+    for field in ["token", "cf_clearance"] {
+        if let Some(secret) = item.get(field).and_then(|v| v.as_str())
+            && !secret.is_empty()
+        {
+            item[field] = json!(fingerprint(secret));
+        }
+    }
+    if let Some(proxy) = item.get("proxy_url").and_then(|v| v.as_str()) {
+        item["proxy_url"] = json!(mask_proxy_url(proxy));
+    }
+}
+
+/// The token page posts back the list `get_tokens_api` returned, so
+/// fingerprints and masked proxy URLs are swapped for the stored values
+/// before saving.
+/// Entries whose fingerprint matches no stored token are dropped.
+fn unmask_tokens(mgr: &TokenManager, data: &mut JsonValue) {
+    let known: HashMap<String, TokenInfo> = mgr
+        .pools()
+        .values()
+        .flat_map(|pool| pool.list())
+        .map(|info| (fingerprint(&info.token), info))
+        .collect();
+    let Some(pools) = data.as_object_mut() else {
+        return;
+    };
+    for list in pools.values_mut().filter_map(|v| v.as_array_mut()) {
+        list.retain_mut(|item| {
+            let token = item
+                .get("token")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string();
+            let stored = if is_fingerprint(&token) {
+                let Some(info) = known.get(&token) else {
+                    tracing::warn!("Dropping token {token} from update: unknown fingerprint");
+                    return false;
+                };
+                item["token"] = json!(info.token);
+                Some(info)
+            } else {
+                known.get(&fingerprint(&token))
+            };
+            let posted_cf = item
+                .get("cf_clearance")
+                .and_then(|v| v.as_str())
+                .filter(|cf| is_fingerprint(cf))
+                .map(str::to_string);
+            if let Some(posted_cf) = posted_cf {
+                let current = stored
+                    .and_then(|info| info.cf_clearance.clone())
+                    .filter(|cf| fingerprint(cf) == posted_cf);
+                item["cf_clearance"] = json!(current);
+            }
+            if let Some(stored_proxy) = stored.and_then(|info| info.proxy_url.as_deref())
+                && item.get("proxy_url").and_then(|v| v.as_str())
+                    == Some(mask_proxy_url(stored_proxy).as_str())
+            {
+                item["proxy_url"] = json!(stored_proxy);
+            }
+            true
+        });
+    }
+}
+
+async fn add_tokens_api(
+    headers: HeaderMap,
+    Json(data): Json<TokenAddRequest>,
+) -> Result<Response, ApiError> {
+    verify_api_key(&headers).await?;
+    let pool = data
+        .pool
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .unwrap_or_else(|| "ssoBasic".to_string());
+    let mut tokens: Vec<String> = data
+        .token
+        .into_iter()
+        .chain(data.tokens.unwrap_or_default())
+        .collect();
+    tokens = tokens
+        .into_iter()
+        .map(|t| t.trim().trim_start_matches("sso=").to_string())
+        .filter(|t| !t.is_empty())
+        .collect();
+    tokens.dedup();
+    if tokens.is_empty() {
+        return Err(ApiError::invalid_request("No tokens provided"));
+    }
+
+    let mgr = get_token_manager().await;
+    let mut added = Vec::new();
+    let mut skipped = Vec::new();
+    for token in tokens {
+        match mgr.add(&token, &pool).await {
+            Some(info) => added.push(masked_token(&info)),
+            None => skipped.push(mask_token(&token)),
+        }
+    }
+    if !added.is_empty() {
+        tokio::spawn(async {
+            detect_pending_tiers().await;
+            enable_pending_nsfw().await;
+        });
+    }
+    Ok(Json(json!({"status": "success", "tokens": added, "skipped": skipped})).into_response())
+}
+
+async fn remove_token_api(
+    headers: HeaderMap,
+    Json(data): Json<TokenEditRequest>,
+) -> Result<Response, ApiError> {
+    verify_api_key(&headers).await?;
+    let mgr = get_token_manager().await;
+    token_response(mgr.remove(&mgr.resolve(&data.token)).await)
+}
+
+async fn set_token_status_api(
+    headers: HeaderMap,
+    Json(data): Json<TokenEditRequest>,
+) -> Result<Response, ApiError> {
+    verify_api_key(&headers).await?;
+    let enabled = match data
+        .status
+        .as_deref()
+        .map(|s| s.trim().to_ascii_lowercase())
+    {
+        Some(s) if s == "active" || s == "enabled" => true,
+        Some(s) if s == "disabled" => false,
+        _ => {
+            return Err(
+                ApiError::invalid_request("status must be active or disabled").with_param("status"),
+            );
+        }
+    };
+    let mgr = get_token_manager().await;
+    let info = mgr
+        .update_token(&mgr.resolve(&data.token), |t| t.set_enabled(enabled))
+        .await;
+    if enabled && info.is_some() {
+        tokio::spawn(enable_pending_nsfw());
+    }
+    token_response(info)
+}
+
+async fn set_token_note_api(
+    headers: HeaderMap,
+    Json(data): Json<TokenEditRequest>,
+) -> Result<Response, ApiError> {
+    verify_api_key(&headers).await?;
+    let note = data.note.unwrap_or_default().trim().to_string();
+    let mgr = get_token_manager().await;
+    token_response(
+        mgr.update_token(&mgr.resolve(&data.token), |t| t.note = note)
+            .await,
+    )
+}
+
+async fn set_token_tags_api(
+    headers: HeaderMap,
+    Json(data): Json<TokenEditRequest>,
+) -> Result<Response, ApiError> {
+    verify_api_key(&headers).await?;
+    let mut tags: Vec<String> = Vec::new();
+    for tag in data.tags.unwrap_or_default() {
+        let tag = tag.trim().to_string();
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    let mgr = get_token_manager().await;
+    token_response(
+        mgr.update_token(&mgr.resolve(&data.token), |t| t.tags = tags)
+            .await,
+    )
+}
+
+async fn reset_token_api(
+    headers: HeaderMap,
+    Json(data): Json<TokenEditRequest>,
+) -> Result<Response, ApiError> {
+    verify_api_key(&headers).await?;
+    let mgr = get_token_manager().await;
+    let info = mgr.reset_token(&mgr.resolve(&data.token)).await;
+    if info.is_some() {
+        tokio::spawn(enable_pending_nsfw());
+    }
+    token_response(info)
+}
+
+async fn move_token_api(
+    headers: HeaderMap,
+    Json(data): Json<TokenEditRequest>,
+) -> Result<Response, ApiError> {
+    verify_api_key(&headers).await?;
+    let pool = data
+        .pool
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .ok_or_else(|| ApiError::invalid_request("Missing target pool").with_param("pool"))?;
+    let mgr = get_token_manager().await;
+    token_response(mgr.move_token(&mgr.resolve(&data.token), &pool).await)
+}
+
+async fn set_token_binding_api(
+    headers: HeaderMap,
+    Json(data): Json<TokenBindingRequest>,
+) -> Result<Response, ApiError> {
+    verify_api_key(&headers).await?;
+    if let Some(Some(proxy)) = &data.proxy_url {
+        url::Url::parse(proxy).map_err(|e| {
+            ApiError::invalid_request(format!("Invalid proxy URL: {e}")).with_param("proxy_url")
+        })?;
+    }
+    if let Some(Some(emulation)) = &data.emulation
+        && !is_known_emulation(emulation)
+    {
+        return Err(
+            ApiError::invalid_request(format!("Unknown emulation: {emulation}"))
+                .with_param("emulation"),
+        );
+    }
+    let mgr = get_token_manager().await;
+    token_response(
+        mgr.update_token(&mgr.resolve(&data.token), |t| {
+            // The form shows masked values; posting one back keeps the binding.
+            if let Some(proxy_url) = data.proxy_url {
+                let unchanged = proxy_url.as_deref().is_some_and(|p| {
+                    t.proxy_url.as_deref().map(mask_proxy_url).as_deref() == Some(p)
+                });
+                if !unchanged {
+                    t.proxy_url = proxy_url;
+                }
+            }
+            if let Some(emulation) = data.emulation {
+                t.emulation = emulation;
+            }
+            if let Some(cf_clearance) = data.cf_clearance {
+                let unchanged = cf_clearance.as_deref().is_some_and(|cf| {
+                    is_fingerprint(cf)
+                        && t.cf_clearance.as_deref().map(fingerprint).as_deref() == Some(cf)
+                });
+                if !unchanged {
+                    t.cf_clearance = cf_clearance;
+                }
+            }
+        })
+        .await,
+    )
+}
+
+#[derive(Debug, Deserialize)]
+struct RevealRequest {
+    /// Token fingerprints (or raw tokens) as shown by the token list.
+    #[serde(default)]
+    tokens: Vec<String>,
+    /// Config paths of secret values, e.g. `grok.cf_clearance`.
+    #[serde(default)]
+    keys: Vec<String>,
+}
+
+/// The only admin endpoint that returns raw credentials; every call is
+/// logged with the caller and what was requested.
+async fn reveal_secrets_api(
+    headers: HeaderMap,
+    Json(data): Json<RevealRequest>,
+) -> Result<Response, ApiError> {
+    verify_api_key(&headers).await?;
+    if data.tokens.is_empty() && data.keys.is_empty() {
+        return Err(ApiError::invalid_request("No tokens or keys provided"));
+    }
+    if let Some(key) = data.keys.iter().find(|k| !secrets::is_secret(k)) {
+        return Err(
+            ApiError::invalid_request(format!("{key} is not a secret config key"))
+                .with_param("keys"),
+        );
+    }
+
+    let mgr = get_token_manager().await;
+    let mut tokens = serde_json::Map::new();
+    for reference in &data.tokens {
+        if let Some(info) = mgr.get_info(&mgr.resolve(reference)) {
+            tokens.insert(
+                mask_token(&info.token),
+                json!({"token": info.token, "cf_clearance": info.cf_clearance}),
+            );
+        }
+    }
+    let cfg = get_all_config().await;
+    let mut config = serde_json::Map::new();
+    for key in &data.keys {
+        let value = path::parse(key).and_then(|p| path::lookup(&cfg, &p).cloned());
+        config.insert(key.clone(), value.unwrap_or(JsonValue::Null));
+    }
+
+    tracing::warn!(
+        "[Audit] {} revealed secrets: tokens=[{}] keys=[{}]",
+        admin_actor(&headers),
+        tokens.keys().cloned().collect::<Vec<_>>().join(","),
+        data.keys.join(",")
+    );
+    Ok(Json(json!({"status": "success", "tokens": tokens, "config": config})).into_response())
+}
+
+async fn proxies_response(checked: Option<usize>) -> Result<Response, ApiError> {
+    let strategy: String = crate::core::config::get_config("proxy.strategy", String::new()).await;
+    let proxies = get_proxy_pool().await.status();
+    let mut body = json!({"status": "success", "strategy": strategy, "proxies": proxies});
+    if let Some(passed) = checked {
+        body["passed"] = json!(passed);
+    }
+    Ok(Json(body).into_response())
+}
+
+async fn get_proxies_api(headers: HeaderMap) -> Result<Response, ApiError> {
+    verify_api_key(&headers).await?;
+    proxies_response(None).await
+}
+
+async fn check_proxies_api(headers: HeaderMap) -> Result<Response, ApiError> {
+    verify_api_key(&headers).await?;
+    let passed = probe_all().await;
+    proxies_response(Some(passed)).await
+}
+
+#[derive(Debug, Deserialize)]
+struct ProxyResetRequest {
+    url: String,
+}
+
+async fn reset_proxy_api(
+    headers: HeaderMap,
+    Json(data): Json<ProxyResetRequest>,
+) -> Result<Response, ApiError> {
+    verify_api_key(&headers).await?;
+    if !get_proxy_pool().await.reset(data.url.trim()) {
+        return Err(ApiError::not_found("Proxy not found").with_code("proxy_not_found"));
+    }
+    proxies_response(None).await
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenImportRequest {
+    content: String,
+    pool: Option<String>,
+    validate: Option<bool>,
+    remove_invalid: Option<bool>,
+}
+
+async fn import_tokens_api(
+    headers: HeaderMap,
+    Json(data): Json<TokenImportRequest>,
+) -> Result<Response, ApiError> {
+    verify_api_key(&headers).await?;
+    let default_pool = data
+        .pool
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .unwrap_or_else(|| "ssoBasic".to_string());
+    let parsed = parse_import(&data.content);
+    if parsed.tokens.is_empty() && parsed.invalid.is_empty() {
+        return Err(ApiError::invalid_request("No tokens provided"));
+    }
+
+    let mgr = get_token_manager().await;
+    let mut results = Vec::new();
+    let mut added = Vec::new();
+    let mut duplicate = 0;
+    for entry in parsed.tokens {
+        let pool = entry.pool.clone().unwrap_or_else(|| default_pool.clone());
+        let masked = mask_token(&entry.token);
+        if mgr.add(&entry.token, &pool).await.is_none() {
+            duplicate += 1;
+            results.push(json!({"token": masked, "pool": pool, "result": "duplicate"}));
+            continue;
+        }
+        if entry.note.is_some() || !entry.tags.is_empty() {
+            mgr.update_token(&entry.token, |t| {
+                if let Some(note) = entry.note {
+                    t.note = note;
+                }
+                t.tags = entry.tags;
+            })
+            .await;
+        }
+        results.push(json!({"token": masked, "pool": pool, "result": "added"}));
+        added.push(entry.token);
+    }
+    for input in &parsed.invalid {
+        results.push(json!({"input": input, "result": "invalid"}));
+    }
+
+    let mut response = json!({
+        "status": "success",
+        "summary": {
+            "added": added.len(),
+            "duplicate": duplicate,
+            "invalid": parsed.invalid.len(),
+        },
+        "results": results,
+    });
+    if !added.is_empty() {
+        tokio::spawn(async {
+            detect_pending_tiers().await;
+            enable_pending_nsfw().await;
+        });
+        if data.validate.unwrap_or(false) {
+            let task_id =
+                spawn_import_validation(added, data.remove_invalid.unwrap_or(false)).await;
+            response["task_id"] = JsonValue::String(task_id);
+        }
+    }
+    Ok(Json(response).into_response())
+}
+
+async fn spawn_import_validation(tokens: Vec<String>, remove_invalid: bool) -> String {
+    let max_concurrent: usize =
+        crate::core::config::get_config("performance.usage_max_concurrent", 25usize).await;
+    let batch_size: usize =
+        crate::core::config::get_config("performance.usage_batch_size", 50usize).await;
+
+    let task = create_task(tokens.len()).await;
+    let task_id = task.lock().await.id.clone();
+    let task_for_on_item = task.clone();
+    let on_item: OnItem = std::sync::Arc::new(move |token, ok| {
+        let task = task_for_on_item.clone();
+        Box::pin(async move {
+            let item = json!({"token": mask_token(&token)});
+            task.lock().await.record(ok, Some(item), None, None);
+        })
+    });
+
+    let task_for_spawn = task.clone();
+    let task_id_for_spawn = task_id.clone();
+    tokio::spawn(async move {
+        let results = run_in_batches(
+            tokens,
+            move |token| async move {
+                let mgr = get_token_manager().await;
+                let outcome = mgr.try_sync_usage(&token, "grok-3", false).await;
+                // Only tokens the upstream rejected are deleted; a timeout or
+                // 5xx says nothing about the token itself.
+                let rejected = outcome
+                    .as_ref()
+                    .is_err_and(|err| ErrorClass::from_error(err) == ErrorClass::Auth);
+                if rejected && remove_invalid {
+                    mgr.remove(&token).await;
+                }
+                Ok((outcome.unwrap_or(false), rejected))
+            },
+            max_concurrent,
+            batch_size,
+            Some(on_item),
+            None,
+        )
+        .await;
+
+        if task_for_spawn.lock().await.cancelled {
+            task_for_spawn.lock().await.finish_cancelled();
+            return;
+        }
+
+        let mut results_out = HashMap::new();
+        let mut ok_count = 0;
+        let mut fail_count = 0;
+        for (token, res) in results {
+            let outcome = match res {
+                Ok((true, _)) => {
+                    ok_count += 1;
+                    "valid"
+                }
+                Ok((_, true)) if remove_invalid => {
+                    fail_count += 1;
+                    "removed"
+                }
+                Ok((_, true)) => {
+                    fail_count += 1;
+                    "invalid"
+                }
+                _ => {
+                    fail_count += 1;
+                    "unverified"
+                }
+            };
+            results_out.insert(mask_token(&token), outcome);
+        }
+        let result = json!({
+            "status": "success",
+            "summary": {"total": ok_count + fail_count, "ok": ok_count, "fail": fail_count},
+            "results": results_out,
+        });
+        task_for_spawn.lock().await.finish(result, None);
+        tokio::spawn(expire_task(task_id_for_spawn, 300));
+    });
+    task_id
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenExportQuery {
+    format: Option<String>,
+    mask: Option<bool>,
+    pool: Option<String>,
+}
+
+async fn export_tokens_api(
+    headers: HeaderMap,
+    Query(query): Query<TokenExportQuery>,
+) -> Result<Response, ApiError> {
+    verify_api_key(&headers).await?;
+    let mask = query.mask.unwrap_or(true);
+    if !mask {
+        tracing::warn!(
+            "[Audit] {} exported raw tokens (pool={})",
+            admin_actor(&headers),
+            query.pool.as_deref().unwrap_or("*")
+        );
+    }
+    let mgr = get_token_manager().await;
+    let mut pools: Vec<(String, Vec<TokenInfo>)> = mgr
+        .pools()
+        .iter()
+        .filter(|(name, _)| query.pool.as_ref().is_none_or(|p| p == *name))
+        .map(|(name, pool)| (name.clone(), pool.list()))
+        .collect();
+    pools.sort_by(|a, b| a.0.cmp(&b.0));
+
+    match query.format.as_deref().unwrap_or("json") {
+        "csv" => {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                header::CONTENT_TYPE,
+                "text/csv; charset=utf-8".parse().unwrap(),
+            );
+            headers.insert(
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"tokens.csv\"".parse().unwrap(),
+            );
+            Ok((headers, export_csv(&pools, mask)).into_response())
+        }
+        "json" => Ok(Json(export_json(&pools, mask)).into_response()),
+        _ => Err(ApiError::invalid_request("format must be json or csv").with_param("format")),
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct TokenRefreshRequest {
     token: Option<String>,
@@ -176,7 +1045,6 @@ async fn refresh_tokens_api(
 ) -> Result<Response, ApiError> {
     verify_api_key(&headers).await?;
     let mgr = get_token_manager().await;
-    let mut mgr = mgr.lock().await;
 
     let mut tokens = Vec::new();
     if let Some(t) = data.token {
@@ -190,7 +1058,7 @@ async fn refresh_tokens_api(
     }
     tokens = tokens
         .into_iter()
-        .map(|t| t.trim().to_string())
+        .map(|t| mgr.resolve(&t))
         .filter(|t| !t.is_empty())
         .collect();
     tokens.dedup();
@@ -214,7 +1082,7 @@ async fn refresh_tokens_api(
                 false,
             )
             .await;
-        out.insert(token.clone(), ok);
+        out.insert(mask_token(token), ok);
     }
 
     let mut response = json!({"status": "success", "results": out});
@@ -245,7 +1113,7 @@ async fn refresh_tokens_api_async(
     }
     tokens = tokens
         .into_iter()
-        .map(|t| t.trim().to_string())
+        .map(|t| mgr.resolve(&t))
         .filter(|t| !t.is_empty())
         .collect();
     tokens.dedup();
@@ -287,7 +1155,6 @@ async fn refresh_tokens_api_async(
             move |token| {
                 let mgr = mgr.clone();
                 async move {
-                    let mut mgr = mgr.lock().await;
                     let ok = mgr
                         .sync_usage(
                             &token,
@@ -319,11 +1186,11 @@ async fn refresh_tokens_api_async(
             match res {
                 Ok(true) => {
                     ok_count += 1;
-                    results_out.insert(token, true);
+                    results_out.insert(mask_token(&token), true);
                 }
                 _ => {
                     fail_count += 1;
-                    results_out.insert(token, false);
+                    results_out.insert(mask_token(&token), false);
                 }
             }
         }
@@ -370,8 +1237,7 @@ async fn enable_nsfw_api(
         tokens.extend(list);
     }
     if tokens.is_empty() {
-        let mgr_guard = mgr.lock().await;
-        for pool in mgr_guard.pools.values() {
+        for pool in mgr.pools().values() {
             for info in pool.list() {
                 tokens.push(info.token.clone());
             }
@@ -382,7 +1248,7 @@ async fn enable_nsfw_api(
     }
     tokens = tokens
         .into_iter()
-        .map(|t| t.trim().to_string())
+        .map(|t| mgr.resolve(&t))
         .filter(|t| !t.is_empty())
         .collect();
     tokens.dedup();
@@ -404,20 +1270,7 @@ async fn enable_nsfw_api(
         tokens.clone(),
         move |token| {
             let service = service.clone();
-            let mgr = mgr.clone();
-            async move {
-                let result = service.enable(&token).await;
-                if result.success {
-                    mgr.lock().await.add_tag(&token, "nsfw").await;
-                }
-                Ok(json!({
-                    "success": result.success,
-                    "http_status": result.http_status,
-                    "grpc_status": result.grpc_status,
-                    "grpc_message": result.grpc_message,
-                    "error": result.error,
-                }))
-            }
+            async move { Ok(enable_and_record(&service, &token).await) }
         },
         max_concurrent,
         batch_size,
@@ -430,11 +1283,7 @@ async fn enable_nsfw_api(
     let mut ok_count = 0;
     let mut fail_count = 0;
     for (token, res) in results {
-        let masked = if token.len() > 20 {
-            format!("{}...{}", &token[..8], &token[token.len() - 8..])
-        } else {
-            token.clone()
-        };
+        let masked = mask_token(&token);
         match res {
             Ok(data) => {
                 if data.get("success").and_then(|v| v.as_bool()) == Some(true) {
@@ -464,6 +1313,28 @@ async fn enable_nsfw_api(
     Ok(Json(response).into_response())
 }
 
+async fn verify_nsfw_api(
+    headers: HeaderMap,
+    Json(data): Json<TokenEditRequest>,
+) -> Result<Response, ApiError> {
+    verify_api_key(&headers).await?;
+    let mgr = get_token_manager().await;
+    let token = mgr.resolve(&data.token);
+    let Some(mut status) = mgr.get_info(&token).map(|t| t.imagine.nsfw) else {
+        return Err(ApiError::not_found("Token not found").with_code("token_not_found"));
+    };
+    let verified = NsfwService::new().await.verify(&token).await?;
+    let now = chrono::Utc::now().timestamp_millis();
+    status.enabled = verified;
+    status.verified = verified;
+    status.verified_at = Some(now);
+    if verified {
+        status.error = None;
+    }
+    mgr.record_nsfw(&token, status, false).await;
+    token_response(mgr.get_info(&token))
+}
+
 async fn enable_nsfw_api_async(
     headers: HeaderMap,
     Json(data): Json<NsfwRequest>,
@@ -480,8 +1351,7 @@ async fn enable_nsfw_api_async(
         tokens.extend(list);
     }
     if tokens.is_empty() {
-        let mgr_guard = mgr.lock().await;
-        for pool in mgr_guard.pools.values() {
+        for pool in mgr.pools().values() {
             for info in pool.list() {
                 tokens.push(info.token.clone());
             }
@@ -492,7 +1362,7 @@ async fn enable_nsfw_api_async(
     }
     tokens = tokens
         .into_iter()
-        .map(|t| t.trim().to_string())
+        .map(|t| mgr.resolve(&t))
         .filter(|t| !t.is_empty())
         .collect();
     tokens.dedup();
@@ -529,20 +1399,7 @@ async fn enable_nsfw_api_async(
             tokens_for_spawn.clone(),
             move |token| {
                 let service = service.clone();
-                let mgr = mgr.clone();
-                async move {
-                    let result = service.enable(&token).await;
-                    if result.success {
-                        mgr.lock().await.add_tag(&token, "nsfw").await;
-                    }
-                    Ok(json!({
-                        "success": result.success,
-                        "http_status": result.http_status,
-                        "grpc_status": result.grpc_status,
-                        "grpc_message": result.grpc_message,
-                        "error": result.error,
-                    }))
-                }
+                async move { Ok(enable_and_record(&service, &token).await) }
             },
             max_concurrent,
             batch_size,
@@ -560,11 +1417,7 @@ async fn enable_nsfw_api_async(
         let mut ok_count = 0;
         let mut fail_count = 0;
         for (token, res) in results {
-            let masked = if token.len() > 20 {
-                format!("{}...{}", &token[..8], &token[token.len() - 8..])
-            } else {
-                token.clone()
-            };
+            let masked = mask_token(&token);
             match res {
                 Ok(data) => {
                     if data.get("success").and_then(|v| v.as_bool()) == Some(true) {
@@ -628,18 +1481,14 @@ async fn get_cache_stats_api(
     let video_stats = dl.get_stats("video");
 
     let mgr = get_token_manager().await;
-    let mgr_guard = mgr.lock().await;
     let mut accounts = Vec::new();
-    for (pool_name, pool) in &mgr_guard.pools {
+    let mut all_tokens = Vec::new();
+    for (pool_name, pool) in mgr.pools().iter() {
         for info in pool.list() {
-            let raw = info.token.clone();
-            let masked = if raw.len() > 24 {
-                format!("{}...{}", &raw[..8], &raw[raw.len() - 16..])
-            } else {
-                raw.clone()
-            };
+            let masked = mask_token(&info.token);
+            all_tokens.push(info.token.clone());
             accounts.push(json!({
-                "token": raw,
+                "token": masked,
                 "token_masked": masked,
                 "pool": pool_name,
                 "status": format!("{:?}", info.status).to_lowercase(),
@@ -652,12 +1501,12 @@ async fn get_cache_stats_api(
     if let Some(tokens) = &query.tokens {
         selected_tokens = tokens
             .split(',')
-            .map(|t| t.trim().to_string())
+            .map(|t| mgr.resolve(t))
             .filter(|t| !t.is_empty())
             .collect();
     }
     let scope = query.scope.clone();
-    let selected_token = query.token.clone();
+    let selected_token = query.token.as_deref().map(|t| mgr.resolve(t));
 
     let mut online_stats =
         json!({"count":0,"status":"unknown","token":null,"last_asset_clear_at":null});
@@ -681,26 +1530,20 @@ async fn get_cache_stats_api(
             match list_service.count(token).await {
                 Ok(count) => {
                     total += count;
-                    online_details.push(json!({"token": token, "token_masked": token, "count": count, "status": "ok"}));
+                    let masked = mask_token(token);
+                    online_details.push(json!({"token": masked, "token_masked": masked, "count": count, "status": "ok"}));
                 }
                 Err(e) => {
-                    online_details.push(json!({"token": token, "token_masked": token, "count": 0, "status": format!("error: {e}")}));
+                    let masked = mask_token(token);
+                    online_details.push(json!({"token": masked, "token_masked": masked, "count": 0, "status": format!("error: {e}")}));
                 }
             }
         }
         online_stats =
             json!({"count": total, "status": "ok", "token": null, "last_asset_clear_at": null});
     } else if scope.as_deref() == Some("all") {
-        let tokens: Vec<String> = accounts
-            .iter()
-            .filter_map(|a| {
-                a.get("token")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string())
-            })
-            .collect();
+        let mut tokens = all_tokens;
         original_count = tokens.len();
-        let mut tokens = tokens;
         if tokens.len() > max_tokens {
             tokens.truncate(max_tokens);
             truncated = true;
@@ -711,10 +1554,12 @@ async fn get_cache_stats_api(
             match list_service.count(token).await {
                 Ok(count) => {
                     total += count;
-                    online_details.push(json!({"token": token, "token_masked": token, "count": count, "status": "ok"}));
+                    let masked = mask_token(token);
+                    online_details.push(json!({"token": masked, "token_masked": masked, "count": count, "status": "ok"}));
                 }
                 Err(e) => {
-                    online_details.push(json!({"token": token, "token_masked": token, "count": 0, "status": format!("error: {e}")}));
+                    let masked = mask_token(token);
+                    online_details.push(json!({"token": masked, "token_masked": masked, "count": 0, "status": format!("error: {e}")}));
                 }
             }
         }
@@ -723,10 +1568,10 @@ async fn get_cache_stats_api(
         let list_service = ListService::new().await;
         match list_service.count(&token).await {
             Ok(count) => {
-                online_stats = json!({"count": count, "status": "ok", "token": token});
+                online_stats = json!({"count": count, "status": "ok", "token": mask_token(&token)});
             }
             Err(e) => {
-                online_stats = json!({"count": 0, "status": format!("error: {e}"), "token": token});
+                online_stats = json!({"count": 0, "status": format!("error: {e}"), "token": mask_token(&token)});
             }
         }
     } else {
@@ -820,13 +1665,12 @@ async fn clear_online_cache_api(
 ) -> Result<Response, ApiError> {
     verify_api_key(&headers).await?;
     let mgr = get_token_manager().await;
-    let mut mgr = mgr.lock().await;
     let service = DeleteService::new().await;
 
     if let Some(tokens) = data.tokens {
         let mut token_list = tokens
             .into_iter()
-            .map(|t| t.trim().to_string())
+            .map(|t| mgr.resolve(&t))
             .filter(|t| !t.is_empty())
             .collect::<Vec<_>>();
         if token_list.is_empty() {
@@ -847,13 +1691,13 @@ async fn clear_online_cache_api(
                 Ok(result) => {
                     mgr.mark_asset_clear(token).await;
                     results.insert(
-                        token.clone(),
+                        mask_token(token),
                         json!({"status": "success", "result": result}),
                     );
                 }
                 Err(e) => {
                     results.insert(
-                        token.clone(),
+                        mask_token(token),
                         json!({"status": "error", "error": e.to_string()}),
                     );
                 }
@@ -868,7 +1712,10 @@ async fn clear_online_cache_api(
         return Ok(Json(response).into_response());
     }
 
-    let token = data.token.or_else(|| mgr.get_token("ssoBasic"));
+    let token = data
+        .token
+        .map(|t| mgr.resolve(&t))
+        .or_else(|| mgr.get_token("ssoBasic", &TagFilter::default(), SelectionStrategy::Quota));
     let token =
         token.ok_or_else(|| ApiError::invalid_request("No available token to perform cleanup"))?;
     let result = service
@@ -892,7 +1739,7 @@ async fn clear_online_cache_api_async(
         .ok_or_else(|| ApiError::invalid_request("No tokens provided"))?;
     let mut token_list = tokens
         .into_iter()
-        .map(|t| t.trim().to_string())
+        .map(|t| mgr.resolve(&t))
         .filter(|t| !t.is_empty())
         .collect::<Vec<_>>();
     if token_list.is_empty() {
@@ -933,7 +1780,7 @@ async fn clear_online_cache_api_async(
                 async move {
                     match service.delete_all(&token).await {
                         Ok(result) => {
-                            mgr.lock().await.mark_asset_clear(&token).await;
+                            mgr.mark_asset_clear(&token).await;
                             Ok(json!({"status": "success", "result": result}))
                         }
                         Err(e) => Ok(json!({"status": "error", "error": e.to_string()})),
@@ -962,7 +1809,7 @@ async fn clear_online_cache_api_async(
                 } else {
                     fail_count += 1;
                 }
-                out.insert(token, data);
+                out.insert(mask_token(&token), data);
             } else {
                 fail_count += 1;
             }
@@ -995,20 +1842,15 @@ async fn load_online_cache_api_async(
 ) -> Result<Response, ApiError> {
     verify_api_key(&headers).await?;
     let mgr = get_token_manager().await;
-    let mgr_guard = mgr.lock().await;
     let mut accounts = Vec::new();
-    for (pool_name, pool) in &mgr_guard.pools {
+    let mut all_tokens = Vec::new();
+    for (pool_name, pool) in mgr.pools().iter() {
         for info in pool.list() {
-            let raw = info.token.clone();
-            let masked = if raw.len() > 24 {
-                format!("{}...{}", &raw[..8], &raw[raw.len() - 16..])
-            } else {
-                raw.clone()
-            };
-            accounts.push(json!({"token": raw, "token_masked": masked, "pool": pool_name, "status": format!("{:?}", info.status).to_lowercase(), "last_asset_clear_at": info.last_asset_clear_at}));
+            let masked = mask_token(&info.token);
+            all_tokens.push(info.token.clone());
+            accounts.push(json!({"token": masked, "token_masked": masked, "pool": pool_name, "status": format!("{:?}", info.status).to_lowercase(), "last_asset_clear_at": info.last_asset_clear_at}));
         }
     }
-    drop(mgr_guard);
 
     let mut tokens = Vec::new();
     let mut scope = data.scope.unwrap_or_else(|| "none".to_string());
@@ -1016,14 +1858,7 @@ async fn load_online_cache_api_async(
         tokens = list;
     }
     if tokens.is_empty() && scope == "all" {
-        tokens = accounts
-            .iter()
-            .filter_map(|a| {
-                a.get("token")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string())
-            })
-            .collect();
+        tokens = all_tokens;
         scope = "all".to_string();
     } else if !tokens.is_empty() {
         scope = "selected".to_string();
@@ -1032,7 +1867,7 @@ async fn load_online_cache_api_async(
     }
     tokens = tokens
         .into_iter()
-        .map(|t| t.trim().to_string())
+        .map(|t| mgr.resolve(&t))
         .filter(|t| !t.is_empty())
         .collect();
     tokens.dedup();
@@ -1072,10 +1907,12 @@ async fn load_online_cache_api_async(
                 let list_service = list_service.clone();
                 async move {
                     match list_service.count(&token).await {
-                        Ok(count) => Ok(json!({"token": token, "count": count, "status": "ok"})),
-                        Err(e) => {
-                            Ok(json!({"token": token, "count": 0, "status": format!("error: {e}")}))
+                        Ok(count) => {
+                            Ok(json!({"token": mask_token(&token), "count": count, "status": "ok"}))
                         }
+                        Err(e) => Ok(
+                            json!({"token": mask_token(&token), "count": 0, "status": format!("error: {e}")}),
+                        ),
                     }
                 }
             },
@@ -6,7 +6,7 @@ use futures::StreamExt;
 use serde::Deserialize;
 use serde_json::{Value as JsonValue, json};
 
-use crate::core::auth::verify_api_key;
+use crate::core::auth::verify_inference_key;
 use crate::core::config::get_config;
 use crate::core::exceptions::ApiError;
 use crate::services::grok::chat::{ChatResult, ChatService};
@@ -35,6 +35,7 @@ pub struct ChatCompletionRequest {
     pub stream: Option<bool>,
     pub thinking: Option<String>,
     pub video_config: Option<VideoConfig>,
+    pub user: Option<String>,
 }
 
 pub fn router() -> Router {
@@ -164,12 +165,14 @@ async fn chat_completions(
     headers: HeaderMap,
     Json(req): Json<ChatCompletionRequest>,
 ) -> Result<Response, ApiError> {
-    verify_api_key(&headers).await?;
+    verify_inference_key(&headers).await?;
     let enabled: bool = get_config("downstream.enable_chat_completions", true).await;
     if !enabled {
         return Err(ApiError::not_found("Endpoint disabled"));
     }
     validate_request(&req)?;
+    let mut route = TokenService::route_from_headers(&headers).await;
+    TokenService::bind_session(&mut route, req.user.as_deref(), &req.messages);
 
     let model_info =
         ModelService::get(&req.model).ok_or_else(|| ApiError::invalid_request("Invalid model"))?;
@@ -189,6 +192,7 @@ async fn chat_completions(
             vconf.video_length.unwrap_or(6),
             vconf.resolution.as_deref().unwrap_or("SD"),
             vconf.preset.as_deref().unwrap_or("custom"),
+            &route,
         )
         .await?;
 
@@ -199,6 +203,7 @@ async fn chat_completions(
                 model,
                 think,
                 is_stream,
+                started_at,
             } => {
                 if is_stream {
                     let processor = VideoStreamProcessor::new(&model, &token, think).await;
@@ -213,7 +218,7 @@ async fn chat_completions(
                         while let Some(item) = inner.as_mut().next().await {
                             yield item;
                         }
-                        let _ = TokenService::consume(&token_clone, effort).await;
+                        let _ = TokenService::finish(&token_clone, effort, started_at).await;
                     };
                     let mut headers = HeaderMap::new();
                     headers.insert("Cache-Control", "no-cache".parse().unwrap());
@@ -228,7 +233,7 @@ async fn chat_completions(
                     } else {
                         EffortType::Low
                     };
-                    let _ = TokenService::consume(&token, effort).await;
+                    let _ = TokenService::finish(&token, effort, started_at).await;
                     Ok((StatusCode::OK, Json(result)).into_response())
                 }
             }
@@ -240,6 +245,7 @@ async fn chat_completions(
             req.messages.clone(),
             req.stream,
             req.thinking.clone(),
+            &route,
         )
         .await?;
         match result {
@@ -249,6 +255,7 @@ async fn chat_completions(
                 model,
                 is_stream,
                 think,
+                started_at,
             } => {
                 if is_stream {
                     let processor = StreamProcessor::new(&model, &token, think).await;
@@ -263,7 +270,7 @@ async fn chat_completions(
                         while let Some(item) = inner.as_mut().next().await {
                             yield item;
                         }
-                        let _ = TokenService::consume(&token_clone, effort).await;
+                        let _ = TokenService::finish(&token_clone, effort, started_at).await;
                     };
                     let mut headers = HeaderMap::new();
                     headers.insert("Cache-Control", "no-cache".parse().unwrap());
@@ -278,7 +285,7 @@ async fn chat_completions(
                     } else {
                         EffortType::Low
                     };
-                    let _ = TokenService::consume(&token, effort).await;
+                    let _ = TokenService::finish(&token, effort, started_at).await;
                     Ok((StatusCode::OK, Json(result)).into_response())
                 }
             }
@@ -1,4 +1,5 @@
 use std::convert::Infallible;
+use std::time::Instant;
 
 use async_stream::stream;
 use axum::http::{HeaderMap, StatusCode};
@@ -12,7 +13,7 @@ use serde::Deserialize;
 use serde_json::{Value as JsonValue, json};
 use tokio::sync::mpsc;
 
-use crate::core::auth::verify_api_key;
+use crate::core::auth::verify_inference_key;
 use crate::core::config::get_config;
 use crate::core::exceptions::ApiError;
 use crate::services::grok::chat::GrokChatService;
@@ -31,6 +32,7 @@ pub struct ImageRequest {
     pub response_format: Option<String>,
     pub style: Option<String>,
     pub stream: Option<bool>,
+    pub user: Option<String>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -63,7 +65,7 @@ async fn create_image(
     headers: HeaderMap,
     Json(req): Json<ImageRequest>,
 ) -> Result<Response, ApiError> {
-    verify_api_key(&headers).await?;
+    verify_inference_key(&headers).await?;
     let enabled: bool = get_config("downstream.enable_images", true).await;
     if !enabled {
         return Err(ApiError::not_found("Endpoint disabled"));
@@ -96,13 +98,23 @@ async fn create_image(
         );
     }
 
-    let token = TokenService::get_token_for_model(&model_id).await?;
+    let mut route = TokenService::route_from_headers(&headers).await;
+    TokenService::bind_session(&mut route, req.user.as_deref(), &[]);
+    let token = TokenService::get_token_for_model(&model_id, &route).await?;
 
     if stream {
         let processor =
             ImageStreamProcessor::new(&model_id, &token, n as usize, output_format.is_base64())
                 .await;
-        let response = call_grok_image(&token, &req.prompt, &model_info).await?;
+        let started_at = Instant::now();
+        let response = match call_grok_image(&token, &req.prompt, &model_info).await {
+            Ok(response) => response,
+            Err(err) => {
+                TokenService::record_error(&token, &err).await;
+                return Err(err);
+            }
+        };
+        TokenService::record_ttfb(&token, started_at.elapsed()).await;
         let effort = if model_info.cost == Cost::High {
             EffortType::High
         } else {
@@ -114,7 +126,7 @@ async fn create_image(
             while let Some(item) = inner.as_mut().next().await {
                 yield item;
             }
-            let _ = TokenService::consume(&token_clone, effort).await;
+            let _ = TokenService::finish(&token_clone, effort, started_at).await;
         };
         let mut headers = HeaderMap::new();
         headers.insert("Cache-Control", "no-cache".parse().unwrap());
@@ -130,15 +142,19 @@ async fn create_image(
         EffortType::Low
     };
     let mut all_images: Vec<JsonValue> = Vec::new();
+    let started_at = Instant::now();
 
     if calls_needed == 1 {
         match call_grok_images_once(&token, &req.prompt, &model_info, output_format.is_base64())
             .await
         {
             Ok(images) => all_images.extend(images),
-            Err(err) => tracing::error!("Grok image call failed: {err}"),
+            Err(err) => {
+                tracing::error!("Grok image call failed: {err}");
+                TokenService::record_error(&token, &err).await;
+            }
         }
-        let _ = TokenService::consume(&token, effort.clone()).await;
+        let _ = TokenService::finish(&token, effort.clone(), started_at).await;
     } else {
         let tasks = (0..calls_needed)
             .map(|_| {
@@ -149,9 +165,12 @@ async fn create_image(
         for result in results {
             match result {
                 Ok(images) => all_images.extend(images),
-                Err(err) => tracing::error!("Concurrent image call failed: {err}"),
+                Err(err) => {
+                    tracing::error!("Concurrent image call failed: {err}");
+                    TokenService::record_error(&token, &err).await;
+                }
             }
-            let _ = TokenService::consume(&token, effort.clone()).await;
+            let _ = TokenService::finish(&token, effort.clone(), started_at).await;
         }
     }
 
@@ -179,7 +198,7 @@ async fn create_image_nsfw(
     headers: HeaderMap,
     Json(req): Json<ImageRequest>,
 ) -> Result<Response, ApiError> {
-    verify_api_key(&headers).await?;
+    verify_inference_key(&headers).await?;
 
     let enabled: bool = get_config("downstream.enable_images", true).await;
     if !enabled {
@@ -200,6 +219,10 @@ async fn create_image_nsfw(
         }
     }
 
+    let model_id = req
+        .model
+        .clone()
+        .unwrap_or_else(|| "grok-imagine-1.0".to_string());
     let stream = req.stream.unwrap_or(false);
     let response_format = req.response_format.unwrap_or_else(|| "url".to_string());
     let return_base64 = response_format == "b64_json";
@@ -211,7 +234,7 @@ async fn create_image_nsfw(
         let n = req.n;
 
         let task = tokio::spawn(async move {
-            imagine_nsfw::generate(&prompt, size.as_deref(), n, Some(tx)).await
+            imagine_nsfw::generate(&model_id, &prompt, size.as_deref(), n, Some(tx)).await
         });
 
         let body_stream = stream! {
@@ -256,7 +279,8 @@ async fn create_image_nsfw(
         return Ok((headers, axum::body::Body::from_stream(body_stream)).into_response());
     }
 
-    let result = imagine_nsfw::generate(&req.prompt, req.size.as_deref(), req.n, None).await;
+    let result =
+        imagine_nsfw::generate(&model_id, &req.prompt, req.size.as_deref(), req.n, None).await;
     if !result.success {
         let msg = result
             .error
@@ -299,8 +323,7 @@ async fn call_grok_image(
         .chat(
             token,
             &format!("Image Generation:{prompt}"),
-            &model_info.grok_model,
-            &model_info.model_mode,
+            model_info,
             Some(false),
             true,
             &[],
@@ -315,7 +338,9 @@ async fn call_grok_images_once(
     model_info: &ModelInfo,
     return_base64: bool,
 ) -> Result<Vec<JsonValue>, ApiError> {
+    let started_at = Instant::now();
     let response = call_grok_image(token, prompt, model_info).await?;
+    TokenService::record_ttfb(token, started_at.elapsed()).await;
     let processor = ImageCollectProcessor::new(&model_info.model_id, token, return_base64).await;
     Ok(processor.process(response).await)
 }
@@ -8,8 +8,8 @@ use serde::Deserialize;
 use serde_json::{Value as JsonValue, json};
 use std::convert::Infallible;
 
-use crate::core::auth::verify_api_key;
-use crate::core::config::get_config;
+use crate::core::auth::verify_inference_key;
+use crate::core::config::{get_config, get_model_config};
 use crate::core::exceptions::ApiError;
 use crate::services::grok::chat::{ChatResult, ChatService};
 use crate::services::grok::media::{VideoResult, VideoService};
@@ -35,6 +35,7 @@ pub struct ResponsesRequest {
     pub stream: Option<bool>,
     pub thinking: Option<String>,
     pub video_config: Option<VideoConfig>,
+    pub user: Option<String>,
 }
 
 pub fn router() -> Router {
@@ -162,7 +163,7 @@ async fn responses(
     headers: HeaderMap,
     Json(req): Json<ResponsesRequest>,
 ) -> Result<Response, ApiError> {
-    verify_api_key(&headers).await?;
+    verify_inference_key(&headers).await?;
     let enabled: bool = get_config("downstream.enable_responses", true).await;
     if !enabled {
         return Err(ApiError::not_found("Endpoint disabled"));
@@ -176,10 +177,12 @@ async fn responses(
         .with_code("model_not_found")
     })?;
     let messages = build_messages(&req)?;
+    let mut route = TokenService::route_from_headers(&headers).await;
+    TokenService::bind_session(&mut route, req.user.as_deref(), &messages);
 
     let stream = match req.stream {
         Some(value) => value,
-        None => get_config("grok.stream", true).await,
+        None => get_model_config(&req.model, "stream", true).await,
     };
     if model_info.is_video {
         let vconf = req.video_config.unwrap_or(VideoConfig {
@@ -197,6 +200,7 @@ async fn responses(
             vconf.video_length.unwrap_or(6),
             vconf.resolution.as_deref().unwrap_or("SD"),
             vconf.preset.as_deref().unwrap_or("custom"),
+            &route,
         )
         .await?;
 
@@ -207,6 +211,7 @@ async fn responses(
                 model,
                 think,
                 is_stream,
+                started_at,
             } => {
                 if is_stream {
                     let processor = VideoStreamProcessor::new(&model, &token, think).await;
@@ -298,7 +303,7 @@ async fn responses(
                         });
                         yield sse_ok(format!("data: {}\n\n", completed_evt));
                         yield sse_ok("data: [DONE]\n\n".to_string());
-                        let _ = TokenService::consume(&token_clone, effort).await;
+                        let _ = TokenService::finish(&token_clone, effort, started_at).await;
                     };
                     let mut headers = HeaderMap::new();
                     headers.insert("Cache-Control", "no-cache".parse().unwrap());
@@ -313,7 +318,7 @@ async fn responses(
                     } else {
                         EffortType::Low
                     };
-                    let _ = TokenService::consume(&token, effort).await;
+                    let _ = TokenService::finish(&token, effort, started_at).await;
                     let content = result
                         .get("choices")
                         .and_then(|v| v.get(0))
@@ -332,9 +337,14 @@ async fn responses(
             VideoResult::Json(json) => Ok((StatusCode::OK, Json(json)).into_response()),
         }
     } else {
-        let result =
-            ChatService::completions(&req.model, messages, Some(stream), req.thinking.clone())
-                .await?;
+        let result = ChatService::completions(
+            &req.model,
+            messages,
+            Some(stream),
+            req.thinking.clone(),
+            &route,
+        )
+        .await?;
         match result {
             ChatResult::Stream {
                 stream: line_stream,
@@ -342,6 +352,7 @@ async fn responses(
                 model,
                 is_stream,
                 think,
+                started_at,
             } => {
                 if is_stream {
                     let processor = StreamProcessor::new(&model, &token, think).await;
@@ -433,7 +444,7 @@ async fn responses(
                         });
                         yield sse_ok(format!("data: {}\n\n", completed_evt));
                         yield sse_ok("data: [DONE]\n\n".to_string());
-                        let _ = TokenService::consume(&token_clone, effort).await;
+                        let _ = TokenService::finish(&token_clone, effort, started_at).await;
                     };
                     let mut headers = HeaderMap::new();
                     headers.insert("Cache-Control", "no-cache".parse().unwrap());
@@ -448,7 +459,7 @@ async fn responses(
                     } else {
                         EffortType::Low
                     };
-                    let _ = TokenService::consume(&token, effort).await;
+                    let _ = TokenService::finish(&token, effort, started_at).await;
                     let content = result
                         .get("choices")
                         .and_then(|v| v.get(0))
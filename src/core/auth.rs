@@ -2,8 +2,9 @@ use axum::http::HeaderMap;
 
 use crate::core::config::get_config;
 use crate::core::exceptions::ApiError;
+use crate::core::redact::fingerprint;
 
-fn extract_bearer(headers: &HeaderMap) -> Option<String> {
+pub fn extract_bearer(headers: &HeaderMap) -> Option<String> {
     let auth = headers
         .get(axum::http::header::AUTHORIZATION)?
         .to_str()
@@ -14,6 +15,35 @@ fn extract_bearer(headers: &HeaderMap) -> Option<String> {
     None
 }
 
+/// Who made an admin change, for audit records: the fingerprint of the
+/// bearer credential that passed the admin check (`anonymous` when no key is
+/// configured). `X-Admin-Actor` is client-supplied, so it is only appended as
+/// a display hint, as is the forwarded client address.
+pub fn admin_actor(headers: &HeaderMap) -> String {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+    };
+    let mut actor = extract_bearer(headers)
+        .filter(|token| !token.is_empty())
+        .map_or_else(|| "anonymous".to_string(), |token| fingerprint(&token));
+    if let Some(hint) = header("x-admin-actor") {
+        let hint: String = hint.chars().filter(|c| !c.is_control()).take(64).collect();
+        actor = format!("{actor}[{hint}]");
+    }
+    let client = header("x-forwarded-for")
+        .and_then(|v| v.split(',').next())
+        .map(str::trim)
+        .or_else(|| header("x-real-ip"));
+    match client {
+        Some(ip) => format!("{actor}@{ip}"),
+        None => actor,
+    }
+}
+
 pub async fn verify_api_key(headers: &HeaderMap) -> Result<(), ApiError> {
     let api_key: String = get_config("app.api_key", String::new()).await;
     if api_key.is_empty() {
@@ -27,6 +57,23 @@ pub async fn verify_api_key(headers: &HeaderMap) -> Result<(), ApiError> {
     }
 }
 
+/// Inference routes also accept the tenant keys of `app.api_key_tags`;
+/// admin routes must keep using `verify_api_key`.
+pub async fn verify_inference_key(headers: &HeaderMap) -> Result<(), ApiError> {
+    let api_key: String = get_config("app.api_key", String::new()).await;
+    if api_key.is_empty() {
+        return Ok(());
+    }
+    let policy_keys: serde_json::Map<String, serde_json::Value> =
+        get_config("app.api_key_tags", serde_json::Map::new()).await;
+    let auth = extract_bearer(headers);
+    match auth {
+        Some(token) if token == api_key || policy_keys.contains_key(&token) => Ok(()),
+        Some(_) => Err(ApiError::authentication("Invalid authentication token")),
+        None => Err(ApiError::authentication("Missing authentication token")),
+    }
+}
+
 pub async fn verify_app_key(headers: &HeaderMap) -> Result<(), ApiError> {
     let app_key: String = get_config("app.app_key", String::new()).await;
     if app_key.is_empty() {
@@ -0,0 +1,36 @@
+use axum::http::HeaderMap;
+
+use crate::core::auth::admin_actor;
+use crate::core::redact::fingerprint;
+
+fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        headers.insert(*name, value.parse().unwrap());
+    }
+    headers
+}
+
+#[test]
+fn actor_is_the_credential_fingerprint() {
+    let fp = fingerprint("sk-admin");
+    assert_eq!(
+        admin_actor(&headers(&[("authorization", "Bearer sk-admin")])),
+        fp
+    );
+    assert_eq!(admin_actor(&HeaderMap::new()), "anonymous");
+}
+
+#[test]
+fn actor_header_is_only_a_hint() {
+    let actor = admin_actor(&headers(&[
+        ("authorization", "Bearer sk-admin"),
+        ("x-admin-actor", "alice"),
+        ("x-forwarded-for", "10.0.0.8, 10.0.0.1"),
+    ]));
+    assert_eq!(
+        actor,
+        format!("{}[alice]@10.0.0.8", fingerprint("sk-admin"))
+    );
+    assert!(admin_actor(&headers(&[("x-admin-actor", "admin")])).starts_with("anonymous["));
+}
@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use crate::core::config::path;
+use crate::core::config::watch::changed_keys;
+use crate::core::storage::{Storage, StorageBackend, StorageError};
+
+pub const HISTORY_LIMIT: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigAction {
+    Baseline,
+    Update,
+    Rollback,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiffEntry {
+    pub key: String,
+    pub before: Option<JsonValue>,
+    pub after: Option<JsonValue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigVersion {
+    pub version: u64,
+    pub timestamp: i64,
+    pub actor: String,
+    pub action: ConfigAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollback_of: Option<u64>,
+    pub diff: Vec<DiffEntry>,
+    pub snapshot: JsonValue,
+}
+
+impl ConfigVersion {
+    pub fn summary(&self) -> JsonValue {
+        serde_json::json!({
+            "version": self.version,
+            "timestamp": self.timestamp,
+            "actor": self.actor,
+            "action": self.action,
+            "rollback_of": self.rollback_of,
+            "changed": self.diff.iter().map(|d| d.key.as_str()).collect::<Vec<_>>(),
+        })
+    }
+}
+
+fn lookup<'a>(config: &'a JsonValue, key: &str) -> Option<&'a JsonValue> {
+    path::lookup(config, &path::parse(key)?)
+}
+
+pub fn diff(before: &JsonValue, after: &JsonValue) -> Vec<DiffEntry> {
+    changed_keys(before, after)
+        .into_iter()
+        .map(|key| DiffEntry {
+            before: lookup(before, &key).cloned(),
+            after: lookup(after, &key).cloned(),
+            key,
+        })
+        .collect()
+}
+
+pub async fn load_history(storage: &StorageBackend) -> Result<Vec<ConfigVersion>, StorageError> {
+    let raw = storage.load_config_history().await?;
+    let entries = raw.as_array().cloned().unwrap_or_default();
+    Ok(entries
+        .into_iter()
+        .filter_map(|entry| match serde_json::from_value(entry) {
+            Ok(version) => Some(version),
+            Err(err) => {
+                tracing::warn!("Skipping unreadable config version: {err}");
+                None
+            }
+        })
+        .collect())
+}
+
+pub async fn save_history(
+    storage: &StorageBackend,
+    history: &[ConfigVersion],
+) -> Result<(), StorageError> {
+    let data = serde_json::to_value(history)
+        .map_err(|e| StorageError(format!("serialize config history failed: {e}")))?;
+    storage.save_config_history(&data).await
+}
+
+/// Seeds a baseline from `before` so the first change can be undone too.
+pub fn record(
+    history: &mut Vec<ConfigVersion>,
+    before: &JsonValue,
+    after: &JsonValue,
+    actor: &str,
+    action: ConfigAction,
+    rollback_of: Option<u64>,
+) -> ConfigVersion {
+    let now = chrono::Utc::now().timestamp_millis();
+    if history.is_empty() {
+        history.push(ConfigVersion {
+            version: 1,
+            timestamp: now,
+            actor: "system".to_string(),
+            action: ConfigAction::Baseline,
+            rollback_of: None,
+            diff: Vec::new(),
+            snapshot: before.clone(),
+        });
+    }
+    let version = ConfigVersion {
+        version: history.last().map_or(0, |v| v.version) + 1,
+        timestamp: now,
+        actor: actor.to_string(),
+        action,
+        rollback_of,
+        diff: diff(before, after),
+        snapshot: after.clone(),
+    };
+    history.push(version.clone());
+    if history.len() > HISTORY_LIMIT {
+        let excess = history.len() - HISTORY_LIMIT;
+        history.drain(..excess);
+    }
+    version
+}
@@ -0,0 +1,59 @@
+use serde_json::json;
+
+use crate::core::config::history::{ConfigAction, HISTORY_LIMIT, diff, record};
+
+#[test]
+fn first_record_seeds_a_baseline() {
+    let mut versions = Vec::new();
+    let before = json!({"grok": {"timeout": 120}});
+    let after = json!({"grok": {"timeout": 60}});
+    let version = record(
+        &mut versions,
+        &before,
+        &after,
+        "alice",
+        ConfigAction::Update,
+        None,
+    );
+
+    assert_eq!(versions.len(), 2);
+    assert_eq!(versions[0].action, ConfigAction::Baseline);
+    assert_eq!(versions[0].snapshot, before);
+    assert_eq!(version.version, 2);
+    assert_eq!(version.actor, "alice");
+    assert_eq!(version.diff[0].key, "grok.timeout");
+    assert_eq!(version.diff[0].before, Some(json!(120)));
+    assert_eq!(version.diff[0].after, Some(json!(60)));
+}
+
+#[test]
+fn history_is_bounded_and_versions_keep_increasing() {
+    let mut versions = Vec::new();
+    for n in 0..(HISTORY_LIMIT as i64 + 5) {
+        let before = json!({"grok": {"timeout": n}});
+        let after = json!({"grok": {"timeout": n + 1}});
+        record(
+            &mut versions,
+            &before,
+            &after,
+            "admin",
+            ConfigAction::Update,
+            None,
+        );
+    }
+    assert_eq!(versions.len(), HISTORY_LIMIT);
+    assert_eq!(versions.last().unwrap().version, HISTORY_LIMIT as u64 + 6);
+    assert!(versions.windows(2).all(|w| w[0].version < w[1].version));
+}
+
+#[test]
+fn diff_reports_added_and_removed_keys() {
+    let entries = diff(
+        &json!({"app": {"api_key": "a"}}),
+        &json!({"app": {"app_key": "b"}}),
+    );
+    let keys: Vec<&str> = entries.iter().map(|e| e.key.as_str()).collect();
+    assert_eq!(keys, vec!["app.api_key", "app.app_key"]);
+    assert_eq!(entries[0].after, None);
+    assert_eq!(entries[1].before, None);
+}
@@ -0,0 +1,509 @@
+pub mod history;
+#[cfg(test)]
+mod history_tests;
+pub mod overrides;
+#[cfg(test)]
+mod overrides_tests;
+pub mod path;
+#[cfg(test)]
+mod path_tests;
+pub mod schema;
+#[cfg(test)]
+mod schema_tests;
+pub mod secrets;
+#[cfg(test)]
+mod secrets_tests;
+pub mod watch;
+#[cfg(test)]
+mod watch_tests;
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use once_cell::sync::Lazy;
+
+use serde::de::DeserializeOwned;
+use serde_json::Value as JsonValue;
+use tokio::sync::{OnceCell, RwLock};
+
+use crate::core::storage::crypto::{Document, keyring};
+use crate::core::storage::{Storage, StorageError, get_storage};
+
+use self::history::{ConfigAction, ConfigVersion};
+use self::schema::FieldError;
+pub use self::watch::{on_change, start_config_watcher};
+
+static CONFIG: OnceCell<Arc<Config>> = OnceCell::const_new();
+static MISTYPED_KEYS: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Invalid(Vec<FieldError>),
+    UnknownVersion(u64),
+    Storage(StorageError),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Invalid(errors) => {
+                let joined: Vec<String> = errors.iter().map(ToString::to_string).collect();
+                write!(f, "invalid config: {}", joined.join("; "))
+            }
+            ConfigError::UnknownVersion(version) => write!(f, "unknown config version {version}"),
+            ConfigError::Storage(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<StorageError> for ConfigError {
+    fn from(err: StorageError) -> Self {
+        ConfigError::Storage(err)
+    }
+}
+
+#[derive(Debug)]
+pub struct Config {
+    /// `stored` plus environment/CLI overrides.
+    inner: RwLock<JsonValue>,
+    stored: RwLock<JsonValue>,
+    invalid: RwLock<Vec<FieldError>>,
+    defaults: RwLock<Option<JsonValue>>,
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self {
+            inner: RwLock::new(JsonValue::Object(Default::default())),
+            stored: RwLock::new(JsonValue::Object(Default::default())),
+            invalid: RwLock::new(Vec::new()),
+            defaults: RwLock::new(None),
+        }
+    }
+
+    async fn ensure_defaults(&self) {
+        let mut defaults = self.defaults.write().await;
+        if defaults.is_some() {
+            return;
+        }
+        let value = load_defaults().unwrap_or(JsonValue::Object(Default::default()));
+        *defaults = Some(value);
+    }
+
+    pub async fn load(&self) -> Result<(), StorageError> {
+        self.ensure_defaults().await;
+        let defaults = self
+            .defaults
+            .read()
+            .await
+            .clone()
+            .unwrap_or(JsonValue::Object(Default::default()));
+        let storage = get_storage();
+
+        let mut from_remote = true;
+        let mut config_data = storage.load_config().await?;
+        // A failed read returned above, so seeding never overwrites shared config.
+        if config_data.as_object().is_some_and(|m| m.is_empty()) {
+            from_remote = false;
+            let local = crate::core::storage::LocalStorage::new();
+            config_data = local
+                .load_config()
+                .await
+                .and_then(|data| keyring().open_document(&data, Document::Config))
+                .unwrap_or(JsonValue::Object(Default::default()));
+        }
+        let merged = deep_merge(&defaults, &config_data);
+
+        let should_persist = !from_remote || merged != config_data;
+        if should_persist {
+            storage
+                .with_lock("config_save", 10, || async {
+                    storage.save_config(&merged).await
+                })
+                .await?;
+        }
+
+        self.adopt(merged, &defaults).await;
+        Ok(())
+    }
+
+    pub async fn update(
+        &self,
+        new_config: &JsonValue,
+        actor: &str,
+    ) -> Result<Option<ConfigVersion>, ConfigError> {
+        self.ensure_defaults().await;
+        let defaults = self
+            .defaults
+            .read()
+            .await
+            .clone()
+            .unwrap_or(JsonValue::Object(Default::default()));
+
+        // The UI posts overridden keys back unchanged.
+        let mut incoming = new_config.clone();
+        let mut errors: Vec<FieldError> = Vec::new();
+        for key in overrides::current().keys() {
+            let Some(segments) = path::parse(&key) else {
+                continue;
+            };
+            let posted = path::remove(&mut incoming, &segments);
+            if posted.is_some_and(|v| Some(&v) != overrides::current().get(&key)) {
+                errors.push(FieldError {
+                    field: key,
+                    message: "read-only: set by environment or command line".to_string(),
+                });
+            }
+        }
+
+        // Merge onto storage, not this replica's copy, to keep other replicas' updates.
+        let storage = get_storage();
+        let (merged, recorded) = storage
+            .with_lock("config_save", 10, || async {
+                let base = deep_merge(&defaults, &storage.load_config().await?);
+                let merged = deep_merge(&base, &incoming);
+
+                // Stored configs may carry retired keys; only reject ones this update adds.
+                let known_before: HashSet<String> =
+                    schema::unknown_keys(&base).into_iter().collect();
+                errors.extend(
+                    schema::unknown_keys(&incoming)
+                        .into_iter()
+                        .filter(|key| !known_before.contains(key))
+                        .map(|field| FieldError {
+                            field,
+                            message: "unknown field".to_string(),
+                        }),
+                );
+                errors.extend(schema::validate(&merged));
+                if !errors.is_empty() {
+                    return Ok(Err(ConfigError::Invalid(std::mem::take(&mut errors))));
+                }
+                if history::diff(&base, &merged).is_empty() {
+                    return Ok(Ok((merged, None)));
+                }
+
+                let mut versions = history::load_history(&storage).await?;
+                storage.save_config(&merged).await?;
+                let version = history::record(
+                    &mut versions,
+                    &base,
+                    &merged,
+                    actor,
+                    ConfigAction::Update,
+                    None,
+                );
+                history::save_history(&storage, &versions).await?;
+                Ok(Ok((merged, Some(version))))
+            })
+            .await??;
+        self.adopt(merged, &defaults).await;
+        Ok(recorded)
+    }
+
+    pub async fn rollback(&self, version: u64, actor: &str) -> Result<ConfigVersion, ConfigError> {
+        self.ensure_defaults().await;
+        let defaults = self
+            .defaults
+            .read()
+            .await
+            .clone()
+            .unwrap_or(JsonValue::Object(Default::default()));
+        let storage = get_storage();
+        let (merged, recorded) = storage
+            .with_lock("config_save", 10, || async {
+                let current = deep_merge(&defaults, &storage.load_config().await?);
+                let mut versions = history::load_history(&storage).await?;
+                let Some(target) = versions.iter().find(|v| v.version == version) else {
+                    return Ok(Err(ConfigError::UnknownVersion(version)));
+                };
+                let merged = deep_merge(&defaults, &target.snapshot);
+                let errors = schema::validate(&merged);
+                if !errors.is_empty() {
+                    return Ok(Err(ConfigError::Invalid(errors)));
+                }
+                storage.save_config(&merged).await?;
+                let recorded = history::record(
+                    &mut versions,
+                    &current,
+                    &merged,
+                    actor,
+                    ConfigAction::Rollback,
+                    Some(version),
+                );
+                history::save_history(&storage, &versions).await?;
+                Ok(Ok((merged, recorded)))
+            })
+            .await??;
+        self.adopt(merged, &defaults).await;
+        Ok(recorded)
+    }
+
+    pub async fn reload(&self) -> Result<(), StorageError> {
+        let config_data = get_storage().load_config().await?;
+        self.apply_stored(&config_data).await;
+        Ok(())
+    }
+
+    pub async fn apply_stored(&self, config_data: &JsonValue) -> bool {
+        self.ensure_defaults().await;
+        let defaults = self
+            .defaults
+            .read()
+            .await
+            .clone()
+            .unwrap_or(JsonValue::Object(Default::default()));
+        self.adopt(deep_merge(&defaults, config_data), &defaults)
+            .await
+    }
+
+    /// Storage keeps the invalid values for the admin to fix.
+    async fn adopt(&self, stored: JsonValue, defaults: &JsonValue) -> bool {
+        let mut repaired = stored;
+        *self.invalid.write().await = repair_invalid(&mut repaired, defaults);
+        self.replace(repaired).await
+    }
+
+    async fn replace(&self, stored: JsonValue) -> bool {
+        let effective = deep_merge(&stored, &overrides::current().document());
+        *self.stored.write().await = stored;
+        let keys = {
+            let mut inner = self.inner.write().await;
+            let keys = watch::changed_keys(&inner, &effective);
+            *inner = effective;
+            keys
+        };
+        let changed = !keys.is_empty();
+        watch::publish(keys);
+        changed
+    }
+
+    pub async fn get_value(&self, key: &str) -> Option<JsonValue> {
+        let inner = self.inner.read().await;
+        get_value(&inner, key)
+    }
+}
+
+async fn get_or_init_config() -> &'static Arc<Config> {
+    CONFIG
+        .get_or_init(|| async { Arc::new(Config::new()) })
+        .await
+}
+
+pub async fn load_config() -> Result<(), StorageError> {
+    get_or_init_config().await.load().await
+}
+
+pub async fn update_config(
+    new_config: &JsonValue,
+    actor: &str,
+) -> Result<Option<ConfigVersion>, ConfigError> {
+    get_or_init_config().await.update(new_config, actor).await
+}
+
+pub async fn rollback_config(version: u64, actor: &str) -> Result<ConfigVersion, ConfigError> {
+    get_or_init_config().await.rollback(version, actor).await
+}
+
+pub async fn invalid_config_fields() -> Vec<FieldError> {
+    get_or_init_config().await.invalid.read().await.clone()
+}
+
+pub async fn config_history() -> Result<Vec<ConfigVersion>, StorageError> {
+    history::load_history(&get_storage()).await
+}
+
+/// The stored config over the defaults, as history snapshots record it,
+/// without environment/CLI overrides.
+pub async fn stored_config() -> Result<JsonValue, StorageError> {
+    let defaults = load_defaults().unwrap_or(JsonValue::Object(Default::default()));
+    Ok(deep_merge(&defaults, &get_storage().load_config().await?))
+}
+
+pub fn config_schema() -> JsonValue {
+    let defaults = load_defaults().unwrap_or(JsonValue::Object(Default::default()));
+    let mut schema = schema::json_schema(&defaults);
+    for key in readonly_keys() {
+        let segments = path::parse(&key);
+        let property = match segments.as_deref() {
+            Some([path::Segment::Key(section), path::Segment::Key(name)]) => {
+                &mut schema["properties"][section]["properties"][name]
+            }
+            Some(
+                [
+                    path::Segment::Key(section),
+                    path::Segment::Key(model),
+                    path::Segment::Key(name),
+                ],
+            ) if section == schema::MODELS_SECTION => {
+                let models = &mut schema["properties"][section];
+                // A pinned model gets its own entry so the flag stays off other models.
+                if !models["properties"][model].is_object() {
+                    models["properties"][model] = models["additionalProperties"].clone();
+                }
+                &mut models["properties"][model]["properties"][name]
+            }
+            _ => continue,
+        };
+        if property.is_object() {
+            property["readOnly"] = JsonValue::Bool(true);
+        }
+    }
+    schema
+}
+
+pub fn readonly_keys() -> Vec<String> {
+    overrides::current().keys()
+}
+
+pub async fn get_config_value(key: &str) -> Option<JsonValue> {
+    let cfg = CONFIG
+        .get_or_init(|| async { Arc::new(Config::new()) })
+        .await;
+    cfg.get_value(key).await
+}
+
+pub async fn get_all_config() -> JsonValue {
+    let cfg = CONFIG
+        .get_or_init(|| async { Arc::new(Config::new()) })
+        .await;
+    cfg.inner.read().await.clone()
+}
+
+pub async fn get_config<T: DeserializeOwned>(key: &str, default: T) -> T {
+    if let Some(value) = get_config_value(key).await {
+        match serde_json::from_value::<T>(value) {
+            Ok(parsed) => return parsed,
+            Err(err) => {
+                let first = MISTYPED_KEYS
+                    .lock()
+                    .map(|mut seen| seen.insert(key.to_string()))
+                    .unwrap_or(false);
+                if first {
+                    tracing::warn!("Config {key} has an unexpected type ({err}), using default");
+                }
+            }
+        }
+    }
+    default
+}
+
+pub async fn get_model_config<T: DeserializeOwned>(model: &str, key: &str, default: T) -> T {
+    let model_key = path::join(&[schema::MODELS_SECTION, model, key]);
+    if let Some(value) = get_config_value(&model_key).await {
+        match serde_json::from_value::<T>(value) {
+            Ok(parsed) => return parsed,
+            Err(err) => tracing::warn!("Config {model_key} has an unexpected type ({err})"),
+        }
+    }
+    get_config(&format!("grok.{key}"), default).await
+}
+
+fn repair_invalid(config: &mut JsonValue, defaults: &JsonValue) -> Vec<FieldError> {
+    let errors = schema::validate(config);
+    for error in &errors {
+        let Some(segments) = path::parse(&error.field) else {
+            continue;
+        };
+        match path::lookup(defaults, &segments) {
+            Some(default) => {
+                tracing::warn!("Config {error}, using default {default}");
+                path::set(config, &segments, default.clone());
+            }
+            // Per-model entries have no default; dropping them falls back to `grok.*`.
+            None => {
+                tracing::warn!("Config {error}, ignoring it");
+                path::remove(config, &segments);
+            }
+        }
+    }
+    for key in schema::unknown_keys(config) {
+        tracing::warn!("Config key {key} is not recognised and will be ignored");
+    }
+    errors
+}
+
+fn get_value(config: &JsonValue, key: &str) -> Option<JsonValue> {
+    path::lookup(config, &path::parse(key)?).cloned()
+}
+
+fn deep_merge(base: &JsonValue, override_value: &JsonValue) -> JsonValue {
+    match (base, override_value) {
+        (JsonValue::Object(base_map), JsonValue::Object(override_map)) => {
+            let mut result = base_map.clone();
+            for (k, v) in override_map {
+                let merged = match result.get(k) {
+                    Some(existing) => deep_merge(existing, v),
+                    None => v.clone(),
+                };
+                result.insert(k.clone(), merged);
+            }
+            JsonValue::Object(result)
+        }
+        (_, other) => other.clone(),
+    }
+}
+
+pub(crate) fn load_defaults() -> Option<JsonValue> {
+    let path = project_root().join("config.defaults.toml");
+    if !path.exists() {
+        return None;
+    }
+    let content = std::fs::read_to_string(path).ok()?;
+    let value: toml::Value = content.parse().ok()?;
+    Some(toml_to_json(value))
+}
+
+pub fn project_root() -> PathBuf {
+    std::env::current_dir().unwrap_or_else(|_| Path::new(".").to_path_buf())
+}
+
+pub fn toml_to_json(value: toml::Value) -> JsonValue {
+    match value {
+        toml::Value::String(s) => JsonValue::String(s),
+        toml::Value::Integer(i) => JsonValue::Number(i.into()),
+        toml::Value::Float(f) => JsonValue::Number(serde_json::Number::from_f64(f).unwrap()),
+        toml::Value::Boolean(b) => JsonValue::Bool(b),
+        toml::Value::Datetime(dt) => JsonValue::String(dt.to_string()),
+        toml::Value::Array(arr) => JsonValue::Array(arr.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => {
+            let mut map = serde_json::Map::new();
+            for (k, v) in table {
+                map.insert(k, toml_to_json(v));
+            }
+            JsonValue::Object(map)
+        }
+    }
+}
+
+fn json_to_toml(value: &JsonValue) -> toml::Value {
+    match value {
+        JsonValue::Null => toml::Value::String("".to_string()),
+        JsonValue::Bool(b) => toml::Value::Boolean(*b),
+        JsonValue::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                toml::Value::Integer(i)
+            } else if let Some(f) = n.as_f64() {
+                toml::Value::Float(f)
+            } else {
+                toml::Value::String(n.to_string())
+            }
+        }
+        JsonValue::String(s) => toml::Value::String(s.clone()),
+        JsonValue::Array(arr) => toml::Value::Array(arr.iter().map(json_to_toml).collect()),
+        JsonValue::Object(map) => {
+            let mut table = toml::value::Table::new();
+            for (k, v) in map {
+                table.insert(k.clone(), json_to_toml(v));
+            }
+            toml::Value::Table(table)
+        }
+    }
+}
+
+pub fn config_to_toml(value: &JsonValue) -> toml::Value {
+    json_to_toml(value)
+}
@@ -0,0 +1,143 @@
+use once_cell::sync::OnceCell;
+use serde_json::{Map, Value as JsonValue};
+
+use crate::core::config::path;
+use crate::core::config::schema::{FieldType, spec_for, validate_field};
+
+/// `GROK2API__GROK__TIMEOUT=60` overrides `grok.timeout`.
+pub const ENV_PREFIX: &str = "GROK2API__";
+/// `--set` wins over the environment.
+pub const CLI_FLAG: &str = "--set";
+
+static OVERRIDES: OnceCell<Overrides> = OnceCell::new();
+
+#[derive(Debug, Default, Clone)]
+pub struct Overrides {
+    entries: Vec<(String, String, JsonValue)>,
+}
+
+impl Overrides {
+    pub fn from_sources<E, K, V>(env: E, args: &[String]) -> Result<Self, String>
+    where
+        E: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let mut overrides = Overrides::default();
+        for (name, raw) in env {
+            let Some(rest) = name.as_ref().strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let parts: Vec<String> = rest
+                .split("__")
+                .map(|part| part.to_ascii_lowercase())
+                .collect();
+            let key = path::join(&parts);
+            overrides.insert(key, name.as_ref().to_string(), raw.as_ref());
+        }
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let assignment = match arg.strip_prefix(CLI_FLAG) {
+                Some("") => iter
+                    .next()
+                    .ok_or_else(|| format!("{CLI_FLAG} needs key=value"))?
+                    .as_str(),
+                Some(rest) => match rest.strip_prefix('=') {
+                    Some(assignment) => assignment,
+                    None => continue,
+                },
+                None => continue,
+            };
+            let Some((key, raw)) = assignment.split_once('=') else {
+                return Err(format!("{CLI_FLAG} expects key=value, got {assignment}"));
+            };
+            overrides.insert(key.trim().to_string(), format!("{CLI_FLAG} {key}"), raw);
+        }
+        Ok(overrides)
+    }
+
+    fn insert(&mut self, key: String, source: String, raw: &str) {
+        if path::parse(&key).is_none_or(|segments| segments.len() < 2) {
+            tracing::warn!("Ignoring config override {source}: expected section.key");
+            return;
+        }
+        let value = parse_value(&key, raw);
+        self.entries.retain(|(existing, _, _)| existing != &key);
+        self.entries.push((key, source, value));
+    }
+
+    fn retain_valid(&mut self) {
+        self.entries.retain(|(key, source, value)| {
+            if spec_for(key).is_none() {
+                tracing::warn!("Ignoring config override {source}: unknown key {key}");
+                return false;
+            }
+            match validate_field(key, value) {
+                Some(error) => {
+                    tracing::warn!("Ignoring config override {source}: {error}");
+                    false
+                }
+                None => true,
+            }
+        });
+    }
+
+    pub fn keys(&self) -> Vec<String> {
+        self.entries.iter().map(|(key, _, _)| key.clone()).collect()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        self.entries
+            .iter()
+            .find(|(existing, _, _)| existing == key)
+            .map(|(_, _, value)| value)
+    }
+
+    pub fn document(&self) -> JsonValue {
+        let mut root = JsonValue::Object(Map::new());
+        for (key, _, value) in &self.entries {
+            if let Some(segments) = path::parse(key) {
+                path::set(&mut root, &segments, value.clone());
+            }
+        }
+        root
+    }
+}
+
+/// Unparseable input stays a string and is reported by validation.
+fn parse_value(key: &str, raw: &str) -> JsonValue {
+    let raw = raw.trim();
+    let as_string = matches!(
+        spec_for(key).map(|f| f.ty),
+        Some(
+            FieldType::Str
+                | FieldType::Required
+                | FieldType::Url { .. }
+                | FieldType::Proxy
+                | FieldType::Enum(_)
+                | FieldType::Emulation { .. }
+        )
+    );
+    if as_string {
+        return JsonValue::String(raw.to_string());
+    }
+    serde_json::from_str(raw).unwrap_or_else(|_| JsonValue::String(raw.to_string()))
+}
+
+pub fn init_overrides(args: &[String]) -> Result<(), String> {
+    let mut overrides = Overrides::from_sources(std::env::vars(), args)?;
+    overrides.retain_valid();
+    for (key, source, _) in &overrides.entries {
+        tracing::info!("Config {key} overridden by {source}");
+    }
+    let _ = OVERRIDES.set(overrides);
+    Ok(())
+}
+
+pub fn current() -> &'static Overrides {
+    static EMPTY: Overrides = Overrides {
+        entries: Vec::new(),
+    };
+    OVERRIDES.get().unwrap_or(&EMPTY)
+}
@@ -0,0 +1,46 @@
+use serde_json::json;
+
+use crate::core::config::overrides::Overrides;
+
+fn args(list: &[&str]) -> Vec<String> {
+    list.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn reads_prefixed_env_vars_with_schema_types() {
+    let env = [
+        ("GROK2API__GROK__TIMEOUT", "60"),
+        ("GROK2API__GROK__CF_CLEARANCE", "12345"),
+        ("GROK2API__GROK__RETRY_STATUS_CODES", "[429, 403]"),
+        ("GROK2API__TOKEN__AUTO_REFRESH", "false"),
+        ("SERVER_PORT", "9000"),
+    ];
+    let overrides = Overrides::from_sources(env, &[]).unwrap();
+    assert_eq!(
+        overrides.document(),
+        json!({
+            "grok": {"timeout": 60, "cf_clearance": "12345", "retry_status_codes": [429, 403]},
+            "token": {"auto_refresh": false},
+        })
+    );
+}
+
+#[test]
+fn cli_set_wins_over_env() {
+    let env = [("GROK2API__APP__API_KEY", "from-env")];
+    let overrides = Overrides::from_sources(
+        env,
+        &args(&["--set", "app.api_key=from-cli", "--set=grok.timeout=30"]),
+    )
+    .unwrap();
+    assert_eq!(overrides.get("app.api_key"), Some(&json!("from-cli")));
+    assert_eq!(overrides.get("grok.timeout"), Some(&json!(30)));
+    assert_eq!(overrides.keys(), vec!["app.api_key", "grok.timeout"]);
+}
+
+#[test]
+fn malformed_cli_set_is_an_error() {
+    let empty: [(&str, &str); 0] = [];
+    assert!(Overrides::from_sources(empty, &args(&["--set", "grok.timeout"])).is_err());
+    assert!(Overrides::from_sources(empty, &args(&["--set"])).is_err());
+}
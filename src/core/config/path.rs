@@ -0,0 +1,136 @@
+use serde_json::{Map, Value as JsonValue};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// A bare numeric key also indexes arrays.
+pub fn parse(path: &str) -> Option<Vec<Segment>> {
+    let mut segments = Vec::new();
+    let mut chars = path.chars().peekable();
+    loop {
+        let key = if chars.peek() == Some(&'"') {
+            chars.next();
+            let mut key = String::new();
+            loop {
+                match chars.next()? {
+                    '"' => break,
+                    '\\' => key.push(chars.next()?),
+                    c => key.push(c),
+                }
+            }
+            key
+        } else {
+            let mut key = String::new();
+            while let Some(&c) = chars.peek() {
+                if c == '.' || c == '[' {
+                    break;
+                }
+                key.push(c);
+                chars.next();
+            }
+            if key.is_empty() {
+                return None;
+            }
+            key
+        };
+        segments.push(Segment::Key(key));
+
+        while chars.peek() == Some(&'[') {
+            chars.next();
+            let mut digits = String::new();
+            loop {
+                match chars.next()? {
+                    ']' => break,
+                    c => digits.push(c),
+                }
+            }
+            segments.push(Segment::Index(digits.trim().parse().ok()?));
+        }
+
+        match chars.next() {
+            None => return Some(segments),
+            Some('.') => continue,
+            Some(_) => return None,
+        }
+    }
+}
+
+pub fn quote(key: &str) -> String {
+    let bare = !key.is_empty() && !key.contains(['.', '[', ']', '"', '\\']);
+    if bare {
+        return key.to_string();
+    }
+    let escaped = key.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("\"{escaped}\"")
+}
+
+pub fn join<S: AsRef<str>>(keys: &[S]) -> String {
+    keys.iter()
+        .map(|k| quote(k.as_ref()))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn step<'a>(value: &'a JsonValue, segment: &Segment) -> Option<&'a JsonValue> {
+    match (value, segment) {
+        (JsonValue::Object(map), Segment::Key(key)) => map.get(key),
+        (JsonValue::Array(items), Segment::Key(key)) => items.get(key.parse::<usize>().ok()?),
+        (JsonValue::Array(items), Segment::Index(index)) => items.get(*index),
+        _ => None,
+    }
+}
+
+pub fn lookup<'a>(value: &'a JsonValue, path: &[Segment]) -> Option<&'a JsonValue> {
+    path.iter().try_fold(value, step)
+}
+
+/// Indices must point at existing array items.
+pub fn set(root: &mut JsonValue, path: &[Segment], value: JsonValue) -> bool {
+    let Some((last, parents)) = path.split_last() else {
+        return false;
+    };
+    let mut current = root;
+    for segment in parents {
+        current = match (current, segment) {
+            (JsonValue::Object(map), Segment::Key(key)) => map
+                .entry(key.clone())
+                .or_insert_with(|| JsonValue::Object(Map::new())),
+            (JsonValue::Array(items), Segment::Index(index)) => match items.get_mut(*index) {
+                Some(item) => item,
+                None => return false,
+            },
+            _ => return false,
+        };
+    }
+    match (current, last) {
+        (JsonValue::Object(map), Segment::Key(key)) => {
+            map.insert(key.clone(), value);
+            true
+        }
+        (JsonValue::Array(items), Segment::Index(index)) if *index < items.len() => {
+            items[*index] = value;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Array items are left in place.
+pub fn remove(root: &mut JsonValue, path: &[Segment]) -> Option<JsonValue> {
+    let (last, parents) = path.split_last()?;
+    let mut current = root;
+    for segment in parents {
+        current = match (current, segment) {
+            (JsonValue::Object(map), Segment::Key(key)) => map.get_mut(key)?,
+            (JsonValue::Array(items), Segment::Index(index)) => items.get_mut(*index)?,
+            _ => return None,
+        };
+    }
+    match (current, last) {
+        (JsonValue::Object(map), Segment::Key(key)) => map.remove(key),
+        _ => None,
+    }
+}
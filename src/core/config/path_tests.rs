@@ -0,0 +1,92 @@
+use serde_json::json;
+
+use crate::core::config::path::{self, Segment};
+
+fn key(s: &str) -> Segment {
+    Segment::Key(s.to_string())
+}
+
+#[test]
+fn parses_quoted_keys_and_indices() {
+    assert_eq!(
+        path::parse(r#"models."grok-4.1".timeout"#).unwrap(),
+        vec![key("models"), key("grok-4.1"), key("timeout")]
+    );
+    assert_eq!(
+        path::parse("proxy.pool[0].url").unwrap(),
+        vec![key("proxy"), key("pool"), Segment::Index(0), key("url")]
+    );
+    assert_eq!(
+        path::parse(r#""a\"b".c"#).unwrap(),
+        vec![key("a\"b"), key("c")]
+    );
+}
+
+#[test]
+fn rejects_malformed_paths() {
+    for bad in [
+        "",
+        "a..b",
+        ".a",
+        "a.",
+        "a[x]",
+        "a[0",
+        r#""open.b"#,
+        r#""a"b"#,
+    ] {
+        assert!(path::parse(bad).is_none(), "{bad}");
+    }
+}
+
+#[test]
+fn quote_and_join_round_trip() {
+    assert_eq!(path::quote("timeout"), "timeout");
+    assert_eq!(path::quote("grok-4.1"), r#""grok-4.1""#);
+    let joined = path::join(&["models", "grok-4.1", "timeout"]);
+    assert_eq!(joined, r#"models."grok-4.1".timeout"#);
+    assert_eq!(
+        path::parse(&joined).unwrap(),
+        vec![key("models"), key("grok-4.1"), key("timeout")]
+    );
+}
+
+#[test]
+fn lookup_follows_objects_and_arrays() {
+    let doc = json!({"proxy": {"pool": [{"url": "a"}, {"url": "b"}]}});
+    let by_index = path::parse("proxy.pool[1].url").unwrap();
+    let by_key = path::parse("proxy.pool.1.url").unwrap();
+    assert_eq!(path::lookup(&doc, &by_index), Some(&json!("b")));
+    assert_eq!(path::lookup(&doc, &by_key), Some(&json!("b")));
+    assert_eq!(
+        path::lookup(&doc, &path::parse("proxy.pool[2].url").unwrap()),
+        None
+    );
+}
+
+#[test]
+fn set_creates_tables_and_remove_takes_value() {
+    let mut doc = json!({"grok": {"timeout": 120}});
+    let target = path::parse(r#"models."grok-4.1".timeout"#).unwrap();
+    assert!(path::set(&mut doc, &target, json!(600)));
+    assert_eq!(doc["models"]["grok-4.1"]["timeout"], json!(600));
+
+    assert_eq!(path::remove(&mut doc, &target), Some(json!(600)));
+    assert_eq!(doc["models"]["grok-4.1"], json!({}));
+    assert_eq!(path::remove(&mut doc, &target), None);
+}
+
+#[test]
+fn set_does_not_grow_arrays() {
+    let mut doc = json!({"list": [1]});
+    assert!(path::set(
+        &mut doc,
+        &path::parse("list[0]").unwrap(),
+        json!(2)
+    ));
+    assert!(!path::set(
+        &mut doc,
+        &path::parse("list[1]").unwrap(),
+        json!(3)
+    ));
+    assert_eq!(doc, json!({"list": [2]}));
+}
@@ -0,0 +1,422 @@
+use serde::Serialize;
+use serde_json::{Map, Value as JsonValue, json};
+
+use crate::core::config::path::{self, Segment};
+use crate::services::grok::wreq_client::{emulation_names, is_known_emulation};
+
+pub const MODELS_SECTION: &str = "models";
+
+#[derive(Debug, Clone, Copy)]
+pub enum FieldType {
+    Bool,
+    Int {
+        min: i64,
+        max: i64,
+    },
+    Float {
+        min: f64,
+        max: f64,
+    },
+    Str,
+    Required,
+    Url {
+        optional: bool,
+    },
+    Proxy,
+    Enum(&'static [&'static str]),
+    Emulation {
+        optional: bool,
+    },
+    StrList,
+    IntList {
+        min: i64,
+        max: i64,
+    },
+    /// Items are checked by the consuming module.
+    Array,
+    Object,
+}
+
+#[derive(Debug)]
+pub struct FieldSpec {
+    pub key: &'static str,
+    pub ty: FieldType,
+}
+
+const fn field(key: &'static str, ty: FieldType) -> FieldSpec {
+    FieldSpec { key, ty }
+}
+
+const fn int(min: i64, max: i64) -> FieldType {
+    FieldType::Int { min, max }
+}
+
+use FieldType::{Array, Bool, Object, Proxy, Required, Str, StrList};
+
+/// Must mirror `config.defaults.toml`.
+pub static FIELDS: &[FieldSpec] = &[
+    field("grok.temporary", Bool),
+    field("grok.stream", Bool),
+    field("grok.thinking", Bool),
+    field("grok.dynamic_statsig", Bool),
+    field("grok.filter_tags", StrList),
+    field("grok.timeout", int(1, 3600)),
+    field("grok.base_proxy_url", Proxy),
+    field("grok.asset_proxy_url", Proxy),
+    field("grok.cf_clearance", Str),
+    field(
+        "grok.wreq_emulation",
+        FieldType::Emulation { optional: false },
+    ),
+    field(
+        "grok.wreq_emulation_usage",
+        FieldType::Emulation { optional: true },
+    ),
+    field(
+        "grok.wreq_emulation_nsfw",
+        FieldType::Emulation { optional: true },
+    ),
+    field("grok.max_retry", int(0, 10)),
+    field(
+        "grok.retry_status_codes",
+        FieldType::IntList { min: 100, max: 599 },
+    ),
+    field("grok.imagine_default_image_count", int(1, 10)),
+    field("grok.imagine_sso_daily_limit", int(0, 10_000)),
+    field("grok.imagine_blocked_retry", int(0, 20)),
+    field("grok.imagine_max_retries", int(0, 20)),
+    field("app.app_url", FieldType::Url { optional: true }),
+    field("app.app_key", Required),
+    field("app.api_key", Str),
+    field("app.api_key_tags", Object),
+    field("app.image_format", FieldType::Enum(&["url", "base64"])),
+    field("app.video_format", FieldType::Enum(&["url"])),
+    field("token.auto_refresh", Bool),
+    field("token.refresh_interval_hours", int(1, 720)),
+    field("token.fail_threshold", int(1, 100)),
+    field("token.save_delay_ms", int(0, 60_000)),
+    field(
+        "token.reload_interval_sec",
+        FieldType::Float {
+            min: 0.0,
+            max: 86_400.0,
+        },
+    ),
+    field("token.auto_tier", Bool),
+    field("token.tier_check_interval_hours", int(1, 8760)),
+    field("token.reserved_tags", StrList),
+    field("token.session_affinity", Bool),
+    field("token.session_ttl_sec", int(1, 604_800)),
+    field(
+        "token.selection_strategy",
+        FieldType::Enum(&["quota", "health"]),
+    ),
+    field(
+        "token.health_quarantine_score",
+        FieldType::Float {
+            min: 0.0,
+            max: 100.0,
+        },
+    ),
+    field("token.health_quarantine_sec", int(0, 86_400)),
+    field("token.auto_nsfw", Bool),
+    field("token.nsfw_retry_hours", int(1, 8760)),
+    field("token.pool_low_active", int(0, 100_000)),
+    field("webhook.sinks", Array),
+    field("webhook.max_retries", int(0, 10)),
+    field("webhook.timeout_sec", int(1, 300)),
+    field("proxy.pool", Array),
+    field(
+        "proxy.strategy",
+        FieldType::Enum(&["weighted", "round_robin"]),
+    ),
+    field("proxy.probe_url", FieldType::Url { optional: false }),
+    field("proxy.probe_interval_sec", int(0, 86_400)),
+    field("proxy.probe_timeout_sec", int(1, 300)),
+    field("proxy.fail_threshold", int(1, 100)),
+    field("proxy.eject_base_sec", int(1, 86_400)),
+    field("proxy.eject_max_sec", int(1, 86_400)),
+    field("cache.enable_auto_clean", Bool),
+    field("cache.limit_mb", int(0, 1_048_576)),
+    field("performance.assets_max_concurrent", int(1, 1000)),
+    field("performance.media_max_concurrent", int(1, 1000)),
+    field("performance.usage_max_concurrent", int(1, 1000)),
+    field("performance.assets_delete_batch_size", int(1, 1000)),
+    field("performance.assets_batch_size", int(1, 1000)),
+    field("performance.assets_max_tokens", int(1, 100_000)),
+    field("performance.usage_batch_size", int(1, 1000)),
+    field("performance.usage_max_tokens", int(1, 100_000)),
+    field("performance.nsfw_max_concurrent", int(1, 1000)),
+    field("performance.nsfw_batch_size", int(1, 1000)),
+    field("performance.nsfw_max_tokens", int(1, 100_000)),
+    field("downstream.enable_chat_completions", Bool),
+    field("downstream.enable_responses", Bool),
+    field("downstream.enable_images", Bool),
+    field("downstream.enable_images_nsfw", Bool),
+    field("downstream.enable_models", Bool),
+    field("downstream.enable_files", Bool),
+];
+
+pub static SECRET_PATHS: &[&str] = &[
+    "app.app_key",
+    "app.api_key",
+    "grok.cf_clearance",
+    "webhook.sinks.*.secret",
+];
+
+pub static SECRET_KEY_PATHS: &[&str] = &["app.api_key_tags"];
+
+pub static PROXY_URL_PATHS: &[&str] = &[
+    "grok.base_proxy_url",
+    "grok.asset_proxy_url",
+    "proxy.pool.*",
+    "proxy.pool.*.url",
+];
+
+/// The `grok.*` keys read through `get_model_config`; only these may be
+/// overridden under `models.<id>`.
+pub static MODEL_KEYS: &[&str] = &[
+    "temporary",
+    "stream",
+    "thinking",
+    "timeout",
+    "filter_tags",
+    "max_retry",
+    "retry_status_codes",
+];
+
+pub fn find_field(key: &str) -> Option<&'static FieldSpec> {
+    FIELDS.iter().find(|f| f.key == key)
+}
+
+pub fn spec_for(key: &str) -> Option<&'static FieldSpec> {
+    match path::parse(key)?.as_slice() {
+        [Segment::Key(section), Segment::Key(name)] => find_field(&format!("{section}.{name}")),
+        [Segment::Key(section), Segment::Key(_), Segment::Key(name)]
+            if section == MODELS_SECTION && MODEL_KEYS.contains(&name.as_str()) =>
+        {
+            find_field(&format!("grok.{name}"))
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for FieldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+fn check_url(raw: &str, schemes: &[&str]) -> Result<(), String> {
+    let url = url::Url::parse(raw).map_err(|e| format!("invalid URL: {e}"))?;
+    if !schemes.contains(&url.scheme()) {
+        return Err(format!("URL scheme must be one of {}", schemes.join(", ")));
+    }
+    Ok(())
+}
+
+fn check(ty: FieldType, value: &JsonValue) -> Result<(), String> {
+    match ty {
+        FieldType::Bool => value
+            .is_boolean()
+            .then_some(())
+            .ok_or_else(|| "expected true or false".to_string()),
+        FieldType::Int { min, max } => match value.as_i64() {
+            Some(n) if (min..=max).contains(&n) => Ok(()),
+            Some(n) => Err(format!("{n} is outside {min}..={max}")),
+            None => Err(format!("expected an integer in {min}..={max}")),
+        },
+        FieldType::Float { min, max } => match value.as_f64() {
+            Some(n) if (min..=max).contains(&n) => Ok(()),
+            Some(n) => Err(format!("{n} is outside {min}..={max}")),
+            None => Err(format!("expected a number in {min}..={max}")),
+        },
+        FieldType::Str => value
+            .is_string()
+            .then_some(())
+            .ok_or_else(|| "expected a string".to_string()),
+        FieldType::Required => match value.as_str() {
+            Some(s) if !s.trim().is_empty() => Ok(()),
+            Some(_) => Err("must not be empty".to_string()),
+            None => Err("expected a string".to_string()),
+        },
+        FieldType::Url { optional } => match value.as_str().map(str::trim) {
+            Some("") if optional => Ok(()),
+            Some(raw) => check_url(raw, &["http", "https"]),
+            None => Err("expected a URL string".to_string()),
+        },
+        FieldType::Proxy => match value.as_str().map(str::trim) {
+            Some("") => Ok(()),
+            Some(raw) => check_url(raw, &["http", "https", "socks5", "socks5h"]),
+            None => Err("expected a proxy URL string".to_string()),
+        },
+        FieldType::Enum(options) => match value.as_str() {
+            Some(s) if options.contains(&s) => Ok(()),
+            _ => Err(format!("expected one of {}", options.join(", "))),
+        },
+        FieldType::Emulation { optional } => match value.as_str().map(str::trim) {
+            Some("") if optional => Ok(()),
+            Some(raw) if is_known_emulation(raw) => Ok(()),
+            Some(raw) => Err(format!("unknown emulation {raw}")),
+            None => Err("expected an emulation name".to_string()),
+        },
+        FieldType::StrList => match value.as_array() {
+            Some(items) if items.iter().all(|v| v.is_string()) => Ok(()),
+            _ => Err("expected a list of strings".to_string()),
+        },
+        FieldType::IntList { min, max } => match value.as_array() {
+            Some(items)
+                if items
+                    .iter()
+                    .all(|v| v.as_i64().is_some_and(|n| (min..=max).contains(&n))) =>
+            {
+                Ok(())
+            }
+            _ => Err(format!("expected a list of integers in {min}..={max}")),
+        },
+        FieldType::Array => value
+            .is_array()
+            .then_some(())
+            .ok_or_else(|| "expected a list".to_string()),
+        FieldType::Object => value
+            .is_object()
+            .then_some(())
+            .ok_or_else(|| "expected a table".to_string()),
+    }
+}
+
+pub fn validate_field(key: &str, value: &JsonValue) -> Option<FieldError> {
+    let spec = spec_for(key)?;
+    check(spec.ty, value).err().map(|message| FieldError {
+        field: key.to_string(),
+        message,
+    })
+}
+
+pub fn validate(config: &JsonValue) -> Vec<FieldError> {
+    let mut errors: Vec<FieldError> = FIELDS
+        .iter()
+        .filter_map(|spec| {
+            let (section, key) = spec.key.split_once('.')?;
+            let value = config.get(section)?.get(key)?;
+            validate_field(spec.key, value)
+        })
+        .collect();
+    let models = config.get(MODELS_SECTION).and_then(|m| m.as_object());
+    for (model, table) in models.into_iter().flatten() {
+        let Some(table) = table.as_object() else {
+            errors.push(FieldError {
+                field: path::join(&[MODELS_SECTION, model]),
+                message: "expected a table".to_string(),
+            });
+            continue;
+        };
+        errors.extend(table.iter().filter_map(|(key, value)| {
+            validate_field(&path::join(&[MODELS_SECTION, model, key]), value)
+        }));
+    }
+    errors
+}
+
+pub fn unknown_keys(config: &JsonValue) -> Vec<String> {
+    let mut unknown = Vec::new();
+    for (section, value) in config.as_object().into_iter().flatten() {
+        if section == MODELS_SECTION {
+            for (model, table) in value.as_object().into_iter().flatten() {
+                unknown.extend(
+                    table
+                        .as_object()
+                        .into_iter()
+                        .flatten()
+                        .map(|(key, _)| path::join(&[MODELS_SECTION, model, key]))
+                        .filter(|key| spec_for(key).is_none()),
+                );
+            }
+            continue;
+        }
+        match value.as_object() {
+            Some(map) => unknown.extend(
+                map.keys()
+                    .map(|key| format!("{section}.{key}"))
+                    .filter(|key| find_field(key).is_none()),
+            ),
+            None => unknown.push(section.clone()),
+        }
+    }
+    unknown
+}
+
+fn type_schema(ty: FieldType) -> JsonValue {
+    match ty {
+        FieldType::Bool => json!({"type": "boolean"}),
+        FieldType::Int { min, max } => json!({"type": "integer", "minimum": min, "maximum": max}),
+        FieldType::Float { min, max } => json!({"type": "number", "minimum": min, "maximum": max}),
+        FieldType::Str => json!({"type": "string"}),
+        FieldType::Required => json!({"type": "string", "minLength": 1}),
+        FieldType::Url { .. } => json!({"type": "string", "format": "uri"}),
+        FieldType::Proxy => json!({"type": "string"}),
+        FieldType::Enum(options) => json!({"type": "string", "enum": options}),
+        FieldType::Emulation { optional } => {
+            let mut options: Vec<&str> = emulation_names().collect();
+            if optional {
+                options.insert(0, "");
+            }
+            json!({"type": "string", "enum": options})
+        }
+        FieldType::StrList => json!({"type": "array", "items": {"type": "string"}}),
+        FieldType::IntList { min, max } => json!({
+            "type": "array",
+            "items": {"type": "integer", "minimum": min, "maximum": max},
+        }),
+        FieldType::Array => json!({"type": "array"}),
+        FieldType::Object => json!({"type": "object"}),
+    }
+}
+
+pub fn json_schema(defaults: &JsonValue) -> JsonValue {
+    let mut sections: Map<String, JsonValue> = Map::new();
+    for spec in FIELDS {
+        let Some((section, key)) = spec.key.split_once('.') else {
+            continue;
+        };
+        let mut property = type_schema(spec.ty);
+        if let Some(default) = defaults.get(section).and_then(|s| s.get(key)) {
+            property["default"] = default.clone();
+        }
+        let entry = sections
+            .entry(section.to_string())
+            .or_insert_with(|| json!({"type": "object", "properties": {}}));
+        entry["properties"][key] = property;
+    }
+    let model_properties: Map<String, JsonValue> = MODEL_KEYS
+        .iter()
+        .filter_map(|name| {
+            let spec = find_field(&format!("grok.{name}"))?;
+            Some((name.to_string(), type_schema(spec.ty)))
+        })
+        .collect();
+    sections.insert(
+        MODELS_SECTION.to_string(),
+        json!({
+            "type": "object",
+            "description": "Per-model overrides of grok.* keys, keyed by model id",
+            "additionalProperties": {
+                "type": "object",
+                "properties": model_properties,
+                "additionalProperties": false,
+            },
+        }),
+    );
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "grok2api config",
+        "type": "object",
+        "properties": sections,
+    })
+}
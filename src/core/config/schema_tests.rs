@@ -0,0 +1,96 @@
+use serde_json::json;
+
+use crate::core::config::schema::{FIELDS, MODEL_KEYS, json_schema, unknown_keys, validate};
+use crate::core::config::{load_defaults, repair_invalid, toml_to_json};
+
+#[test]
+fn defaults_pass_validation_and_are_all_described() {
+    let defaults = load_defaults().expect("config.defaults.toml");
+    assert_eq!(validate(&defaults), vec![]);
+    assert_eq!(unknown_keys(&defaults), Vec::<String>::new());
+}
+
+#[test]
+fn reports_field_level_errors() {
+    let config = toml_to_json(
+        r#"
+        [grok]
+        timeout = "abc"
+        wreq_emulation = "netscape_4"
+        retry_status_codes = [429, 42]
+        [app]
+        image_format = "gif"
+        app_key = " "
+        [token]
+        reload_interval_sec = 1.5
+        "#
+        .parse()
+        .unwrap(),
+    );
+    let fields: Vec<String> = validate(&config).into_iter().map(|e| e.field).collect();
+    assert_eq!(
+        fields,
+        vec![
+            "grok.timeout",
+            "grok.wreq_emulation",
+            "grok.retry_status_codes",
+            "app.app_key",
+            "app.image_format",
+        ]
+    );
+}
+
+#[test]
+fn flags_unknown_keys() {
+    let config = json!({"grok": {"timeout": 60, "timeuot": 60}, "extra": 1});
+    assert_eq!(unknown_keys(&config), vec!["extra", "grok.timeuot"]);
+}
+
+#[test]
+fn models_accept_only_per_model_keys() {
+    let config = json!({"models": {"grok-4": {"timeout": 60, "cf_clearance": "x"}}});
+    assert_eq!(unknown_keys(&config), vec!["models.grok-4.cf_clearance"]);
+    let schema = json_schema(&json!({}));
+    let properties = schema["properties"]["models"]["additionalProperties"]["properties"]
+        .as_object()
+        .unwrap();
+    assert_eq!(properties.len(), MODEL_KEYS.len());
+    assert!(MODEL_KEYS.iter().all(|key| properties.contains_key(*key)));
+}
+
+#[test]
+fn json_schema_covers_every_field_with_defaults() {
+    let schema = json_schema(&json!({"app": {"image_format": "url"}}));
+    let format = &schema["properties"]["app"]["properties"]["image_format"];
+    assert_eq!(format["enum"], json!(["url", "base64"]));
+    assert_eq!(format["default"], "url");
+    let timeout = &schema["properties"]["grok"]["properties"]["timeout"];
+    assert_eq!(timeout["type"], "integer");
+    for spec in FIELDS {
+        let (section, key) = spec.key.split_once('.').unwrap();
+        assert!(schema["properties"][section]["properties"][key].is_object());
+    }
+}
+
+#[test]
+fn repair_reports_fields_it_reset() {
+    let defaults = json!({"grok": {"timeout": 120}, "app": {"image_format": "url"}});
+    let mut config = json!({
+        "grok": {"timeout": "abc"},
+        "app": {"image_format": "url"},
+        "models": {"grok-4": {"timeout": -1}},
+    });
+    let fields: Vec<String> = repair_invalid(&mut config, &defaults)
+        .into_iter()
+        .map(|e| e.field)
+        .collect();
+    assert_eq!(fields, vec!["grok.timeout", "models.grok-4.timeout"]);
+    assert_eq!(
+        config,
+        json!({
+            "grok": {"timeout": 120},
+            "app": {"image_format": "url"},
+            "models": {"grok-4": {}},
+        })
+    );
+}
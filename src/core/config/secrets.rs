@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+use serde_json::Value as JsonValue;
+
+use super::path::{self, Segment};
+use super::schema::{PROXY_URL_PATHS, SECRET_KEY_PATHS, SECRET_PATHS};
+use crate::core::redact::fingerprint;
+use crate::services::proxy::mask_proxy_url;
+
+type Visitor<'a, E> = &'a mut dyn FnMut(&mut String) -> Result<(), E>;
+type KindVisitor<'a, E> = &'a mut dyn FnMut(Kind, &mut String) -> Result<(), E>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Value,
+    Key,
+    ProxyUrl,
+}
+
+fn patterns() -> impl Iterator<Item = (Vec<&'static str>, Kind)> {
+    let split = |kind| move |pattern: &&'static str| (pattern.split('.').collect(), kind);
+    SECRET_PATHS
+        .iter()
+        .map(split(Kind::Value))
+        .chain(SECRET_KEY_PATHS.iter().map(split(Kind::Key)))
+        .chain(PROXY_URL_PATHS.iter().map(split(Kind::ProxyUrl)))
+}
+
+fn walk<E>(
+    value: &mut JsonValue,
+    pattern: &[&str],
+    leaf: &mut dyn FnMut(&mut JsonValue) -> Result<(), E>,
+) -> Result<(), E> {
+    let Some((head, rest)) = pattern.split_first() else {
+        return leaf(value);
+    };
+    match value {
+        JsonValue::Object(map) => {
+            for (key, child) in map.iter_mut() {
+                if *head == "*" || *head == key.as_str() {
+                    walk(child, rest, leaf)?;
+                }
+            }
+        }
+        JsonValue::Array(items) => {
+            for (index, child) in items.iter_mut().enumerate() {
+                if *head == "*" || head.parse::<usize>() == Ok(index) {
+                    walk(child, rest, leaf)?;
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+pub fn visit<E>(value: &mut JsonValue, pattern: &[&str], f: Visitor<'_, E>) -> Result<(), E> {
+    walk(value, pattern, &mut |leaf| match leaf {
+        JsonValue::String(s) if !s.is_empty() => f(s),
+        _ => Ok(()),
+    })
+}
+
+pub fn visit_keys<E>(value: &mut JsonValue, pattern: &[&str], f: Visitor<'_, E>) -> Result<(), E> {
+    walk(value, pattern, &mut |leaf| {
+        let JsonValue::Object(map) = leaf else {
+            return Ok(());
+        };
+        let mut renamed = serde_json::Map::new();
+        for (key, child) in map.iter() {
+            let mut key = key.clone();
+            if !key.is_empty() {
+                f(&mut key)?;
+            }
+            renamed.insert(key, child.clone());
+        }
+        *map = renamed;
+        Ok(())
+    })
+}
+
+pub fn visit_all<E>(value: &mut JsonValue, patterns: &[&str], f: Visitor<'_, E>) -> Result<(), E> {
+    for pattern in patterns {
+        let pattern: Vec<&str> = pattern.split('.').collect();
+        visit(value, &pattern, f)?;
+    }
+    Ok(())
+}
+
+fn visit_kind<E>(
+    value: &mut JsonValue,
+    pattern: &[&str],
+    kind: Kind,
+    f: KindVisitor<'_, E>,
+) -> Result<(), E> {
+    let visitor = &mut |s: &mut String| f(kind, s);
+    match kind {
+        Kind::Key => visit_keys(value, pattern, visitor),
+        Kind::Value | Kind::ProxyUrl => visit(value, pattern, visitor),
+    }
+}
+
+fn each_secret<E>(config: &mut JsonValue, f: KindVisitor<'_, E>) -> Result<(), E> {
+    for (pattern, kind) in patterns() {
+        visit_kind(config, &pattern, kind, f)?;
+    }
+    Ok(())
+}
+
+fn each_diff_secret<E>(entry: &mut JsonValue, f: KindVisitor<'_, E>) -> Result<(), E> {
+    let Some(at) = entry.get("key").and_then(|k| k.as_str()).and_then(keys) else {
+        return Ok(());
+    };
+    for (pattern, kind) in patterns() {
+        if !covers(&at, &pattern) {
+            continue;
+        }
+        for side in ["before", "after"] {
+            if let Some(value) = entry.get_mut(side) {
+                visit_kind(value, &pattern[at.len()..], kind, f)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn visit_secrets<E>(config: &mut JsonValue, f: Visitor<'_, E>) -> Result<(), E> {
+    each_secret(config, &mut |_, s| f(s))
+}
+
+pub fn visit_diff_entry<E>(entry: &mut JsonValue, f: Visitor<'_, E>) -> Result<(), E> {
+    each_diff_secret(entry, &mut |_, s| f(s))
+}
+
+pub fn is_secret(key: &str) -> bool {
+    let Some(at) = keys(key) else {
+        return false;
+    };
+    patterns().any(|(pattern, _)| pattern.len() == at.len() && covers(&at, &pattern))
+}
+
+fn keys(key: &str) -> Option<Vec<String>> {
+    let segments = path::parse(key)?;
+    Some(
+        segments
+            .into_iter()
+            .map(|segment| match segment {
+                Segment::Key(key) => key,
+                Segment::Index(index) => index.to_string(),
+            })
+            .collect(),
+    )
+}
+
+fn covers(at: &[String], pattern: &[&str]) -> bool {
+    pattern.len() >= at.len()
+        && at
+            .iter()
+            .zip(pattern)
+            .all(|(a, p)| *p == "*" || *p == a.as_str())
+}
+
+fn masked(kind: Kind, s: &str) -> String {
+    match kind {
+        Kind::ProxyUrl => mask_proxy_url(s),
+        Kind::Value | Kind::Key => fingerprint(s),
+    }
+}
+
+fn mask_value(kind: Kind, s: &mut String) -> Result<(), Infallible> {
+    *s = masked(kind, s);
+    Ok(())
+}
+
+pub fn mask(config: &mut JsonValue) {
+    let Ok(()) = each_secret(config, &mut mask_value);
+}
+
+pub fn mask_diff(entries: &mut JsonValue) {
+    for entry in entries.as_array_mut().into_iter().flatten() {
+        let Ok(()) = each_diff_secret(entry, &mut mask_value);
+    }
+}
+
+/// A secret posted back in its masked form keeps the current value.
+pub fn restore(posted: &mut JsonValue, current: &JsonValue) {
+    let mut known = HashMap::new();
+    let Ok(()) = each_secret(&mut current.clone(), &mut |kind, s: &mut String| {
+        let mask = masked(kind, s);
+        if mask != *s {
+            known.insert(mask, s.clone());
+        }
+        Ok::<(), Infallible>(())
+    });
+    let Ok(()) = each_secret(posted, &mut |_, s: &mut String| {
+        if let Some(original) = known.get(s.as_str()) {
+            *s = original.clone();
+        }
+        Ok::<(), Infallible>(())
+    });
+}
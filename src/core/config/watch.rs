@@ -0,0 +1,157 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use serde_json::Value as JsonValue;
+use tokio::sync::broadcast;
+
+use crate::core::config::{get_or_init_config, path};
+use crate::core::storage::{Storage, StorageChange, get_storage};
+
+const CHANGES_CAPACITY: usize = 64;
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+static CHANGES: Lazy<broadcast::Sender<Arc<ConfigChange>>> =
+    Lazy::new(|| broadcast::channel(CHANGES_CAPACITY).0);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigChange {
+    pub keys: Vec<String>,
+}
+
+impl ConfigChange {
+    /// A bare section name matches any key in it.
+    pub fn touches(&self, key: &str) -> bool {
+        self.keys.iter().any(|changed| {
+            changed == key
+                || changed
+                    .strip_prefix(key)
+                    .is_some_and(|rest| rest.starts_with('.'))
+                || key
+                    .strip_prefix(changed.as_str())
+                    .is_some_and(|rest| rest.starts_with('.'))
+        })
+    }
+}
+
+pub fn subscribe() -> broadcast::Receiver<Arc<ConfigChange>> {
+    CHANGES.subscribe()
+}
+
+pub(crate) fn publish(keys: Vec<String>) {
+    if keys.is_empty() {
+        return;
+    }
+    tracing::debug!("Config changed: {}", keys.join(", "));
+    let _ = CHANGES.send(Arc::new(ConfigChange { keys }));
+}
+
+/// Runs `handler` once now and after each change; a lagged receiver re-runs it.
+pub fn on_change<F, Fut>(keys: &'static [&'static str], handler: F)
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    let mut changes = subscribe();
+    tokio::spawn(async move {
+        handler().await;
+        loop {
+            match changes.recv().await {
+                Ok(change) if keys.iter().any(|key| change.touches(key)) => {}
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+            handler().await;
+        }
+    });
+}
+
+pub fn changed_keys(old: &JsonValue, new: &JsonValue) -> Vec<String> {
+    let empty = serde_json::Map::new();
+    let old_map = old.as_object().unwrap_or(&empty);
+    let new_map = new.as_object().unwrap_or(&empty);
+    let mut sections: Vec<&String> = old_map.keys().chain(new_map.keys()).collect();
+    sections.sort();
+    sections.dedup();
+
+    let mut keys = Vec::new();
+    for section in sections {
+        let (before, after) = (old_map.get(section), new_map.get(section));
+        // A missing section compares like an empty one, so its keys are listed.
+        match (
+            before.map_or(Some(&empty), |v| v.as_object()),
+            after.map_or(Some(&empty), |v| v.as_object()),
+        ) {
+            (Some(before), Some(after)) => {
+                let mut names: Vec<&String> = before.keys().chain(after.keys()).collect();
+                names.sort();
+                names.dedup();
+                keys.extend(
+                    names
+                        .into_iter()
+                        .filter(|name| before.get(*name) != after.get(*name))
+                        .map(|name| path::join(&[section, name])),
+                );
+            }
+            _ if before != after => keys.push(path::quote(section)),
+            _ => {}
+        }
+    }
+    keys
+}
+
+pub fn start_config_watcher() {
+    match get_storage().subscribe_changes() {
+        Some(changes) => {
+            tokio::spawn(follow_remote(changes));
+        }
+        None => {
+            tokio::spawn(poll_storage());
+        }
+    }
+}
+
+async fn follow_remote(mut changes: broadcast::Receiver<StorageChange>) {
+    loop {
+        match changes.recv().await {
+            Ok(StorageChange::Config) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+            Ok(_) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+        match get_or_init_config().await.reload().await {
+            Ok(()) => tracing::info!("Config reloaded after remote change"),
+            Err(err) => tracing::warn!("Config reload failed: {err}"),
+        }
+    }
+}
+
+async fn poll_storage() {
+    let storage = get_storage();
+    let mut last = storage.load_config().await.ok();
+    let mut last_error: Option<String> = None;
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        match storage.load_config().await {
+            Ok(stored) => {
+                last_error = None;
+                if last.as_ref() == Some(&stored) {
+                    continue;
+                }
+                last = Some(stored.clone());
+                if get_or_init_config().await.apply_stored(&stored).await {
+                    tracing::info!("Config reloaded from storage");
+                }
+            }
+            // A half-saved file fails to parse; keep the running config.
+            Err(err) => {
+                let message = err.to_string();
+                if last_error.as_ref() != Some(&message) {
+                    tracing::warn!("Config reload failed: {message}");
+                    last_error = Some(message);
+                }
+            }
+        }
+    }
+}
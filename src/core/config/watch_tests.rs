@@ -0,0 +1,38 @@
+use serde_json::json;
+
+use crate::core::config::watch::{ConfigChange, changed_keys};
+
+#[test]
+fn changed_keys_lists_added_removed_and_modified_entries() {
+    let old = json!({
+        "grok": {"timeout": 120, "stream": true},
+        "token": {"refresh_interval_hours": 8},
+        "legacy": 1,
+    });
+    let new = json!({
+        "grok": {"timeout": 60, "stream": true, "thinking": false},
+        "token": {"refresh_interval_hours": 8},
+        "performance": {"media_max_concurrent": 10},
+    });
+    assert_eq!(
+        changed_keys(&old, &new),
+        vec![
+            "grok.thinking",
+            "grok.timeout",
+            "legacy",
+            "performance.media_max_concurrent",
+        ]
+    );
+    assert!(changed_keys(&new, &new).is_empty());
+}
+
+#[test]
+fn touches_matches_keys_and_sections() {
+    let change = ConfigChange {
+        keys: vec!["performance.media_max_concurrent".to_string()],
+    };
+    assert!(change.touches("performance.media_max_concurrent"));
+    assert!(change.touches("performance"));
+    assert!(!change.touches("performance.media"));
+    assert!(!change.touches("token.refresh_interval_hours"));
+}
@@ -33,6 +33,7 @@ pub struct ErrorResponse {
 pub struct ApiError {
     pub status: StatusCode,
     pub body: ErrorBody,
+    pub upstream_status: Option<u16>,
 }
 
 impl ApiError {
@@ -45,6 +46,7 @@ impl ApiError {
                 param: None,
                 code: None,
             },
+            upstream_status: None,
         }
     }
 
@@ -58,6 +60,11 @@ impl ApiError {
         self
     }
 
+    pub fn with_upstream_status(mut self, status: u16) -> Self {
+        self.upstream_status = Some(status);
+        self
+    }
+
     pub fn invalid_request(message: impl Into<String>) -> Self {
         ApiError::new(
             StatusCode::BAD_REQUEST,
@@ -117,7 +124,8 @@ impl std::fmt::Display for ApiError {
 }
 
 impl IntoResponse for ApiError {
-    fn into_response(self) -> Response {
+    fn into_response(mut self) -> Response {
+        self.body.message = crate::core::redact::redact(&self.body.message);
         let body = ErrorResponse { error: self.body };
         (self.status, Json(body)).into_response()
     }
@@ -1,7 +1,12 @@
 pub mod auth;
+#[cfg(test)]
+mod auth_tests;
 pub mod batch_tasks;
 pub mod config;
 pub mod exceptions;
+pub mod redact;
+#[cfg(test)]
+mod redact_tests;
 pub mod response_middleware;
 pub mod static_assets;
 pub mod storage;
@@ -0,0 +1,188 @@
+use std::io::{self, Write};
+
+use sha2::{Digest, Sha256};
+use tracing_subscriber::fmt::MakeWriter;
+
+const FINGERPRINT_PREFIX: &str = "fp_";
+const FINGERPRINT_HEX: usize = 12;
+const REDACTED: &str = "[redacted]";
+
+const HEADERS: &[&str] = &["cookie", "set-cookie", "authorization"];
+const COOKIES: &[&str] = &["sso", "sso-rw", "cf_clearance"];
+
+/// The cookie form `sso=<token>` shares the bare token's fingerprint.
+pub fn fingerprint(secret: &str) -> String {
+    let secret = secret.trim();
+    let secret = secret.strip_prefix("sso=").unwrap_or(secret);
+    let digest = Sha256::digest(secret.as_bytes());
+    let hex: String = digest
+        .iter()
+        .take(FINGERPRINT_HEX / 2)
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    format!("{FINGERPRINT_PREFIX}{hex}")
+}
+
+pub fn is_fingerprint(value: &str) -> bool {
+    value.len() == FINGERPRINT_PREFIX.len() + FINGERPRINT_HEX
+        && value.starts_with(FINGERPRINT_PREFIX)
+        && value[FINGERPRINT_PREFIX.len()..]
+            .bytes()
+            .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+pub fn redact(text: &str) -> String {
+    let mut out = text.to_string();
+    for name in HEADERS {
+        out = rewrite(&out, name, header_value, |_| REDACTED.to_string());
+    }
+    out = rewrite(&out, "bearer ", bearer_value, |_| REDACTED.to_string());
+    for name in COOKIES {
+        out = rewrite(&out, &format!("{name}="), cookie_value, fingerprint);
+    }
+    rewrite(&out, "eyj", jwt_value, fingerprint)
+}
+
+/// `needle` must be lowercase ASCII; matching is case-insensitive.
+fn rewrite(
+    text: &str,
+    needle: &str,
+    value: fn(&str, usize) -> Option<(usize, usize)>,
+    replace: impl Fn(&str) -> String,
+) -> String {
+    let lower = text.to_ascii_lowercase();
+    let mut out = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut from = 0;
+    while let Some(found) = lower[from..].find(needle) {
+        let start = from + found;
+        let after = start + needle.len();
+        from = after;
+        if !word_start(text, start) {
+            continue;
+        }
+        let Some((begin, end)) = value(text, after).filter(|(b, e)| b < e) else {
+            continue;
+        };
+        let current = &text[begin..end];
+        if current == REDACTED || is_fingerprint(current) {
+            continue;
+        }
+        out.push_str(&text[copied..begin]);
+        out.push_str(&replace(current));
+        copied = end;
+        from = end;
+    }
+    out.push_str(&text[copied..]);
+    out
+}
+
+fn word_start(text: &str, index: usize) -> bool {
+    text[..index]
+        .chars()
+        .next_back()
+        .is_none_or(|c| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+}
+
+fn skip(bytes: &[u8], mut at: usize, chars: &[u8]) -> usize {
+    while at < bytes.len() && chars.contains(&bytes[at]) {
+        at += 1;
+    }
+    at
+}
+
+fn scan_until(bytes: &[u8], at: usize, stop: impl Fn(u8) -> bool) -> usize {
+    bytes[at..]
+        .iter()
+        .position(|&b| stop(b))
+        .map_or(bytes.len(), |p| at + p)
+}
+
+/// `Cookie: value` runs to end of line; `"cookie": "value"` to the closing quote.
+fn header_value(text: &str, at: usize) -> Option<(usize, usize)> {
+    let bytes = text.as_bytes();
+    let at = skip(bytes, at, b"\"'\\ \t");
+    if bytes.get(at) != Some(&b':') {
+        return None;
+    }
+    let at = skip(bytes, at + 1, b" \t");
+    if bytes.get(at) == Some(&b'"') {
+        let begin = at + 1;
+        let mut escaped = false;
+        let end = text[begin..]
+            .char_indices()
+            .find(|&(_, c)| {
+                let close = !escaped && (c == '"' || c == '\n');
+                escaped = !escaped && c == '\\';
+                close
+            })
+            .map_or(text.len(), |(i, _)| begin + i);
+        return Some((begin, end));
+    }
+    Some((at, scan_until(bytes, at, |b| b == b'\n' || b == b'\r')))
+}
+
+fn cookie_value(text: &str, at: usize) -> Option<(usize, usize)> {
+    let bytes = text.as_bytes();
+    let end = scan_until(bytes, at, |b| {
+        b.is_ascii_whitespace() || matches!(b, b';' | b',' | b'&' | b'"' | b'\'' | b'\\')
+    });
+    Some((at, end))
+}
+
+fn bearer_value(text: &str, at: usize) -> Option<(usize, usize)> {
+    let bytes = text.as_bytes();
+    let at = skip(bytes, at, b" ");
+    let end = scan_until(bytes, at, |b| {
+        b.is_ascii_whitespace() || matches!(b, b',' | b';' | b'"' | b'\'' | b'\\')
+    });
+    Some((at, end))
+}
+
+fn jwt_value(text: &str, at: usize) -> Option<(usize, usize)> {
+    let begin = at - 3;
+    if &text[begin..at] != "eyJ" {
+        return None;
+    }
+    let bytes = text.as_bytes();
+    let end = scan_until(bytes, at, |b| {
+        !(b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b'='))
+    });
+    let candidate = &text[begin..end];
+    (candidate.len() >= 20 && candidate.contains('.')).then_some((begin, end))
+}
+
+/// Buffers each event so [`redact`] sees it whole before it reaches stdout.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RedactingStdout;
+
+impl<'a> MakeWriter<'a> for RedactingStdout {
+    type Writer = RedactingWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter(Vec::new())
+    }
+}
+
+pub struct RedactingWriter(Vec<u8>);
+
+impl Write for RedactingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for RedactingWriter {
+    fn drop(&mut self) {
+        if self.0.is_empty() {
+            return;
+        }
+        let text = redact(&String::from_utf8_lossy(&self.0));
+        let _ = io::stdout().lock().write_all(text.as_bytes());
+    }
+}
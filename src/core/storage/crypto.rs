@@ -0,0 +1,330 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use once_cell::sync::OnceCell;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
+
+use super::env_or;
+use crate::core::config::secrets;
+use crate::core::storage::{Storage, StorageBackend, StorageError, StorageSpec};
+
+pub const KEY_ENV: &str = "SERVER_STORAGE_ENCRYPTION_KEY";
+/// One key per line; the first seals when `KEY_ENV` is unset, the rest only open.
+pub const KEY_FILE_ENV: &str = "SERVER_STORAGE_ENCRYPTION_KEY_FILE";
+pub const OLD_KEYS_ENV: &str = "SERVER_STORAGE_ENCRYPTION_OLD_KEYS";
+
+const PREFIX: &str = "enc:v1:";
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const DECRYPT_ERROR: &str = "decrypt failed";
+const LOCK_TIMEOUT_SEC: u64 = 30;
+
+const TOKEN_SECRET_PATHS: &[&str] = &["*.*.token", "*.*.cf_clearance", "*.*.proxy_url"];
+
+static KEYRING: OnceCell<Keyring> = OnceCell::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Document {
+    Config,
+    Tokens,
+    ConfigHistory,
+}
+
+struct MasterKey {
+    id: String,
+    cipher: Aes256Gcm,
+}
+
+impl MasterKey {
+    fn parse(encoded: &str) -> Result<Self, String> {
+        let bytes = STANDARD
+            .decode(encoded.trim())
+            .map_err(|e| format!("encryption key is not valid base64: {e}"))?;
+        if bytes.len() != KEY_LEN {
+            return Err(format!(
+                "encryption key must be {KEY_LEN} bytes, got {}",
+                bytes.len()
+            ));
+        }
+        let digest = Sha256::digest(&bytes);
+        Ok(Self {
+            id: digest[..4].iter().map(|b| format!("{b:02x}")).collect(),
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&bytes)),
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct Keyring {
+    primary: Option<MasterKey>,
+    retired: Vec<MasterKey>,
+}
+
+impl Keyring {
+    pub fn new(primary: Option<&str>, retired: &[&str]) -> Result<Self, String> {
+        Ok(Self {
+            primary: primary.map(MasterKey::parse).transpose()?,
+            retired: retired
+                .iter()
+                .map(|k| MasterKey::parse(k))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    pub fn from_env() -> Result<Self, String> {
+        let mut keys: Vec<String> = Vec::new();
+        let key = env_or(KEY_ENV, "");
+        if !key.is_empty() {
+            keys.push(key);
+        }
+        let file = env_or(KEY_FILE_ENV, "");
+        if !file.is_empty() {
+            let text = std::fs::read_to_string(&file)
+                .map_err(|e| format!("read {KEY_FILE_ENV} {file} failed: {e}"))?;
+            keys.extend(
+                text.lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(str::to_string),
+            );
+        }
+        let (primary, mut retired): (Option<&str>, Vec<&str>) = match keys.split_first() {
+            Some((primary, rest)) => (
+                Some(primary.as_str()),
+                rest.iter().map(String::as_str).collect(),
+            ),
+            None => (None, Vec::new()),
+        };
+        let old_keys = env_or(OLD_KEYS_ENV, "");
+        retired.extend(old_keys.split(',').map(str::trim).filter(|k| !k.is_empty()));
+        Self::new(primary, &retired)
+    }
+
+    pub fn primary_id(&self) -> Option<&str> {
+        self.primary.as_ref().map(|k| k.id.as_str())
+    }
+
+    fn find(&self, id: &str) -> Option<&MasterKey> {
+        self.primary
+            .iter()
+            .chain(self.retired.iter())
+            .find(|k| k.id == id)
+    }
+
+    pub fn seal(&self, plain: &str) -> Result<String, StorageError> {
+        let Some(key) = &self.primary else {
+            return Ok(plain.to_string());
+        };
+        if plain.is_empty() {
+            return Ok(String::new());
+        }
+        let data_key = Aes256Gcm::generate_key(OsRng);
+        let wrapped = encrypt(&key.cipher, data_key.as_slice())?;
+        let body = encrypt(&Aes256Gcm::new(&data_key), plain.as_bytes())?;
+        Ok(format!("{PREFIX}{}:{wrapped}:{body}", key.id))
+    }
+
+    /// Values without `PREFIX` predate encryption and pass through.
+    pub fn open(&self, value: &str) -> Result<String, StorageError> {
+        let Some(rest) = value.strip_prefix(PREFIX) else {
+            return Ok(value.to_string());
+        };
+        let mut parts = rest.splitn(3, ':');
+        let (Some(id), Some(wrapped), Some(body)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(decrypt_error("malformed sealed value"));
+        };
+        let key = self.find(id).ok_or_else(|| {
+            decrypt_error(format!(
+                "value sealed with unknown key {id}; set {KEY_ENV} or list the key in {OLD_KEYS_ENV}"
+            ))
+        })?;
+        let data_key = decrypt(&key.cipher, wrapped)?;
+        if data_key.len() != KEY_LEN {
+            return Err(decrypt_error("wrapped data key has the wrong length"));
+        }
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&data_key));
+        String::from_utf8(decrypt(&cipher, body)?).map_err(|_| decrypt_error("value is not UTF-8"))
+    }
+
+    pub fn seal_document(
+        &self,
+        doc: &JsonValue,
+        document: Document,
+    ) -> Result<JsonValue, StorageError> {
+        rewrite(doc, document, &mut |value| self.seal(value))
+    }
+
+    pub fn open_document(
+        &self,
+        doc: &JsonValue,
+        document: Document,
+    ) -> Result<JsonValue, StorageError> {
+        rewrite(doc, document, &mut |value| self.open(value))
+    }
+}
+
+fn decrypt_error(detail: impl std::fmt::Display) -> StorageError {
+    StorageError(format!("{DECRYPT_ERROR}: {detail}"))
+}
+
+fn encrypt(cipher: &Aes256Gcm, plain: &[u8]) -> Result<String, StorageError> {
+    let nonce = Aes256Gcm::generate_nonce(OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plain)
+        .map_err(|_| StorageError("encrypt failed".to_string()))?;
+    Ok(STANDARD.encode([nonce.as_slice(), &ciphertext].concat()))
+}
+
+fn decrypt(cipher: &Aes256Gcm, encoded: &str) -> Result<Vec<u8>, StorageError> {
+    let bytes = STANDARD
+        .decode(encoded)
+        .map_err(|_| decrypt_error("sealed value is not valid base64"))?;
+    if bytes.len() < NONCE_LEN {
+        return Err(decrypt_error("sealed value is truncated"));
+    }
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| decrypt_error("wrong key or corrupted value"))
+}
+
+fn rewrite(
+    doc: &JsonValue,
+    document: Document,
+    f: &mut dyn FnMut(&str) -> Result<String, StorageError>,
+) -> Result<JsonValue, StorageError> {
+    let mut doc = doc.clone();
+    let f = &mut |s: &mut String| {
+        *s = f(s)?;
+        Ok::<(), StorageError>(())
+    };
+    match document {
+        Document::Config => secrets::visit_secrets(&mut doc, f)?,
+        Document::Tokens => secrets::visit_all(&mut doc, TOKEN_SECRET_PATHS, f)?,
+        Document::ConfigHistory => {
+            for version in doc.as_array_mut().into_iter().flatten() {
+                if let Some(snapshot) = version.get_mut("snapshot") {
+                    secrets::visit_secrets(snapshot, f)?;
+                }
+                let entries = version.get_mut("diff").and_then(|d| d.as_array_mut());
+                for entry in entries.into_iter().flatten() {
+                    secrets::visit_diff_entry(entry, f)?;
+                }
+            }
+        }
+    }
+    Ok(doc)
+}
+
+pub fn init_keyring() -> Result<(), String> {
+    let keyring = Keyring::from_env()?;
+    match keyring.primary_id() {
+        Some(id) => tracing::info!("Storage encryption enabled (key {id})"),
+        None if !keyring.retired.is_empty() => {
+            tracing::warn!(
+                "Storage encryption has only retired keys; secrets are saved in plaintext"
+            )
+        }
+        None => {}
+    }
+    let _ = KEYRING.set(keyring);
+    Ok(())
+}
+
+pub fn keyring() -> &'static Keyring {
+    KEYRING.get_or_init(|| {
+        Keyring::from_env().unwrap_or_else(|err| {
+            tracing::error!("Storage encryption key unusable: {err}");
+            Keyring::default()
+        })
+    })
+}
+
+pub fn is_decrypt_error(err: &StorageError) -> bool {
+    err.0.starts_with(DECRYPT_ERROR)
+}
+
+/// A missing key must stop startup before anything is saved over sealed data.
+pub async fn check_readable(storage: &StorageBackend) -> Result<(), StorageError> {
+    for result in [
+        storage.load_config().await,
+        storage.load_tokens().await,
+        storage.load_config_history().await,
+    ] {
+        match result {
+            Err(err) if is_decrypt_error(&err) => return Err(err),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReencryptReport {
+    pub storage: &'static str,
+    pub key: Option<String>,
+    pub tokens: usize,
+    pub config_versions: usize,
+}
+
+pub async fn reencrypt_storage(storage: &StorageBackend) -> Result<ReencryptReport, StorageError> {
+    let tokens = storage
+        .with_lock("tokens_save", LOCK_TIMEOUT_SEC, || async {
+            let tokens = storage.load_tokens().await?;
+            storage.save_tokens(&tokens).await?;
+            Ok(tokens)
+        })
+        .await?;
+    let history = storage
+        .with_lock("config_save", LOCK_TIMEOUT_SEC, || async {
+            let config = storage.load_config().await?;
+            storage.save_config(&config).await?;
+            let history = storage.load_config_history().await?;
+            storage.save_config_history(&history).await?;
+            Ok(history)
+        })
+        .await?;
+    let report = ReencryptReport {
+        storage: storage.kind(),
+        key: keyring().primary_id().map(str::to_string),
+        tokens: tokens
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter_map(|(_, list)| list.as_array())
+            .map(Vec::len)
+            .sum(),
+        config_versions: history.as_array().map_or(0, Vec::len),
+    };
+    tracing::info!(
+        "Storage re-encrypted: {} tokens, {} config versions, key {}",
+        report.tokens,
+        report.config_versions,
+        report.key.as_deref().unwrap_or("none")
+    );
+    Ok(report)
+}
+
+pub async fn run_cli() -> i32 {
+    let result = match StorageSpec::from_env().open() {
+        Ok(storage) => reencrypt_storage(&storage).await,
+        Err(err) => Err(err),
+    };
+    match result {
+        Ok(report) => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&report).unwrap_or_default()
+            );
+            0
+        }
+        Err(err) => {
+            eprintln!("storage re-encryption failed: {err}");
+            1
+        }
+    }
+}
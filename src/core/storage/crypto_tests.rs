@@ -0,0 +1,223 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::json;
+
+use crate::core::storage::crypto::{Document, Keyring};
+
+fn key(byte: u8) -> String {
+    STANDARD.encode([byte; 32])
+}
+
+fn ring(primary: Option<u8>, retired: &[u8]) -> Keyring {
+    let primary = primary.map(key);
+    let retired: Vec<String> = retired.iter().copied().map(key).collect();
+    let retired: Vec<&str> = retired.iter().map(String::as_str).collect();
+    Keyring::new(primary.as_deref(), &retired).unwrap()
+}
+
+#[test]
+fn seals_and_opens_values() {
+    let keyring = ring(Some(1), &[]);
+    let sealed = keyring.seal("sso-secret").unwrap();
+    assert!(sealed.starts_with("enc:v1:"));
+    assert!(!sealed.contains("sso-secret"));
+    assert_eq!(ring(Some(1), &[]).open(&sealed).unwrap(), "sso-secret");
+}
+
+#[test]
+fn plaintext_passes_through() {
+    assert_eq!(ring(Some(1), &[]).open("legacy").unwrap(), "legacy");
+    assert_eq!(ring(None, &[]).seal("legacy").unwrap(), "legacy");
+}
+
+#[test]
+fn rejects_bad_keys() {
+    assert!(Keyring::new(Some("not base64!"), &[]).is_err());
+    assert!(Keyring::new(Some(&STANDARD.encode([1u8; 16])), &[]).is_err());
+}
+
+#[test]
+fn unknown_or_wrong_key_fails_to_open() {
+    let sealed = ring(Some(1), &[]).seal("sso-secret").unwrap();
+    assert!(ring(Some(2), &[]).open(&sealed).is_err());
+    assert!(ring(None, &[]).open(&sealed).is_err());
+
+    let mut tampered = sealed.clone();
+    let last = tampered.pop().unwrap();
+    tampered.push(if last == 'A' { 'B' } else { 'A' });
+    assert!(ring(Some(1), &[]).open(&tampered).is_err());
+}
+
+#[test]
+fn rotation_reseals_with_the_new_key() {
+    let old = ring(Some(1), &[]).seal("sso-secret").unwrap();
+    let rotated = ring(Some(2), &[1]);
+    assert_eq!(rotated.open(&old).unwrap(), "sso-secret");
+    let resealed = rotated.seal("sso-secret").unwrap();
+    assert_ne!(resealed, old);
+    assert_eq!(ring(Some(2), &[]).open(&resealed).unwrap(), "sso-secret");
+
+    let decrypt_only = ring(None, &[2]);
+    assert_eq!(decrypt_only.open(&resealed).unwrap(), "sso-secret");
+    assert_eq!(decrypt_only.seal("sso-secret").unwrap(), "sso-secret");
+}
+
+#[test]
+fn config_document_seals_only_secret_fields() {
+    let keyring = ring(Some(1), &[]);
+    let config = json!({
+        "app": {"app_key": "admin", "api_key": "", "image_format": "url"},
+        "grok": {"cf_clearance": "cf", "timeout": 120},
+        "models": {"grok-4": {"timeout": 600}},
+        "webhook": {"sinks": [{"url": "https://example.com", "secret": "hmac"}]},
+    });
+    let sealed = keyring.seal_document(&config, Document::Config).unwrap();
+    for value in [
+        &sealed["app"]["app_key"],
+        &sealed["grok"]["cf_clearance"],
+        &sealed["webhook"]["sinks"][0]["secret"],
+    ] {
+        assert!(value.as_str().unwrap().starts_with("enc:v1:"), "{value}");
+    }
+    assert_eq!(sealed["app"]["api_key"], "");
+    assert_eq!(sealed["app"]["image_format"], "url");
+    assert_eq!(sealed["models"]["grok-4"]["timeout"], 600);
+    assert_eq!(
+        keyring.open_document(&sealed, Document::Config).unwrap(),
+        config
+    );
+}
+
+#[test]
+fn token_document_seals_token_credentials() {
+    let keyring = ring(Some(1), &[]);
+    let tokens = json!({"ssoBasic": [{"token": "abc", "cf_clearance": null, "note": "n"}]});
+    let sealed = keyring.seal_document(&tokens, Document::Tokens).unwrap();
+    assert!(
+        sealed["ssoBasic"][0]["token"]
+            .as_str()
+            .unwrap()
+            .starts_with("enc:v1:")
+    );
+    assert_eq!(sealed["ssoBasic"][0]["note"], "n");
+    assert_eq!(
+        keyring.open_document(&sealed, Document::Tokens).unwrap(),
+        tokens
+    );
+}
+
+#[test]
+fn history_seals_snapshots_and_diffs() {
+    let keyring = ring(Some(1), &[]);
+    let history = json!([{
+        "version": 2,
+        "snapshot": {"app": {"app_key": "new"}},
+        "diff": [
+            {"key": "app.app_key", "before": "old", "after": "new"},
+            {"key": "webhook.sinks[0]", "before": null, "after": {"url": "https://h", "secret": "s"}},
+            {"key": "grok.timeout", "before": 60, "after": 120},
+        ],
+    }]);
+    let sealed = keyring
+        .seal_document(&history, Document::ConfigHistory)
+        .unwrap();
+    let version = &sealed[0];
+    assert!(is_sealed(&version["snapshot"]["app"]["app_key"]));
+    assert!(is_sealed(&version["diff"][0]["before"]));
+    assert!(is_sealed(&version["diff"][0]["after"]));
+    assert!(is_sealed(&version["diff"][1]["after"]["secret"]));
+    assert_eq!(version["diff"][1]["after"]["url"], "https://h");
+    assert_eq!(version["diff"][2]["after"], 120);
+    assert_eq!(
+        keyring
+            .open_document(&sealed, Document::ConfigHistory)
+            .unwrap(),
+        history
+    );
+}
+
+fn is_sealed(value: &serde_json::Value) -> bool {
+    value.as_str().is_some_and(|s| s.starts_with("enc:v1:"))
+}
+
+#[test]
+fn config_document_seals_api_key_tag_keys() {
+    let keyring = ring(Some(1), &[]);
+    let config = json!({"app": {"api_key_tags": {"sk-tenant": "vip", "sk-other": "-test"}}});
+    let sealed = keyring.seal_document(&config, Document::Config).unwrap();
+    let tags = sealed["app"]["api_key_tags"].as_object().unwrap();
+    assert_eq!(tags.len(), 2);
+    assert!(tags.keys().all(|k| k.starts_with("enc:v1:")), "{tags:?}");
+    assert!(tags.values().any(|v| v == "vip"));
+    assert_eq!(
+        keyring.open_document(&sealed, Document::Config).unwrap(),
+        config
+    );
+}
+
+#[test]
+fn config_document_seals_proxy_urls() {
+    let keyring = ring(Some(1), &[]);
+    let config = json!({
+        "grok": {"base_proxy_url": "http://u:p@h:1", "asset_proxy_url": "http://u:p@h:2"},
+        "proxy": {"pool": ["socks5://u:p@h:4", {"url": "http://u:p@h:5", "weight": 2}]},
+    });
+    let sealed = keyring.seal_document(&config, Document::Config).unwrap();
+    for value in [
+        &sealed["grok"]["base_proxy_url"],
+        &sealed["grok"]["asset_proxy_url"],
+        &sealed["proxy"]["pool"][0],
+        &sealed["proxy"]["pool"][1]["url"],
+    ] {
+        assert!(is_sealed(value), "{value}");
+    }
+    assert_eq!(sealed["proxy"]["pool"][1]["weight"], 2);
+    assert_eq!(
+        keyring.open_document(&sealed, Document::Config).unwrap(),
+        config
+    );
+}
+
+#[test]
+fn token_document_seals_bound_proxy_urls() {
+    let keyring = ring(Some(1), &[]);
+    let tokens = json!({"ssoBasic": [{"token": "abc", "proxy_url": "http://u:p@h:1"}]});
+    let sealed = keyring.seal_document(&tokens, Document::Tokens).unwrap();
+    assert!(is_sealed(&sealed["ssoBasic"][0]["proxy_url"]));
+    assert_eq!(
+        keyring.open_document(&sealed, Document::Tokens).unwrap(),
+        tokens
+    );
+}
+
+#[test]
+fn history_seals_tag_keys_and_proxies_in_diffs() {
+    let keyring = ring(Some(1), &[]);
+    let history = json!([{
+        "version": 3,
+        "snapshot": {"app": {"api_key_tags": {"sk-tenant": "vip"}}},
+        "diff": [
+            {"key": "app.api_key_tags", "before": {}, "after": {"sk-tenant": "vip"}},
+            {"key": "proxy.pool", "before": [], "after": ["http://u:p@h:1"]},
+        ],
+    }]);
+    let sealed = keyring
+        .seal_document(&history, Document::ConfigHistory)
+        .unwrap();
+    let version = &sealed[0];
+    let sealed_keys = |v: &serde_json::Value| {
+        v.as_object()
+            .unwrap()
+            .keys()
+            .all(|k| k.starts_with("enc:v1:"))
+    };
+    assert!(sealed_keys(&version["snapshot"]["app"]["api_key_tags"]));
+    assert!(sealed_keys(&version["diff"][0]["after"]));
+    assert!(is_sealed(&version["diff"][1]["after"][0]));
+    assert_eq!(
+        keyring
+            .open_document(&sealed, Document::ConfigHistory)
+            .unwrap(),
+        history
+    );
+}
@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+use crate::core::storage::StorageError;
+
+/// One in-process mutex per lock name, so sections on different names never
+/// queue behind each other before reaching the shared backend.
+#[derive(Default)]
+pub(crate) struct NamedLocks {
+    locks: std::sync::Mutex<HashMap<String, Arc<Mutex<()>>>>,
+}
+
+impl NamedLocks {
+    pub(crate) async fn lock(
+        &self,
+        name: &str,
+        deadline: Instant,
+    ) -> Result<OwnedMutexGuard<()>, StorageError> {
+        let lock = self
+            .locks
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default()
+            .clone();
+        tokio::time::timeout_at(deadline.into(), lock.lock_owned())
+            .await
+            .map_err(|_| StorageError(format!("lock timeout: {name}")))
+    }
+}
+
+/// Runs `section` while extending the lease every third of its length.
+/// `renew` returns `Ok(false)` once the lock belongs to someone else; the
+/// section is then dropped, since another holder may already be inside it.
+pub(crate) async fn run_leased<T, S, R, RF>(
+    name: &str,
+    lease: Duration,
+    section: S,
+    mut renew: R,
+) -> Result<T, StorageError>
+where
+    S: Future<Output = Result<T, StorageError>>,
+    R: FnMut() -> RF,
+    RF: Future<Output = Result<bool, StorageError>>,
+{
+    let period = (lease / 3).max(Duration::from_millis(10));
+    let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    tokio::pin!(section);
+    // The section keeps running while a renewal is in flight; it may hold the
+    // database write lock the renewal is waiting for.
+    let mut renewal: Option<Pin<Box<RF>>> = None;
+    loop {
+        tokio::select! {
+            result = &mut section => return result,
+            _ = ticker.tick(), if renewal.is_none() => renewal = Some(Box::pin(renew())),
+            renewed = async { renewal.as_mut().unwrap().await }, if renewal.is_some() => {
+                renewal = None;
+                match renewed {
+                    Ok(true) => {}
+                    Ok(false) => return Err(StorageError(format!("lock lease lost: {name}"))),
+                    // A later renewal still succeeds if the lease has not run out.
+                    Err(err) => tracing::warn!("Renew lock {name} failed: {err}"),
+                }
+            }
+        }
+    }
+}
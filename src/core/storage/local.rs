@@ -8,30 +8,7 @@ use serde_json::Value as JsonValue;
 use tokio::sync::Mutex;
 
 use crate::core::config::{config_to_toml, project_root, toml_to_json};
-
-#[derive(Debug, Clone)]
-pub struct StorageError(pub String);
-
-impl std::fmt::Display for StorageError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
-    }
-}
-
-impl std::error::Error for StorageError {}
-
-#[async_trait]
-pub trait Storage: Send + Sync {
-    async fn load_config(&self) -> Result<JsonValue, StorageError>;
-    async fn save_config(&self, data: &JsonValue) -> Result<(), StorageError>;
-    async fn load_tokens(&self) -> Result<JsonValue, StorageError>;
-    async fn save_tokens(&self, data: &JsonValue) -> Result<(), StorageError>;
-    async fn with_lock<F, Fut, T>(&self, name: &str, timeout: u64, f: F) -> Result<T, StorageError>
-    where
-        F: FnOnce() -> Fut + Send,
-        Fut: std::future::Future<Output = Result<T, StorageError>> + Send,
-        T: Send;
-}
+use crate::core::storage::{Storage, StorageError};
 
 pub struct LocalStorage {
     lock: Mutex<()>,
@@ -52,6 +29,10 @@ impl LocalStorage {
         project_root().join("data").join("token.json")
     }
 
+    fn history_path() -> PathBuf {
+        project_root().join("data").join("config_history.json")
+    }
+
     fn lock_dir() -> PathBuf {
         project_root().join("data").join(".locks")
     }
@@ -84,6 +65,10 @@ impl LocalStorage {
 
 #[async_trait]
 impl Storage for LocalStorage {
+    fn kind(&self) -> &'static str {
+        "local"
+    }
+
     async fn load_config(&self) -> Result<JsonValue, StorageError> {
         let path = Self::config_path();
         if !path.exists() {
@@ -148,6 +133,36 @@ impl Storage for LocalStorage {
         Ok(())
     }
 
+    async fn load_config_history(&self) -> Result<JsonValue, StorageError> {
+        let path = Self::history_path();
+        if !path.exists() {
+            return Ok(JsonValue::Array(Vec::new()));
+        }
+        let content = tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|e| StorageError(format!("read config history failed: {e}")))?;
+        serde_json::from_str(&content)
+            .map_err(|e| StorageError(format!("parse config history failed: {e}")))
+    }
+
+    async fn save_config_history(&self, data: &JsonValue) -> Result<(), StorageError> {
+        let path = Self::history_path();
+        let dir = path.parent().unwrap_or(Path::new("."));
+        tokio::fs::create_dir_all(dir)
+            .await
+            .map_err(|e| StorageError(format!("create config history dir failed: {e}")))?;
+        let content = serde_json::to_string_pretty(data)
+            .map_err(|e| StorageError(format!("serialize config history failed: {e}")))?;
+        let tmp_path = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, content)
+            .await
+            .map_err(|e| StorageError(format!("write tmp config history failed: {e}")))?;
+        tokio::fs::rename(&tmp_path, &path)
+            .await
+            .map_err(|e| StorageError(format!("rename config history failed: {e}")))?;
+        Ok(())
+    }
+
     async fn with_lock<F, Fut, T>(&self, name: &str, timeout: u64, f: F) -> Result<T, StorageError>
     where
         F: FnOnce() -> Fut + Send,
@@ -163,21 +178,3 @@ impl Storage for LocalStorage {
         result
     }
 }
-
-static STORAGE: once_cell::sync::OnceCell<std::sync::Arc<LocalStorage>> =
-    once_cell::sync::OnceCell::new();
-
-pub fn get_storage() -> std::sync::Arc<LocalStorage> {
-    STORAGE
-        .get_or_init(|| {
-            let storage_type =
-                std::env::var("SERVER_STORAGE_TYPE").unwrap_or_else(|_| "local".to_string());
-            if storage_type.to_lowercase() != "local" {
-                tracing::warn!(
-                    "Only local storage is supported in Rust version. Requested: {storage_type}"
-                );
-            }
-            std::sync::Arc::new(LocalStorage::new())
-        })
-        .clone()
-}
@@ -0,0 +1,284 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
+
+use crate::core::storage::{Storage, StorageBackend, StorageError, StorageSpec};
+use crate::services::token::transfer::mask_token;
+
+const LOCK_TIMEOUT_SEC: u64 = 30;
+
+/// Comma-separated backends the admin API may migrate from or to: storage
+/// URLs, or the type for backends opened without one (`local`, `sqlite`).
+/// Unset disables the API; the CLI is not restricted.
+pub const API_TARGETS_ENV: &str = "SERVER_STORAGE_MIGRATE_TARGETS";
+
+/// Differences between the source and the target as it was before copying.
+#[derive(Debug, Default, Serialize)]
+pub struct Diff {
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl Diff {
+    fn between(source: &BTreeMap<String, String>, target: &BTreeMap<String, String>) -> Self {
+        let mut diff = Diff::default();
+        for (key, value) in source {
+            match target.get(key) {
+                None => diff.added.push(key.clone()),
+                Some(existing) if existing != value => diff.changed.push(key.clone()),
+                Some(_) => {}
+            }
+        }
+        diff.removed = target
+            .keys()
+            .filter(|k| !source.contains_key(*k))
+            .cloned()
+            .collect();
+        diff
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SectionReport {
+    pub source_count: usize,
+    pub source_checksum: String,
+    pub target_count: usize,
+    pub target_checksum: String,
+    pub diff: Diff,
+    /// Set once the copy was read back; `None` on dry runs.
+    pub verified: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MigrationReport {
+    pub source: &'static str,
+    pub target: &'static str,
+    pub dry_run: bool,
+    pub config: SectionReport,
+    pub tokens: SectionReport,
+    pub history: SectionReport,
+}
+
+impl MigrationReport {
+    pub fn verified(&self) -> bool {
+        [&self.config, &self.tokens, &self.history]
+            .iter()
+            .all(|section| section.verified != Some(false))
+    }
+}
+
+fn checksum(value: &JsonValue) -> String {
+    // serde_json maps are sorted, so equal documents serialize identically.
+    let digest = Sha256::digest(value.to_string().as_bytes());
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// `section.key` → serialized value.
+fn config_entries(config: &JsonValue) -> BTreeMap<String, String> {
+    let mut entries = BTreeMap::new();
+    for (section, value) in config.as_object().into_iter().flatten() {
+        match value.as_object() {
+            Some(map) => {
+                for (key, value) in map {
+                    entries.insert(format!("{section}.{key}"), value.to_string());
+                }
+            }
+            None => {
+                entries.insert(section.clone(), value.to_string());
+            }
+        }
+    }
+    entries
+}
+
+/// `pool/masked-token` → serialized token, which includes its Imagine state.
+fn token_entries(tokens: &JsonValue) -> BTreeMap<String, String> {
+    let mut entries = BTreeMap::new();
+    for (pool, list) in tokens.as_object().into_iter().flatten() {
+        for item in list.as_array().into_iter().flatten() {
+            let Some(token) = item.get("token").and_then(|v| v.as_str()) else {
+                continue;
+            };
+            entries.insert(format!("{pool}/{}", mask_token(token)), item.to_string());
+        }
+    }
+    entries
+}
+
+/// `v<version>` → serialized config version.
+fn history_entries(history: &JsonValue) -> BTreeMap<String, String> {
+    history
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|version| {
+            let number = version.get("version").and_then(|v| v.as_u64()).unwrap_or(0);
+            (format!("v{number}"), version.to_string())
+        })
+        .collect()
+}
+
+fn section(
+    source: &JsonValue,
+    target: &JsonValue,
+    entries: fn(&JsonValue) -> BTreeMap<String, String>,
+) -> SectionReport {
+    let source_entries = entries(source);
+    let target_entries = entries(target);
+    SectionReport {
+        source_count: source_entries.len(),
+        source_checksum: checksum(source),
+        target_count: target_entries.len(),
+        target_checksum: checksum(target),
+        diff: Diff::between(&source_entries, &target_entries),
+        verified: None,
+    }
+}
+
+fn record_copy(
+    report: &mut SectionReport,
+    copied: &JsonValue,
+    entries: fn(&JsonValue) -> BTreeMap<String, String>,
+) {
+    report.target_count = entries(copied).len();
+    report.target_checksum = checksum(copied);
+    report.verified = Some(
+        report.target_count == report.source_count
+            && report.target_checksum == report.source_checksum,
+    );
+}
+
+/// Copies config, config history and tokens from `source` to `target`, replacing what the
+/// target held. With `dry_run` only the diff is computed.
+pub async fn migrate_storage(
+    source: &StorageBackend,
+    target: &StorageBackend,
+    dry_run: bool,
+) -> Result<MigrationReport, StorageError> {
+    let source_config = source.load_config().await?;
+    let source_tokens = source.load_tokens().await?;
+    let target_config = target.load_config().await?;
+    let target_tokens = target.load_tokens().await?;
+    let source_history = source.load_config_history().await?;
+    let target_history = target.load_config_history().await?;
+
+    let mut report = MigrationReport {
+        source: source.kind(),
+        target: target.kind(),
+        dry_run,
+        config: section(&source_config, &target_config, config_entries),
+        tokens: section(&source_tokens, &target_tokens, token_entries),
+        history: section(&source_history, &target_history, history_entries),
+    };
+    if dry_run {
+        return Ok(report);
+    }
+
+    target
+        .with_lock("config_save", LOCK_TIMEOUT_SEC, || async {
+            target.save_config(&source_config).await?;
+            target.save_config_history(&source_history).await
+        })
+        .await?;
+    target
+        .with_lock("tokens_save", LOCK_TIMEOUT_SEC, || async {
+            target.save_tokens(&source_tokens).await
+        })
+        .await?;
+
+    // Read back through the target so verification covers its own encoding.
+    let copied_config = target.load_config().await?;
+    let copied_tokens = target.load_tokens().await?;
+    let copied_history = target.load_config_history().await?;
+    record_copy(&mut report.config, &copied_config, config_entries);
+    record_copy(&mut report.tokens, &copied_tokens, token_entries);
+    record_copy(&mut report.history, &copied_history, history_entries);
+    tracing::info!(
+        "Storage migrated {} -> {}: {} config entries, {} tokens, {} config versions, verified={}",
+        report.source,
+        report.target,
+        report.config.source_count,
+        report.tokens.source_count,
+        report.history.source_count,
+        report.verified()
+    );
+    Ok(report)
+}
+
+pub fn api_allows(spec: &StorageSpec) -> bool {
+    allowed_by(&std::env::var(API_TARGETS_ENV).unwrap_or_default(), spec)
+}
+
+pub(crate) fn allowed_by(list: &str, spec: &StorageSpec) -> bool {
+    let wanted = match spec.url.as_deref().map(str::trim).filter(|u| !u.is_empty()) {
+        Some(url) => url.to_string(),
+        None => match spec.kind.trim().to_lowercase() {
+            kind if kind.is_empty() => "local".to_string(),
+            kind => kind,
+        },
+    };
+    list.split(',')
+        .map(str::trim)
+        .any(|entry| !entry.is_empty() && entry == wanted)
+}
+
+pub const CLI_USAGE: &str = "usage: grok2api-rs migrate-storage --from <type> --to <type> \
+[--from-url <url>] [--to-url <url>] [--from-prefix <prefix>] [--to-prefix <prefix>] [--dry-run]";
+
+/// `migrate-storage` subcommand; prints the JSON report and returns the
+/// process exit code.
+pub async fn run_cli(args: &[String]) -> i32 {
+    let mut from = StorageSpec::default();
+    let mut to = StorageSpec::default();
+    let mut dry_run = false;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let slot = match arg.as_str() {
+            "--dry-run" => {
+                dry_run = true;
+                continue;
+            }
+            "--from" => &mut from.kind,
+            "--to" => &mut to.kind,
+            "--from-url" => from.url.get_or_insert_default(),
+            "--to-url" => to.url.get_or_insert_default(),
+            "--from-prefix" => from.prefix.get_or_insert_default(),
+            "--to-prefix" => to.prefix.get_or_insert_default(),
+            _ => {
+                eprintln!("unknown argument {arg}\n{CLI_USAGE}");
+                return 2;
+            }
+        };
+        let Some(value) = iter.next() else {
+            eprintln!("{arg} needs a value\n{CLI_USAGE}");
+            return 2;
+        };
+        *slot = value.clone();
+    }
+    if from.kind.is_empty() || to.kind.is_empty() {
+        eprintln!("{CLI_USAGE}");
+        return 2;
+    }
+
+    let opened = from.open().and_then(|source| Ok((source, to.open()?)));
+    let result = match opened {
+        Ok((source, target)) => migrate_storage(&source, &target, dry_run).await,
+        Err(err) => Err(err),
+    };
+    match result {
+        Ok(report) => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&report).unwrap_or_default()
+            );
+            if report.verified() { 0 } else { 1 }
+        }
+        Err(err) => {
+            eprintln!("storage migration failed: {err}");
+            1
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+use serde_json::json;
+
+use crate::core::storage::migrate::{allowed_by, migrate_storage};
+use crate::core::storage::test_util::TempDb;
+use crate::core::storage::{Storage, StorageBackend, StorageSpec};
+use crate::services::token::transfer::mask_token;
+
+fn open(db: &TempDb) -> StorageBackend {
+    StorageBackend::Sql(db.open(Duration::from_secs(30)))
+}
+
+#[tokio::test]
+async fn dry_run_reports_diff_and_copy_verifies() {
+    let (source_db, target_db) = (TempDb::new(), TempDb::new());
+    let (source, target) = (open(&source_db), open(&target_db));
+
+    let imagine = json!({"daily_count": 3, "nsfw": {"enabled": true, "verified": true}});
+    source
+        .save_config(&json!({"app": {"api_key": "k"}, "token": {"auto_nsfw": true}}))
+        .await
+        .unwrap();
+    source
+        .save_tokens(&json!({"ssoBasic": [
+            {"token": "keep", "quota": 80, "imagine": imagine},
+            {"token": "new", "quota": 80},
+        ]}))
+        .await
+        .unwrap();
+    let history = json!([
+        {"version": 1, "action": "baseline", "snapshot": {"app": {"api_key": "old"}}},
+        {"version": 2, "action": "update", "snapshot": {"app": {"api_key": "k"}}},
+    ]);
+    source.save_config_history(&history).await.unwrap();
+    target
+        .save_config(&json!({"app": {"api_key": "old"}}))
+        .await
+        .unwrap();
+    target
+        .save_tokens(&json!({"ssoBasic": [{"token": "keep", "quota": 10}, {"token": "stale"}]}))
+        .await
+        .unwrap();
+
+    let report = migrate_storage(&source, &target, true).await.unwrap();
+    let masked = |token| vec![format!("ssoBasic/{}", mask_token(token))];
+    assert_eq!(report.tokens.diff.added, masked("new"));
+    assert_eq!(report.tokens.diff.changed, masked("keep"));
+    assert_eq!(report.tokens.diff.removed, masked("stale"));
+    assert_eq!(report.config.diff.changed, vec!["app.api_key"]);
+    assert_eq!(report.config.diff.added, vec!["token.auto_nsfw"]);
+    assert_eq!(report.history.diff.added, vec!["v1", "v2"]);
+    assert_eq!(report.tokens.verified, None);
+    assert_eq!(
+        target.load_tokens().await.unwrap()["ssoBasic"][0]["quota"],
+        10
+    );
+
+    let report = migrate_storage(&source, &target, false).await.unwrap();
+    assert!(report.verified());
+    assert_eq!(report.tokens.target_count, 2);
+    assert_eq!(report.tokens.source_checksum, report.tokens.target_checksum);
+    assert_eq!(report.config.source_checksum, report.config.target_checksum);
+    assert_eq!(report.history.verified, Some(true));
+    assert_eq!(target.load_config_history().await.unwrap(), history);
+    assert_eq!(
+        target.load_tokens().await.unwrap()["ssoBasic"][0]["imagine"],
+        imagine
+    );
+}
+
+#[test]
+fn api_targets_must_be_listed() {
+    let spec = |kind: &str, url: Option<&str>| StorageSpec {
+        kind: kind.to_string(),
+        url: url.map(str::to_string),
+        prefix: None,
+    };
+    let list = "local, redis://10.0.0.5:6379/0";
+    assert!(allowed_by(list, &spec("local", None)));
+    assert!(allowed_by(list, &spec("", None)));
+    assert!(allowed_by(
+        list,
+        &spec("redis", Some(" redis://10.0.0.5:6379/0"))
+    ));
+    assert!(!allowed_by(list, &spec("redis", None)));
+    assert!(!allowed_by(list, &spec("redis", Some("redis://evil:6379"))));
+    assert!(!allowed_by("", &spec("local", None)));
+}
@@ -0,0 +1,266 @@
+pub mod crypto;
+#[cfg(test)]
+mod crypto_tests;
+mod lease;
+pub mod local;
+pub mod migrate;
+#[cfg(test)]
+mod migrate_tests;
+pub mod redis;
+#[cfg(test)]
+mod redis_tests;
+pub mod sql;
+#[cfg(test)]
+mod sql_tests;
+#[cfg(test)]
+mod test_util;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use tokio::sync::broadcast;
+
+use crate::core::config::project_root;
+
+use self::crypto::{Document, keyring};
+pub use self::local::LocalStorage;
+pub use self::redis::RedisStorage;
+pub use self::sql::SqlStorage;
+
+#[derive(Debug, Clone)]
+pub struct StorageError(pub String);
+
+impl std::fmt::Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+/// What another replica just wrote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageChange {
+    Config,
+    Tokens,
+}
+
+#[async_trait]
+pub trait Storage: Send + Sync {
+    fn kind(&self) -> &'static str;
+    async fn load_config(&self) -> Result<JsonValue, StorageError>;
+    async fn save_config(&self, data: &JsonValue) -> Result<(), StorageError>;
+    async fn load_tokens(&self) -> Result<JsonValue, StorageError>;
+    async fn save_tokens(&self, data: &JsonValue) -> Result<(), StorageError>;
+    /// Config versions as a JSON array, oldest first; empty when none exist.
+    async fn load_config_history(&self) -> Result<JsonValue, StorageError>;
+    async fn save_config_history(&self, data: &JsonValue) -> Result<(), StorageError>;
+    async fn with_lock<F, Fut, T>(&self, name: &str, timeout: u64, f: F) -> Result<T, StorageError>
+    where
+        F: FnOnce() -> Fut + Send,
+        Fut: std::future::Future<Output = Result<T, StorageError>> + Send,
+        T: Send;
+
+    /// Writes made by other processes; `None` when the backend cannot
+    /// observe them and callers should fall back to polling.
+    fn subscribe_changes(&self) -> Option<broadcast::Receiver<StorageChange>> {
+        None
+    }
+}
+
+pub enum StorageBackend {
+    Local(LocalStorage),
+    Redis(RedisStorage),
+    Sql(SqlStorage),
+}
+
+/// Seals secret values on save and opens them on load, whichever backend
+/// holds them; see [`crypto`].
+#[async_trait]
+impl Storage for StorageBackend {
+    fn kind(&self) -> &'static str {
+        match self {
+            StorageBackend::Local(s) => s.kind(),
+            StorageBackend::Redis(s) => s.kind(),
+            StorageBackend::Sql(s) => s.kind(),
+        }
+    }
+
+    async fn load_config(&self) -> Result<JsonValue, StorageError> {
+        let stored = match self {
+            StorageBackend::Local(s) => s.load_config().await,
+            StorageBackend::Redis(s) => s.load_config().await,
+            StorageBackend::Sql(s) => s.load_config().await,
+        }?;
+        keyring().open_document(&stored, Document::Config)
+    }
+
+    async fn save_config(&self, data: &JsonValue) -> Result<(), StorageError> {
+        let sealed = keyring().seal_document(data, Document::Config)?;
+        match self {
+            StorageBackend::Local(s) => s.save_config(&sealed).await,
+            StorageBackend::Redis(s) => s.save_config(&sealed).await,
+            StorageBackend::Sql(s) => s.save_config(&sealed).await,
+        }
+    }
+
+    async fn load_tokens(&self) -> Result<JsonValue, StorageError> {
+        let stored = match self {
+            StorageBackend::Local(s) => s.load_tokens().await,
+            StorageBackend::Redis(s) => s.load_tokens().await,
+            StorageBackend::Sql(s) => s.load_tokens().await,
+        }?;
+        keyring().open_document(&stored, Document::Tokens)
+    }
+
+    async fn save_tokens(&self, data: &JsonValue) -> Result<(), StorageError> {
+        let sealed = keyring().seal_document(data, Document::Tokens)?;
+        match self {
+            StorageBackend::Local(s) => s.save_tokens(&sealed).await,
+            StorageBackend::Redis(s) => s.save_tokens(&sealed).await,
+            StorageBackend::Sql(s) => s.save_token_rows(data, &sealed).await,
+        }
+    }
+
+    async fn load_config_history(&self) -> Result<JsonValue, StorageError> {
+        let stored = match self {
+            StorageBackend::Local(s) => s.load_config_history().await,
+            StorageBackend::Redis(s) => s.load_config_history().await,
+            StorageBackend::Sql(s) => s.load_config_history().await,
+        }?;
+        keyring().open_document(&stored, Document::ConfigHistory)
+    }
+
+    async fn save_config_history(&self, data: &JsonValue) -> Result<(), StorageError> {
+        let sealed = keyring().seal_document(data, Document::ConfigHistory)?;
+        match self {
+            StorageBackend::Local(s) => s.save_config_history(&sealed).await,
+            StorageBackend::Redis(s) => s.save_config_history(&sealed).await,
+            StorageBackend::Sql(s) => s.save_config_history(&sealed).await,
+        }
+    }
+
+    async fn with_lock<F, Fut, T>(&self, name: &str, timeout: u64, f: F) -> Result<T, StorageError>
+    where
+        F: FnOnce() -> Fut + Send,
+        Fut: std::future::Future<Output = Result<T, StorageError>> + Send,
+        T: Send,
+    {
+        match self {
+            StorageBackend::Local(s) => s.with_lock(name, timeout, f).await,
+            StorageBackend::Redis(s) => s.with_lock(name, timeout, f).await,
+            StorageBackend::Sql(s) => s.with_lock(name, timeout, f).await,
+        }
+    }
+
+    fn subscribe_changes(&self) -> Option<broadcast::Receiver<StorageChange>> {
+        match self {
+            StorageBackend::Local(s) => s.subscribe_changes(),
+            StorageBackend::Redis(s) => s.subscribe_changes(),
+            StorageBackend::Sql(s) => s.subscribe_changes(),
+        }
+    }
+}
+
+fn env_or(key: &str, default: &str) -> String {
+    std::env::var(key)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| default.to_string())
+}
+
+fn lock_lease() -> Duration {
+    let lease = env_or("SERVER_STORAGE_LOCK_LEASE_SEC", "30")
+        .parse::<u64>()
+        .unwrap_or(30);
+    Duration::from_secs(lease.max(1))
+}
+
+/// `data/grok2api.db`, created on first connect.
+fn default_sqlite_url() -> String {
+    let dir = project_root().join("data");
+    if let Err(err) = std::fs::create_dir_all(&dir) {
+        tracing::warn!("Create data dir failed: {err}");
+    }
+    format!("sqlite://{}?mode=rwc", dir.join("grok2api.db").display())
+}
+
+/// Which backend to open; `url` and `prefix` fall back to the per-type
+/// defaults when empty.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct StorageSpec {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub prefix: Option<String>,
+}
+
+impl StorageSpec {
+    pub fn from_env() -> Self {
+        Self {
+            kind: env_or("SERVER_STORAGE_TYPE", "local"),
+            url: std::env::var("SERVER_STORAGE_URL").ok(),
+            prefix: std::env::var("SERVER_STORAGE_PREFIX").ok(),
+        }
+    }
+
+    pub fn open(&self) -> Result<StorageBackend, StorageError> {
+        let url = self.url.as_deref().map(str::trim).filter(|u| !u.is_empty());
+        let storage_type = self.kind.trim().to_lowercase();
+        match storage_type.as_str() {
+            "" | "local" => Ok(StorageBackend::Local(LocalStorage::new())),
+            "redis" => {
+                let prefix = self
+                    .prefix
+                    .as_deref()
+                    .map(str::trim)
+                    .filter(|p| !p.is_empty())
+                    .unwrap_or("grok2api");
+                let url = url.unwrap_or("redis://127.0.0.1:6379");
+                RedisStorage::new(url, prefix, lock_lease()).map(StorageBackend::Redis)
+            }
+            "sqlite" | "postgres" | "postgresql" | "pgsql" => {
+                let url = match url {
+                    Some(url) => url.to_string(),
+                    None if storage_type == "sqlite" => default_sqlite_url(),
+                    None => {
+                        return Err(StorageError(format!(
+                            "SERVER_STORAGE_URL is required for {storage_type}"
+                        )));
+                    }
+                };
+                SqlStorage::new(&url, lock_lease()).map(StorageBackend::Sql)
+            }
+            other => Err(StorageError(format!("unsupported storage type {other}"))),
+        }
+    }
+}
+
+static STORAGE: once_cell::sync::OnceCell<Arc<StorageBackend>> = once_cell::sync::OnceCell::new();
+
+/// Opens the configured backend at startup; a bad backend config must stop
+/// the process instead of silently writing to local files.
+pub fn init_storage() -> Result<(), StorageError> {
+    let storage = StorageSpec::from_env().open()?;
+    tracing::info!("Storage backend: {}", storage.kind());
+    let _ = STORAGE.set(Arc::new(storage));
+    Ok(())
+}
+
+pub fn get_storage() -> Arc<StorageBackend> {
+    STORAGE
+        .get_or_init(|| {
+            let storage = StorageSpec::from_env()
+                .open()
+                .unwrap_or_else(|err| panic!("Storage backend unavailable: {err}"));
+            Arc::new(storage)
+        })
+        .clone()
+}
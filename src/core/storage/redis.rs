@@ -0,0 +1,280 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use redis::aio::ConnectionManager;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use tokio::sync::{OnceCell, broadcast};
+
+use crate::core::storage::lease::{NamedLocks, run_leased};
+use crate::core::storage::{Storage, StorageChange, StorageError};
+
+const LOCK_POLL_MS: u64 = 50;
+const RECONNECT_DELAY_SEC: u64 = 1;
+
+/// Deletes the lock only while it still carries our lease token, so a holder
+/// whose lease already expired cannot release somebody else's lock.
+const RELEASE_SCRIPT: &str = "if redis.call('GET', KEYS[1]) == ARGV[1] then \
+     return redis.call('DEL', KEYS[1]) else return 0 end";
+
+/// Extends the lease only while the lock still carries our token.
+const RENEW_SCRIPT: &str = "if redis.call('GET', KEYS[1]) == ARGV[1] then \
+     return redis.call('PEXPIRE', KEYS[1], ARGV[2]) else return 0 end";
+
+#[derive(Serialize, Deserialize)]
+struct ChangeNotice {
+    kind: StorageChange,
+    origin: String,
+}
+
+/// Keeps config and tokens as JSON strings under `<prefix>:config` and
+/// `<prefix>:tokens`, and announces every save on `<prefix>:changes`.
+pub struct RedisStorage {
+    client: redis::Client,
+    conn: OnceCell<ConnectionManager>,
+    prefix: String,
+    instance_id: String,
+    lease: Duration,
+    locks: NamedLocks,
+    changes: broadcast::Sender<StorageChange>,
+    listening: AtomicBool,
+}
+
+impl RedisStorage {
+    pub fn new(url: &str, prefix: &str, lease: Duration) -> Result<Self, StorageError> {
+        let client = redis::Client::open(url)
+            .map_err(|e| StorageError(format!("invalid redis url: {e}")))?;
+        Ok(Self {
+            client,
+            conn: OnceCell::new(),
+            prefix: prefix.trim_end_matches(':').to_string(),
+            instance_id: uuid::Uuid::new_v4().to_string(),
+            lease,
+            locks: NamedLocks::default(),
+            changes: broadcast::channel(16).0,
+            listening: AtomicBool::new(false),
+        })
+    }
+
+    fn key(&self, name: &str) -> String {
+        format!("{}:{}", self.prefix, name)
+    }
+
+    async fn conn(&self) -> Result<ConnectionManager, StorageError> {
+        self.conn
+            .get_or_try_init(|| async { ConnectionManager::new(self.client.clone()).await })
+            .await
+            .cloned()
+            .map_err(|e| StorageError(format!("redis connect failed: {e}")))
+    }
+
+    async fn load_json(&self, name: &str) -> Result<JsonValue, StorageError> {
+        let mut conn = self.conn().await?;
+        let raw: Option<String> = redis::cmd("GET")
+            .arg(self.key(name))
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| StorageError(format!("read {name} failed: {e}")))?;
+        match raw {
+            Some(raw) => serde_json::from_str(&raw)
+                .map_err(|e| StorageError(format!("parse {name} failed: {e}"))),
+            None => Ok(JsonValue::Object(Default::default())),
+        }
+    }
+
+    async fn write_json(
+        &self,
+        conn: &mut ConnectionManager,
+        name: &str,
+        data: &JsonValue,
+    ) -> Result<(), StorageError> {
+        let content = serde_json::to_string(data)
+            .map_err(|e| StorageError(format!("serialize {name} failed: {e}")))?;
+        redis::cmd("SET")
+            .arg(self.key(name))
+            .arg(content)
+            .query_async(conn)
+            .await
+            .map_err(|e| StorageError(format!("write {name} failed: {e}")))
+    }
+
+    async fn save_json(
+        &self,
+        name: &str,
+        data: &JsonValue,
+        change: StorageChange,
+    ) -> Result<(), StorageError> {
+        let mut conn = self.conn().await?;
+        self.write_json(&mut conn, name, data).await?;
+        self.publish(&mut conn, change).await;
+        Ok(())
+    }
+
+    async fn publish(&self, conn: &mut ConnectionManager, kind: StorageChange) {
+        let notice = ChangeNotice {
+            kind,
+            origin: self.instance_id.clone(),
+        };
+        let Ok(payload) = serde_json::to_string(&notice) else {
+            return;
+        };
+        let result: redis::RedisResult<i64> = redis::cmd("PUBLISH")
+            .arg(self.key("changes"))
+            .arg(payload)
+            .query_async(conn)
+            .await;
+        if let Err(err) = result {
+            tracing::warn!("Redis change notification failed: {err}");
+        }
+    }
+
+    fn start_listener(&self) {
+        if self.listening.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let client = self.client.clone();
+        let channel = self.key("changes");
+        let origin = self.instance_id.clone();
+        let changes = self.changes.clone();
+        tokio::spawn(async move {
+            loop {
+                match listen(&client, &channel, &origin, &changes).await {
+                    Ok(()) => tracing::warn!("Redis change subscription closed, reconnecting"),
+                    Err(err) => tracing::warn!("Redis change subscription failed: {err}"),
+                }
+                tokio::time::sleep(Duration::from_secs(RECONNECT_DELAY_SEC)).await;
+            }
+        });
+    }
+}
+
+async fn listen(
+    client: &redis::Client,
+    channel: &str,
+    origin: &str,
+    changes: &broadcast::Sender<StorageChange>,
+) -> redis::RedisResult<()> {
+    let mut pubsub = client.get_async_pubsub().await?;
+    pubsub.subscribe(channel).await?;
+    let mut messages = pubsub.into_on_message();
+    while let Some(msg) = messages.next().await {
+        let Ok(notice) = serde_json::from_slice::<ChangeNotice>(msg.get_payload_bytes()) else {
+            continue;
+        };
+        if notice.origin != origin {
+            let _ = changes.send(notice.kind);
+        }
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl Storage for RedisStorage {
+    fn kind(&self) -> &'static str {
+        "redis"
+    }
+
+    async fn load_config(&self) -> Result<JsonValue, StorageError> {
+        self.load_json("config").await
+    }
+
+    async fn save_config(&self, data: &JsonValue) -> Result<(), StorageError> {
+        self.save_json("config", data, StorageChange::Config).await
+    }
+
+    async fn load_tokens(&self) -> Result<JsonValue, StorageError> {
+        self.load_json("tokens").await
+    }
+
+    async fn save_tokens(&self, data: &JsonValue) -> Result<(), StorageError> {
+        self.save_json("tokens", data, StorageChange::Tokens).await
+    }
+
+    async fn load_config_history(&self) -> Result<JsonValue, StorageError> {
+        match self.load_json("config_history").await? {
+            JsonValue::Array(versions) => Ok(JsonValue::Array(versions)),
+            _ => Ok(JsonValue::Array(Vec::new())),
+        }
+    }
+
+    /// History is only read on demand, so saving it notifies nobody.
+    async fn save_config_history(&self, data: &JsonValue) -> Result<(), StorageError> {
+        let mut conn = self.conn().await?;
+        self.write_json(&mut conn, "config_history", data).await
+    }
+
+    /// The lock key expires after the configured lease, so a replica that dies
+    /// mid-section cannot block the others for longer than that. A live holder
+    /// keeps renewing it and fails the section if the key was lost meanwhile.
+    async fn with_lock<F, Fut, T>(&self, name: &str, timeout: u64, f: F) -> Result<T, StorageError>
+    where
+        F: FnOnce() -> Fut + Send,
+        Fut: std::future::Future<Output = Result<T, StorageError>> + Send,
+        T: Send,
+    {
+        let deadline = Instant::now() + Duration::from_secs(timeout);
+        let _guard = self.locks.lock(name, deadline).await?;
+
+        let key = self.key(&format!("lock:{name}"));
+        let token = uuid::Uuid::new_v4().to_string();
+        let mut conn = self.conn().await?;
+        loop {
+            let acquired: Option<String> = redis::cmd("SET")
+                .arg(&key)
+                .arg(&token)
+                .arg("NX")
+                .arg("PX")
+                .arg(self.lease.as_millis() as u64)
+                .query_async(&mut conn)
+                .await
+                .map_err(|e| StorageError(format!("acquire lock failed: {e}")))?;
+            if acquired.is_some() {
+                break;
+            }
+            if Instant::now() >= deadline {
+                return Err(StorageError(format!("lock timeout: {name}")));
+            }
+            tokio::time::sleep(Duration::from_millis(LOCK_POLL_MS)).await;
+        }
+
+        let lease_ms = self.lease.as_millis() as u64;
+        let result = run_leased(name, self.lease, f(), || {
+            let mut conn = conn.clone();
+            let key = key.clone();
+            let token = token.clone();
+            async move {
+                let renewed: i64 = redis::cmd("EVAL")
+                    .arg(RENEW_SCRIPT)
+                    .arg(1)
+                    .arg(&key)
+                    .arg(&token)
+                    .arg(lease_ms)
+                    .query_async(&mut conn)
+                    .await
+                    .map_err(|e| StorageError(format!("renew lock failed: {e}")))?;
+                Ok(renewed == 1)
+            }
+        })
+        .await;
+        let released: redis::RedisResult<i64> = redis::cmd("EVAL")
+            .arg(RELEASE_SCRIPT)
+            .arg(1)
+            .arg(&key)
+            .arg(&token)
+            .query_async(&mut conn)
+            .await;
+        match released {
+            Ok(0) => tracing::warn!("Lock {name} lease expired before release"),
+            Ok(_) => {}
+            Err(err) => tracing::warn!("Release lock {name} failed: {err}"),
+        }
+        result
+    }
+
+    fn subscribe_changes(&self) -> Option<broadcast::Receiver<StorageChange>> {
+        self.start_listener();
+        Some(self.changes.subscribe())
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::core::storage::{RedisStorage, Storage, StorageError};
+
+type Store = Arc<Mutex<HashMap<String, (Vec<u8>, Option<Instant>)>>>;
+
+/// Minimal RESP2 stand-in for the commands `RedisStorage` issues: GET, SET
+/// (with NX/PX), DEL, PUBLISH and the lock release and renew EVALs. Anything
+/// else is acknowledged with `+OK`.
+async fn stand_in() -> (String, Store) {
+    let store: Store = Arc::new(Mutex::new(HashMap::new()));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let shared = store.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(serve(stream, shared.clone()));
+        }
+    });
+    (format!("redis://{addr}/"), store)
+}
+
+async fn read_command(reader: &mut BufReader<TcpStream>) -> Option<Vec<Vec<u8>>> {
+    let mut line = String::new();
+    reader.read_line(&mut line).await.ok()?;
+    let count: usize = line.trim().strip_prefix('*')?.parse().ok()?;
+    let mut args = Vec::with_capacity(count);
+    for _ in 0..count {
+        line.clear();
+        reader.read_line(&mut line).await.ok()?;
+        let len: usize = line.trim().strip_prefix('$')?.parse().ok()?;
+        let mut buf = vec![0u8; len + 2];
+        reader.read_exact(&mut buf).await.ok()?;
+        buf.truncate(len);
+        args.push(buf);
+    }
+    Some(args)
+}
+
+fn bulk(value: Option<&[u8]>) -> Vec<u8> {
+    match value {
+        Some(v) => {
+            let mut out = format!("${}\r\n", v.len()).into_bytes();
+            out.extend_from_slice(v);
+            out.extend_from_slice(b"\r\n");
+            out
+        }
+        None => b"$-1\r\n".to_vec(),
+    }
+}
+
+fn execute(store: &Store, args: &[Vec<u8>]) -> Vec<u8> {
+    let mut store = store.lock().unwrap();
+    let now = Instant::now();
+    store.retain(|_, (_, expires)| expires.is_none_or(|at| at > now));
+    let name = String::from_utf8_lossy(&args[0]).to_uppercase();
+    let key = |i: usize| String::from_utf8_lossy(&args[i]).to_string();
+    match name.as_str() {
+        "GET" => bulk(store.get(&key(1)).map(|(v, _)| v.as_slice())),
+        "SET" => {
+            let mut nx = false;
+            let mut expires = None;
+            let mut i = 3;
+            while i < args.len() {
+                match String::from_utf8_lossy(&args[i]).to_uppercase().as_str() {
+                    "NX" => nx = true,
+                    "PX" => {
+                        let ms: u64 = String::from_utf8_lossy(&args[i + 1]).parse().unwrap();
+                        expires = Some(now + Duration::from_millis(ms));
+                        i += 1;
+                    }
+                    _ => {}
+                }
+                i += 1;
+            }
+            if nx && store.contains_key(&key(1)) {
+                return bulk(None);
+            }
+            store.insert(key(1), (args[2].clone(), expires));
+            b"+OK\r\n".to_vec()
+        }
+        "DEL" => format!(":{}\r\n", store.remove(&key(1)).is_some() as i64).into_bytes(),
+        "EVAL" => {
+            let lock_key = key(3);
+            let owned = store.get(&lock_key).is_some_and(|(v, _)| *v == args[4]);
+            if owned {
+                // Renewals carry the new lease as a second argument.
+                match args.get(5) {
+                    Some(ms) => {
+                        let ms: u64 = String::from_utf8_lossy(ms).parse().unwrap();
+                        store.get_mut(&lock_key).unwrap().1 = Some(now + Duration::from_millis(ms));
+                    }
+                    None => {
+                        store.remove(&lock_key);
+                    }
+                }
+            }
+            format!(":{}\r\n", owned as i64).into_bytes()
+        }
+        "PUBLISH" => b":0\r\n".to_vec(),
+        _ => b"+OK\r\n".to_vec(),
+    }
+}
+
+async fn serve(stream: TcpStream, store: Store) {
+    let mut reader = BufReader::new(stream);
+    while let Some(args) = read_command(&mut reader).await {
+        if args.is_empty() {
+            continue;
+        }
+        let reply = execute(&store, &args);
+        if reader.get_mut().write_all(&reply).await.is_err() {
+            break;
+        }
+    }
+}
+
+fn storage(url: &str, lease: Duration) -> RedisStorage {
+    RedisStorage::new(url, "test", lease).unwrap()
+}
+
+#[tokio::test]
+async fn round_trips_config_and_tokens() {
+    let (url, store) = stand_in().await;
+    let storage = storage(&url, Duration::from_secs(30));
+
+    assert_eq!(storage.load_tokens().await.unwrap(), json!({}));
+
+    let tokens = json!({"ssoBasic": [{"token": "abc", "quota": 80}]});
+    storage.save_tokens(&tokens).await.unwrap();
+    let config = json!({"token": {"auto_refresh": false}});
+    storage.save_config(&config).await.unwrap();
+
+    assert_eq!(storage.load_tokens().await.unwrap(), tokens);
+    assert_eq!(storage.load_config().await.unwrap(), config);
+    assert!(store.lock().unwrap().contains_key("test:tokens"));
+}
+
+#[tokio::test]
+async fn lock_is_exclusive_across_instances() {
+    let (url, store) = stand_in().await;
+    let first = Arc::new(storage(&url, Duration::from_secs(30)));
+    let second = storage(&url, Duration::from_secs(30));
+
+    let holder = first.clone();
+    let (held_tx, held_rx) = tokio::sync::oneshot::channel();
+    let task = tokio::spawn(async move {
+        holder
+            .with_lock("tokens_save", 5, || async {
+                let _ = held_tx.send(());
+                tokio::time::sleep(Duration::from_millis(1500)).await;
+                Ok(())
+            })
+            .await
+    });
+    held_rx.await.unwrap();
+
+    let result = second
+        .with_lock("tokens_save", 1, || async { Ok(()) })
+        .await;
+    assert!(matches!(result, Err(StorageError(msg)) if msg.contains("lock timeout")));
+
+    task.await.unwrap().unwrap();
+    assert!(!store.lock().unwrap().contains_key("test:lock:tokens_save"));
+    second
+        .with_lock("tokens_save", 1, || async { Ok(()) })
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn abandoned_lock_expires_with_its_lease() {
+    let (url, store) = stand_in().await;
+    store.lock().unwrap().insert(
+        "test:lock:config_save".to_string(),
+        (
+            b"crashed-replica".to_vec(),
+            Some(Instant::now() + Duration::from_millis(300)),
+        ),
+    );
+
+    let storage = storage(&url, Duration::from_secs(30));
+    let started = Instant::now();
+    let value = storage
+        .with_lock("config_save", 5, || async { Ok(42) })
+        .await
+        .unwrap();
+    assert_eq!(value, 42);
+    assert!(started.elapsed() >= Duration::from_millis(250));
+}
+
+#[tokio::test]
+async fn lease_is_renewed_while_the_section_runs() {
+    let (url, _store) = stand_in().await;
+    let first = Arc::new(storage(&url, Duration::from_millis(300)));
+    let second = storage(&url, Duration::from_millis(300));
+
+    let holder = first.clone();
+    let (held_tx, held_rx) = tokio::sync::oneshot::channel();
+    let task = tokio::spawn(async move {
+        holder
+            .with_lock("tokens_save", 5, || async {
+                let _ = held_tx.send(());
+                tokio::time::sleep(Duration::from_millis(1500)).await;
+                Ok(())
+            })
+            .await
+    });
+    held_rx.await.unwrap();
+
+    let result = second
+        .with_lock("tokens_save", 1, || async { Ok(()) })
+        .await;
+    assert!(matches!(result, Err(StorageError(msg)) if msg.contains("lock timeout")));
+    task.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn lost_lease_fails_the_section() {
+    let (url, store) = stand_in().await;
+    let storage = storage(&url, Duration::from_millis(300));
+
+    let thief = store.clone();
+    let result = storage
+        .with_lock("config_save", 5, || async move {
+            thief.lock().unwrap().insert(
+                "test:lock:config_save".to_string(),
+                (b"other-replica".to_vec(), None),
+            );
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(())
+        })
+        .await;
+    assert!(matches!(result, Err(StorageError(msg)) if msg.contains("lease lost")));
+    assert!(store.lock().unwrap().contains_key("test:lock:config_save"));
+}
+
+#[tokio::test]
+async fn different_names_do_not_wait_on_each_other() {
+    let (url, _store) = stand_in().await;
+    let storage = Arc::new(storage(&url, Duration::from_secs(30)));
+
+    let holder = storage.clone();
+    let (held_tx, held_rx) = tokio::sync::oneshot::channel();
+    let (done_tx, done_rx) = tokio::sync::oneshot::channel::<()>();
+    let task = tokio::spawn(async move {
+        holder
+            .with_lock("tokens_save", 5, || async {
+                let _ = held_tx.send(());
+                let _ = done_rx.await;
+                Ok(())
+            })
+            .await
+    });
+    held_rx.await.unwrap();
+
+    let value = storage
+        .with_lock("config_save", 1, || async { Ok(1) })
+        .await
+        .unwrap();
+    assert_eq!(value, 1);
+
+    let _ = done_tx.send(());
+    task.await.unwrap().unwrap();
+}
@@ -0,0 +1,596 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
+use sqlx::any::AnyPoolOptions;
+use sqlx::{Any, AnyConnection, AnyPool, Connection, Row, Transaction};
+use tokio::sync::{Mutex, OnceCell};
+
+use crate::core::storage::lease::{NamedLocks, run_leased};
+use crate::core::storage::{Storage, StorageError};
+
+const LOCK_POLL_MS: u64 = 50;
+
+/// Applied in order and recorded in `schema_migrations`. Statements must be
+/// valid on both SQLite and PostgreSQL and use `$N` placeholders.
+const MIGRATIONS: &[(i64, &[&str])] = &[
+    (
+        1,
+        &[
+            "CREATE TABLE IF NOT EXISTS config_entries (
+            section TEXT NOT NULL,
+            name TEXT NOT NULL,
+            value TEXT NOT NULL,
+            PRIMARY KEY (section, name)
+        )",
+            "CREATE TABLE IF NOT EXISTS tokens (
+            token TEXT PRIMARY KEY,
+            pool TEXT NOT NULL,
+            position BIGINT NOT NULL,
+            status TEXT NOT NULL,
+            data TEXT NOT NULL,
+            updated_at BIGINT NOT NULL
+        )",
+            "CREATE INDEX IF NOT EXISTS idx_tokens_pool ON tokens (pool, position)",
+            "CREATE INDEX IF NOT EXISTS idx_tokens_status ON tokens (status)",
+            "CREATE TABLE IF NOT EXISTS token_tags (
+            token TEXT NOT NULL,
+            tag TEXT NOT NULL,
+            PRIMARY KEY (token, tag)
+        )",
+            "CREATE INDEX IF NOT EXISTS idx_token_tags_tag ON token_tags (tag)",
+            "CREATE TABLE IF NOT EXISTS storage_locks (
+            name TEXT PRIMARY KEY,
+            owner TEXT NOT NULL,
+            expires_at BIGINT NOT NULL
+        )",
+        ],
+    ),
+    (
+        2,
+        &["CREATE TABLE IF NOT EXISTS config_history (
+            version BIGINT PRIMARY KEY,
+            data TEXT NOT NULL
+        )"],
+    ),
+    // Sealed tokens change on every save, so rows are matched by a hash of
+    // the plaintext instead. Existing rows are rewritten on the next save;
+    // tags are rebuilt from each row's data then.
+    (
+        3,
+        &[
+            "ALTER TABLE tokens ADD COLUMN id TEXT",
+            "ALTER TABLE tokens ADD COLUMN digest TEXT",
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_tokens_id ON tokens (id)",
+            "DROP TABLE token_tags",
+            "CREATE TABLE token_tags (
+            id TEXT NOT NULL,
+            tag TEXT NOT NULL,
+            PRIMARY KEY (id, tag)
+        )",
+            "CREATE INDEX IF NOT EXISTS idx_token_tags_tag ON token_tags (tag)",
+        ],
+    ),
+];
+
+fn sql_err(what: &str) -> impl Fn(sqlx::Error) -> StorageError + '_ {
+    move |e| StorageError(format!("{what} failed: {e}"))
+}
+
+struct TokenRow {
+    id: String,
+    token: String,
+    pool: String,
+    position: i64,
+    status: String,
+    tags: Vec<String>,
+    data: String,
+    digest: String,
+}
+
+/// What this process last read or wrote for a row. Rows written before
+/// migration 3 have neither an id nor a digest.
+struct KnownRow {
+    id: Option<String>,
+    token: String,
+    digest: Option<String>,
+}
+
+fn sha256_hex(value: &str) -> String {
+    Sha256::digest(value.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// `plain` and `sealed` are the same document before and after sealing, so
+/// ids and digests stay stable while the ciphertext changes on every seal.
+fn token_rows(plain: &JsonValue, sealed: &JsonValue) -> Result<Vec<TokenRow>, StorageError> {
+    let mut rows = Vec::new();
+    let Some(pools) = plain.as_object() else {
+        return Ok(rows);
+    };
+    for (pool, list) in pools {
+        for (position, item) in list.as_array().into_iter().flatten().enumerate() {
+            let Some(token) = item.get("token").and_then(|v| v.as_str()) else {
+                continue;
+            };
+            if token.is_empty() {
+                continue;
+            }
+            let sealed_item = &sealed[pool.as_str()][position];
+            let status = item
+                .get("status")
+                .and_then(|v| v.as_str())
+                .unwrap_or("active")
+                .to_string();
+            let tags = item
+                .get("tags")
+                .and_then(|v| v.as_array())
+                .map(|tags| {
+                    tags.iter()
+                        .filter_map(|t| t.as_str().map(|s| s.to_string()))
+                        .collect::<HashSet<_>>()
+                        .into_iter()
+                        .collect()
+                })
+                .unwrap_or_default();
+            let serialize = |value: &JsonValue| {
+                serde_json::to_string(value)
+                    .map_err(|e| StorageError(format!("serialize token failed: {e}")))
+            };
+            rows.push(TokenRow {
+                id: sha256_hex(token),
+                token: sealed_item
+                    .get("token")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or(token)
+                    .to_string(),
+                pool: pool.clone(),
+                position: position as i64,
+                status,
+                tags,
+                data: serialize(sealed_item)?,
+                digest: sha256_hex(&format!("{pool}\u{1f}{position}\u{1f}{}", serialize(item)?)),
+            });
+        }
+    }
+    Ok(rows)
+}
+
+/// Stores config as `(section, name)` rows and each token as its own row with
+/// pool, status and tags indexed. Token saves only touch rows that changed
+/// since this process last read or wrote them.
+pub struct SqlStorage {
+    url: String,
+    kind: &'static str,
+    pool: OnceCell<AnyPool>,
+    /// Outside the pool: SQLite's only pooled connection may be held by the
+    /// locked section while its lease is renewed.
+    renew_conn: Mutex<Option<AnyConnection>>,
+    lease: Duration,
+    locks: NamedLocks,
+    instance_id: String,
+    known: Mutex<HashMap<String, KnownRow>>,
+}
+
+impl SqlStorage {
+    pub fn new(url: &str, lease: Duration) -> Result<Self, StorageError> {
+        let kind = if url.starts_with("sqlite:") {
+            "sqlite"
+        } else if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            "postgres"
+        } else {
+            return Err(StorageError(format!("unsupported sql url: {url}")));
+        };
+        Ok(Self {
+            url: url.to_string(),
+            kind,
+            pool: OnceCell::new(),
+            renew_conn: Mutex::new(None),
+            lease,
+            locks: NamedLocks::default(),
+            instance_id: uuid::Uuid::new_v4().to_string(),
+            known: Mutex::new(HashMap::new()),
+        })
+    }
+
+    async fn pool(&self) -> Result<&AnyPool, StorageError> {
+        self.pool
+            .get_or_try_init(|| async {
+                sqlx::any::install_default_drivers();
+                // SQLite allows a single writer; one connection avoids
+                // "database is locked" between our own connections.
+                let max_connections = if self.kind == "sqlite" { 1 } else { 5 };
+                let pool = AnyPoolOptions::new()
+                    .max_connections(max_connections)
+                    .connect(&self.url)
+                    .await
+                    .map_err(sql_err("connect"))?;
+                migrate(&pool).await?;
+                Ok(pool)
+            })
+            .await
+    }
+
+    async fn try_acquire(&self, name: &str) -> Result<bool, StorageError> {
+        let pool = self.pool().await?;
+        let now = chrono::Utc::now().timestamp_millis();
+        sqlx::query("DELETE FROM storage_locks WHERE name = $1 AND expires_at < $2")
+            .bind(name)
+            .bind(now)
+            .execute(pool)
+            .await
+            .map_err(sql_err("expire lock"))?;
+        let result = sqlx::query(
+            "INSERT INTO storage_locks (name, owner, expires_at) VALUES ($1, $2, $3)
+             ON CONFLICT (name) DO NOTHING",
+        )
+        .bind(name)
+        .bind(&self.instance_id)
+        .bind(now + self.lease.as_millis() as i64)
+        .execute(pool)
+        .await
+        .map_err(sql_err("acquire lock"))?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn renew(&self, name: &str) -> Result<bool, StorageError> {
+        self.pool().await?;
+        let mut slot = self.renew_conn.lock().await;
+        let conn = match slot.as_mut() {
+            Some(conn) => conn,
+            None => slot.insert(
+                AnyConnection::connect(&self.url)
+                    .await
+                    .map_err(sql_err("connect"))?,
+            ),
+        };
+        let expires_at = chrono::Utc::now().timestamp_millis() + self.lease.as_millis() as i64;
+        let result =
+            sqlx::query("UPDATE storage_locks SET expires_at = $1 WHERE name = $2 AND owner = $3")
+                .bind(expires_at)
+                .bind(name)
+                .bind(&self.instance_id)
+                .execute(&mut *conn)
+                .await;
+        match result {
+            Ok(result) => Ok(result.rows_affected() == 1),
+            Err(err) => {
+                // Reconnect on the next renewal.
+                *slot = None;
+                Err(sql_err("renew lock")(err))
+            }
+        }
+    }
+
+    /// Upserts only rows whose plaintext changed and deletes only tokens this
+    /// process had seen, so concurrent writers touching different tokens do
+    /// not overwrite each other.
+    pub async fn save_token_rows(
+        &self,
+        plain: &JsonValue,
+        sealed: &JsonValue,
+    ) -> Result<(), StorageError> {
+        let rows = token_rows(plain, sealed)?;
+        let pool = self.pool().await?;
+        let mut known = self.known.lock().await;
+        let current: HashSet<&str> = rows.iter().map(|r| r.id.as_str()).collect();
+        let removed: Vec<String> = known
+            .keys()
+            .filter(|key| !current.contains(key.as_str()))
+            .cloned()
+            .collect();
+        let changed: Vec<&TokenRow> = rows
+            .iter()
+            .filter(|r| {
+                known.get(&r.id).and_then(|k| k.digest.as_deref()) != Some(r.digest.as_str())
+            })
+            .collect();
+        if changed.is_empty() && removed.is_empty() {
+            return Ok(());
+        }
+
+        let mut tx = pool.begin().await.map_err(sql_err("begin tokens"))?;
+        // Deletes go first: an unsealed legacy row holds the same token value
+        // its rewrite is about to insert.
+        for key in &removed {
+            delete_token(&mut tx, &known[key]).await?;
+        }
+        for row in &changed {
+            write_token(&mut tx, row).await?;
+        }
+        tx.commit().await.map_err(sql_err("commit tokens"))?;
+
+        for key in &removed {
+            known.remove(key);
+        }
+        for row in changed {
+            known.insert(
+                row.id.clone(),
+                KnownRow {
+                    id: Some(row.id.clone()),
+                    token: row.token.clone(),
+                    digest: Some(row.digest.clone()),
+                },
+            );
+        }
+        Ok(())
+    }
+}
+
+async fn migrate(pool: &AnyPool) -> Result<(), StorageError> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version BIGINT PRIMARY KEY,
+            applied_at BIGINT NOT NULL
+        )",
+    )
+    .execute(pool)
+    .await
+    .map_err(sql_err("create schema_migrations"))?;
+    let applied: HashSet<i64> = sqlx::query("SELECT version FROM schema_migrations")
+        .fetch_all(pool)
+        .await
+        .map_err(sql_err("read schema_migrations"))?
+        .iter()
+        .filter_map(|row| row.try_get::<i64, _>(0).ok())
+        .collect();
+
+    for (version, statements) in MIGRATIONS {
+        if applied.contains(version) {
+            continue;
+        }
+        let mut tx = pool.begin().await.map_err(sql_err("begin migration"))?;
+        for statement in *statements {
+            sqlx::query(statement)
+                .execute(&mut *tx)
+                .await
+                .map_err(sql_err("migration"))?;
+        }
+        sqlx::query(
+            "INSERT INTO schema_migrations (version, applied_at) VALUES ($1, $2)
+             ON CONFLICT (version) DO NOTHING",
+        )
+        .bind(*version)
+        .bind(chrono::Utc::now().timestamp_millis())
+        .execute(&mut *tx)
+        .await
+        .map_err(sql_err("record migration"))?;
+        tx.commit().await.map_err(sql_err("commit migration"))?;
+        tracing::info!("Applied storage migration {version}");
+    }
+    Ok(())
+}
+
+async fn write_token(tx: &mut Transaction<'_, Any>, row: &TokenRow) -> Result<(), StorageError> {
+    sqlx::query(
+        "INSERT INTO tokens (id, token, pool, position, status, data, digest, updated_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+         ON CONFLICT (id) DO UPDATE SET
+            token = excluded.token,
+            pool = excluded.pool,
+            position = excluded.position,
+            status = excluded.status,
+            data = excluded.data,
+            digest = excluded.digest,
+            updated_at = excluded.updated_at",
+    )
+    .bind(&row.id)
+    .bind(&row.token)
+    .bind(&row.pool)
+    .bind(row.position)
+    .bind(&row.status)
+    .bind(&row.data)
+    .bind(&row.digest)
+    .bind(chrono::Utc::now().timestamp_millis())
+    .execute(&mut **tx)
+    .await
+    .map_err(sql_err("write token"))?;
+    sqlx::query("DELETE FROM token_tags WHERE id = $1")
+        .bind(&row.id)
+        .execute(&mut **tx)
+        .await
+        .map_err(sql_err("clear token tags"))?;
+    for tag in &row.tags {
+        sqlx::query("INSERT INTO token_tags (id, tag) VALUES ($1, $2)")
+            .bind(&row.id)
+            .bind(tag)
+            .execute(&mut **tx)
+            .await
+            .map_err(sql_err("write token tag"))?;
+    }
+    Ok(())
+}
+
+async fn delete_token(tx: &mut Transaction<'_, Any>, row: &KnownRow) -> Result<(), StorageError> {
+    if let Some(id) = &row.id {
+        sqlx::query("DELETE FROM token_tags WHERE id = $1")
+            .bind(id)
+            .execute(&mut **tx)
+            .await
+            .map_err(sql_err("delete token tags"))?;
+    }
+    // Another replica may have re-sealed the row since; the id still matches.
+    sqlx::query("DELETE FROM tokens WHERE id = $1 OR token = $2")
+        .bind(row.id.as_deref())
+        .bind(&row.token)
+        .execute(&mut **tx)
+        .await
+        .map_err(sql_err("delete token"))?;
+    Ok(())
+}
+
+#[async_trait]
+impl Storage for SqlStorage {
+    fn kind(&self) -> &'static str {
+        self.kind
+    }
+
+    async fn load_config(&self) -> Result<JsonValue, StorageError> {
+        let rows = sqlx::query("SELECT section, name, value FROM config_entries")
+            .fetch_all(self.pool().await?)
+            .await
+            .map_err(sql_err("read config"))?;
+        let mut config = serde_json::Map::new();
+        for row in rows {
+            let section: String = row.try_get(0).map_err(sql_err("read config"))?;
+            let name: String = row.try_get(1).map_err(sql_err("read config"))?;
+            let raw: String = row.try_get(2).map_err(sql_err("read config"))?;
+            let value: JsonValue = serde_json::from_str(&raw)
+                .map_err(|e| StorageError(format!("parse config {section}.{name} failed: {e}")))?;
+            if name.is_empty() {
+                config.insert(section, value);
+                continue;
+            }
+            let entry = config
+                .entry(section)
+                .or_insert_with(|| JsonValue::Object(Default::default()));
+            if let Some(map) = entry.as_object_mut() {
+                map.insert(name, value);
+            }
+        }
+        Ok(JsonValue::Object(config))
+    }
+
+    /// Sections become one row per key; top-level scalars use an empty name.
+    async fn save_config(&self, data: &JsonValue) -> Result<(), StorageError> {
+        let mut entries: Vec<(String, String, String)> = Vec::new();
+        for (section, value) in data.as_object().into_iter().flatten() {
+            match value.as_object() {
+                Some(map) => {
+                    for (name, value) in map {
+                        entries.push((section.clone(), name.clone(), value.to_string()));
+                    }
+                }
+                None => entries.push((section.clone(), String::new(), value.to_string())),
+            }
+        }
+        let pool = self.pool().await?;
+        let mut tx = pool.begin().await.map_err(sql_err("begin config"))?;
+        sqlx::query("DELETE FROM config_entries")
+            .execute(&mut *tx)
+            .await
+            .map_err(sql_err("clear config"))?;
+        for (section, name, value) in entries {
+            sqlx::query("INSERT INTO config_entries (section, name, value) VALUES ($1, $2, $3)")
+                .bind(section)
+                .bind(name)
+                .bind(value)
+                .execute(&mut *tx)
+                .await
+                .map_err(sql_err("write config"))?;
+        }
+        tx.commit().await.map_err(sql_err("commit config"))
+    }
+
+    async fn load_config_history(&self) -> Result<JsonValue, StorageError> {
+        let rows = sqlx::query("SELECT data FROM config_history ORDER BY version")
+            .fetch_all(self.pool().await?)
+            .await
+            .map_err(sql_err("read config history"))?;
+        let mut versions = Vec::with_capacity(rows.len());
+        for row in rows {
+            let raw: String = row.try_get(0).map_err(sql_err("read config history"))?;
+            versions.push(
+                serde_json::from_str(&raw)
+                    .map_err(|e| StorageError(format!("parse config history failed: {e}")))?,
+            );
+        }
+        Ok(JsonValue::Array(versions))
+    }
+
+    /// One row per version, rewritten as a whole like `config_entries`.
+    async fn save_config_history(&self, data: &JsonValue) -> Result<(), StorageError> {
+        let pool = self.pool().await?;
+        let mut tx = pool
+            .begin()
+            .await
+            .map_err(sql_err("begin config history"))?;
+        sqlx::query("DELETE FROM config_history")
+            .execute(&mut *tx)
+            .await
+            .map_err(sql_err("clear config history"))?;
+        for entry in data.as_array().into_iter().flatten() {
+            let version = entry.get("version").and_then(|v| v.as_i64()).unwrap_or(0);
+            sqlx::query("INSERT INTO config_history (version, data) VALUES ($1, $2)")
+                .bind(version)
+                .bind(entry.to_string())
+                .execute(&mut *tx)
+                .await
+                .map_err(sql_err("write config history"))?;
+        }
+        tx.commit().await.map_err(sql_err("commit config history"))
+    }
+
+    async fn load_tokens(&self) -> Result<JsonValue, StorageError> {
+        let rows =
+            sqlx::query("SELECT id, token, pool, data, digest FROM tokens ORDER BY pool, position")
+                .fetch_all(self.pool().await?)
+                .await
+                .map_err(sql_err("read tokens"))?;
+        let mut pools = serde_json::Map::new();
+        let mut known = HashMap::new();
+        for row in rows {
+            let id: Option<String> = row.try_get(0).map_err(sql_err("read tokens"))?;
+            let token: String = row.try_get(1).map_err(sql_err("read tokens"))?;
+            let pool: String = row.try_get(2).map_err(sql_err("read tokens"))?;
+            let data: String = row.try_get(3).map_err(sql_err("read tokens"))?;
+            let digest: Option<String> = row.try_get(4).map_err(sql_err("read tokens"))?;
+            let value: JsonValue = serde_json::from_str(&data)
+                .map_err(|e| StorageError(format!("parse token failed: {e}")))?;
+            // Legacy rows are keyed by their token, which never matches an id.
+            let key = id.clone().unwrap_or_else(|| token.clone());
+            known.insert(key, KnownRow { id, token, digest });
+            if let Some(list) = pools
+                .entry(pool)
+                .or_insert_with(|| JsonValue::Array(Vec::new()))
+                .as_array_mut()
+            {
+                list.push(value);
+            }
+        }
+        *self.known.lock().await = known;
+        Ok(JsonValue::Object(pools))
+    }
+
+    async fn save_tokens(&self, data: &JsonValue) -> Result<(), StorageError> {
+        self.save_token_rows(data, data).await
+    }
+
+    /// Locks are rows with a lease; an expired lease is cleared by the next
+    /// process that asks for the lock. The holder renews its row while the
+    /// section runs.
+    async fn with_lock<F, Fut, T>(&self, name: &str, timeout: u64, f: F) -> Result<T, StorageError>
+    where
+        F: FnOnce() -> Fut + Send,
+        Fut: std::future::Future<Output = Result<T, StorageError>> + Send,
+        T: Send,
+    {
+        let deadline = Instant::now() + Duration::from_secs(timeout);
+        let _guard = self.locks.lock(name, deadline).await?;
+        while !self.try_acquire(name).await? {
+            if Instant::now() >= deadline {
+                return Err(StorageError(format!("lock timeout: {name}")));
+            }
+            tokio::time::sleep(Duration::from_millis(LOCK_POLL_MS)).await;
+        }
+
+        let result = run_leased(name, self.lease, f(), || self.renew(name)).await;
+        let released = sqlx::query("DELETE FROM storage_locks WHERE name = $1 AND owner = $2")
+            .bind(name)
+            .bind(&self.instance_id)
+            .execute(self.pool().await?)
+            .await;
+        match released {
+            Ok(r) if r.rows_affected() == 0 => {
+                tracing::warn!("Lock {name} lease expired before release")
+            }
+            Ok(_) => {}
+            Err(err) => tracing::warn!("Release lock {name} failed: {err}"),
+        }
+        result
+    }
+}
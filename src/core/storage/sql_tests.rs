@@ -0,0 +1,236 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::json;
+
+use crate::core::storage::crypto::{Document, Keyring};
+use crate::core::storage::test_util::TempDb;
+use crate::core::storage::{Storage, StorageError};
+
+fn token(raw: &str, quota: i64, tags: &[&str]) -> serde_json::Value {
+    json!({"token": raw, "status": "active", "quota": quota, "tags": tags})
+}
+
+#[tokio::test]
+async fn round_trips_config_and_tokens() {
+    let db = TempDb::new();
+    let storage = db.open(Duration::from_secs(30));
+
+    assert_eq!(storage.load_tokens().await.unwrap(), json!({}));
+
+    let config = json!({"app": {"api_key": "k"}, "token": {"auto_refresh": false}});
+    storage.save_config(&config).await.unwrap();
+    assert_eq!(storage.load_config().await.unwrap(), config);
+
+    let tokens = json!({
+        "ssoBasic": [token("b", 80, &["nsfw"]), token("a", 10, &[])],
+        "ssoSuper": [token("c", 140, &[])],
+    });
+    storage.save_tokens(&tokens).await.unwrap();
+
+    // A second handle re-runs migrations and reads through a fresh pool.
+    let reopened = db.open(Duration::from_secs(30));
+    assert_eq!(reopened.load_tokens().await.unwrap(), tokens);
+}
+
+#[tokio::test]
+async fn concurrent_writers_update_separate_rows() {
+    let db = TempDb::new();
+    let seed = db.open(Duration::from_secs(30));
+    seed.save_tokens(&json!({"ssoBasic": [token("a", 80, &[]), token("b", 80, &[])]}))
+        .await
+        .unwrap();
+
+    let first = db.open(Duration::from_secs(30));
+    let second = db.open(Duration::from_secs(30));
+    let mut view_a = first.load_tokens().await.unwrap();
+    let mut view_b = second.load_tokens().await.unwrap();
+
+    view_a["ssoBasic"][0]["quota"] = json!(70);
+    view_b["ssoBasic"][1]["quota"] = json!(60);
+    view_b["ssoBasic"]
+        .as_array_mut()
+        .unwrap()
+        .push(token("c", 80, &[]));
+    first.save_tokens(&view_a).await.unwrap();
+    second.save_tokens(&view_b).await.unwrap();
+
+    let merged = seed.load_tokens().await.unwrap();
+    let quotas: Vec<(String, i64)> = merged["ssoBasic"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| {
+            (
+                t["token"].as_str().unwrap().to_string(),
+                t["quota"].as_i64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        quotas,
+        vec![
+            ("a".to_string(), 70),
+            ("b".to_string(), 60),
+            ("c".to_string(), 80)
+        ]
+    );
+
+    // Removing a token only deletes its own row.
+    view_a["ssoBasic"].as_array_mut().unwrap().remove(1);
+    first.save_tokens(&view_a).await.unwrap();
+    let remaining = seed.load_tokens().await.unwrap();
+    let names: Vec<&str> = remaining["ssoBasic"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["token"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["a", "c"]);
+}
+
+#[tokio::test]
+async fn lock_is_exclusive_while_renewed() {
+    let db = TempDb::new();
+    let first = Arc::new(db.open(Duration::from_millis(300)));
+    let second = db.open(Duration::from_secs(30));
+
+    let holder = first.clone();
+    let (held_tx, held_rx) = tokio::sync::oneshot::channel();
+    let task = tokio::spawn(async move {
+        holder
+            .with_lock("tokens_save", 5, || async {
+                let _ = held_tx.send(());
+                // Outlives the lease several times over; renewals keep it held.
+                tokio::time::sleep(Duration::from_millis(1500)).await;
+                Ok(())
+            })
+            .await
+    });
+    held_rx.await.unwrap();
+
+    let result = second
+        .with_lock("tokens_save", 1, || async { Ok(()) })
+        .await;
+    assert!(matches!(result, Err(StorageError(msg)) if msg.contains("lock timeout")));
+
+    task.await.unwrap().unwrap();
+    let value = second
+        .with_lock("tokens_save", 1, || async { Ok(7) })
+        .await
+        .unwrap();
+    assert_eq!(value, 7);
+}
+
+#[tokio::test]
+async fn abandoned_lock_expires_with_its_lease() {
+    let db = TempDb::new();
+    let first = Arc::new(db.open(Duration::from_millis(300)));
+    let second = db.open(Duration::from_secs(30));
+
+    let holder = first.clone();
+    let (held_tx, held_rx) = tokio::sync::oneshot::channel();
+    let task = tokio::spawn(async move {
+        holder
+            .with_lock("tokens_save", 5, || async {
+                let _ = held_tx.send(());
+                std::future::pending::<()>().await;
+                Ok(())
+            })
+            .await
+    });
+    held_rx.await.unwrap();
+    // Stops renewing without releasing, as a crashed replica would.
+    task.abort();
+
+    let started = Instant::now();
+    let value = second
+        .with_lock("tokens_save", 5, || async { Ok(7) })
+        .await
+        .unwrap();
+    assert_eq!(value, 7);
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
+
+#[tokio::test]
+async fn lost_lease_fails_the_section() {
+    let db = TempDb::new();
+    let storage = db.open(Duration::from_millis(300));
+    let url = db.url();
+
+    let result = storage
+        .with_lock("config_save", 5, || async move {
+            let pool = sqlx::AnyPool::connect(&url).await.unwrap();
+            sqlx::query("UPDATE storage_locks SET owner = 'other-replica'")
+                .execute(&pool)
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(())
+        })
+        .await;
+    assert!(matches!(result, Err(StorageError(msg)) if msg.contains("lease lost")));
+}
+
+#[tokio::test]
+async fn sealed_tokens_keep_one_row_per_token() {
+    let db = TempDb::new();
+    let keyring = Keyring::new(Some(&STANDARD.encode([7u8; 32])), &[]).unwrap();
+    let plain = json!({"ssoBasic": [token("a", 80, &["nsfw"]), token("b", 80, &[])]});
+    let seal = || keyring.seal_document(&plain, Document::Tokens).unwrap();
+
+    let first = db.open(Duration::from_secs(30));
+    let second = db.open(Duration::from_secs(30));
+    first.save_token_rows(&plain, &seal()).await.unwrap();
+    // Same tokens sealed with fresh nonces, as another replica would.
+    let resealed = seal();
+    second.save_token_rows(&plain, &resealed).await.unwrap();
+    first.save_token_rows(&plain, &seal()).await.unwrap();
+
+    let stored = db
+        .open(Duration::from_secs(30))
+        .load_tokens()
+        .await
+        .unwrap();
+    assert_eq!(stored, resealed);
+    assert_eq!(
+        keyring.open_document(&stored, Document::Tokens).unwrap(),
+        plain
+    );
+}
+
+#[tokio::test]
+async fn legacy_rows_are_rekeyed_on_save() {
+    let db = TempDb::new();
+    let storage = db.open(Duration::from_secs(30));
+    assert_eq!(storage.load_tokens().await.unwrap(), json!({}));
+    let pool = sqlx::AnyPool::connect(&db.url()).await.unwrap();
+    sqlx::query(
+        "INSERT INTO tokens (token, pool, position, status, data, updated_at)
+         VALUES ('a', 'ssoBasic', 0, 'active', $1, 0)",
+    )
+    .bind(token("a", 80, &[]).to_string())
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let mut tokens = storage.load_tokens().await.unwrap();
+    tokens["ssoBasic"][0]["quota"] = json!(70);
+    storage.save_tokens(&tokens).await.unwrap();
+
+    let ids: Vec<Option<String>> = sqlx::query_scalar("SELECT id FROM tokens")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(ids.len(), 1);
+    assert!(ids[0].is_some());
+    assert_eq!(
+        db.open(Duration::from_secs(30))
+            .load_tokens()
+            .await
+            .unwrap(),
+        tokens
+    );
+}
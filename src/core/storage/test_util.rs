@@ -0,0 +1,27 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::core::storage::SqlStorage;
+
+/// A SQLite file under the temp dir, removed on drop.
+pub struct TempDb(PathBuf);
+
+impl TempDb {
+    pub fn new() -> Self {
+        Self(std::env::temp_dir().join(format!("grok2api-{}.db", uuid::Uuid::new_v4())))
+    }
+
+    pub fn url(&self) -> String {
+        format!("sqlite://{}?mode=rwc", self.0.display())
+    }
+
+    pub fn open(&self, lease: Duration) -> SqlStorage {
+        SqlStorage::new(&self.url(), lease).unwrap()
+    }
+}
+
+impl Drop for TempDb {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
@@ -17,18 +17,56 @@ async fn main() {
         .with_target(false)
         .with_thread_ids(true)
         .with_thread_names(true)
+        .with_writer(core::redact::RedactingStdout)
         .init();
 
+    if let Err(err) = core::storage::crypto::init_keyring() {
+        eprintln!("{err}");
+        std::process::exit(2);
+    }
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("migrate-storage") => {
+            std::process::exit(core::storage::migrate::run_cli(&args[1..]).await);
+        }
+        Some("reencrypt-storage") => {
+            std::process::exit(core::storage::crypto::run_cli().await);
+        }
+        _ => {}
+    }
+
+    if let Err(err) = core::config::overrides::init_overrides(&args) {
+        eprintln!("{err}");
+        std::process::exit(2);
+    }
+
+    if let Err(err) = core::storage::init_storage() {
+        eprintln!("Storage backend unavailable: {err}");
+        std::process::exit(2);
+    }
+
+    if let Err(err) = core::storage::crypto::check_readable(&core::storage::get_storage()).await {
+        eprintln!("{err}");
+        std::process::exit(2);
+    }
+
     // Initialize config at startup
     if let Err(err) = core::config::load_config().await {
-        tracing::warn!("Failed to load config: {err}");
+        eprintln!("Failed to load config: {err}");
+        std::process::exit(2);
     }
+    core::config::start_config_watcher();
 
-    let auto_refresh: bool = core::config::get_config("token.auto_refresh", true).await;
-    if auto_refresh {
-        let scheduler = services::token::scheduler::get_scheduler().await;
-        scheduler.lock().await.start();
-    }
+    services::token::scheduler::start_scheduler_watcher();
+    services::token::manager::start_pool_level_watcher();
+    services::token::tier::start_tier_checker();
+    services::token::nsfw::start_nsfw_enabler();
+    services::grok::media::start_media_limit_watcher();
+    services::grok::assets::start_asset_client_watcher();
+    services::proxy::start_proxy_pool_watcher();
+    services::proxy::checker::start_proxy_checker();
+    services::token::webhook::start_webhook_dispatcher();
 
     let host = std::env::var("SERVER_HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
     let port = std::env::var("SERVER_PORT")
@@ -44,6 +82,35 @@ async fn main() {
     tracing::info!("Starting grok2api-rs at http://{addr}");
 
     axum::serve(tokio::net::TcpListener::bind(addr).await.unwrap(), app)
+        .with_graceful_shutdown(shutdown_signal())
         .await
         .unwrap();
+
+    let _ = services::token::manager::flush_tokens().await;
+    tracing::info!("grok2api-rs stopped");
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    tracing::info!("Shutdown signal received, flushing token state");
 }
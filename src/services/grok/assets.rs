@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
@@ -10,9 +11,11 @@ use tokio::sync::Mutex;
 use url::Url;
 use uuid::Uuid;
 
-use crate::core::config::get_config;
+use crate::core::config::{get_config, on_change};
 use crate::core::exceptions::ApiError;
 use crate::services::grok::statsig::StatsigService;
+use crate::services::grok::wreq_client::apply_browser_headers;
+use crate::services::proxy::{ProxyPurpose, select_proxy};
 
 const UPLOAD_API: &str = "https://grok.com/rest/app-chat/upload-file";
 const LIST_API: &str = "https://grok.com/rest/assets";
@@ -22,6 +25,7 @@ const DOWNLOAD_API: &str = "https://assets.grok.com";
 const DEFAULT_MIME: &str = "application/octet-stream";
 
 async fn wreq_request(
+    token: &str,
     proxy: &str,
     timeout: u64,
     method: &str,
@@ -30,7 +34,13 @@ async fn wreq_request(
     body: Option<&[u8]>,
     capture_headers: bool,
 ) -> Result<(u16, Vec<(String, String)>, Vec<u8>), ApiError> {
-    let client = crate::services::grok::wreq_client::build_client(Some(proxy), timeout).await?;
+    let client = crate::services::grok::wreq_client::build_client_for_token(
+        token,
+        Some(proxy),
+        timeout,
+        None,
+    )
+    .await?;
     let method = wreq::Method::from_bytes(method.as_bytes())
         .map_err(|e| ApiError::upstream(format!("Invalid HTTP method `{method}`: {e}")))?;
 
@@ -42,8 +52,8 @@ async fn wreq_request(
         request = request.body(payload.to_vec());
     }
 
-    let response = request
-        .send()
+    let response = client
+        .send(request)
         .await
         .map_err(|e| ApiError::upstream(format!("wreq request failed: {e}")))?;
 
@@ -112,16 +122,39 @@ pub struct BaseService {
 
 type ArcMutex = std::sync::Arc<Mutex<()>>;
 
+/// Fetch clients shared per proxy URL, dropped whenever the proxy config changes.
+static ASSET_CLIENTS: once_cell::sync::Lazy<std::sync::Mutex<HashMap<String, Client>>> =
+    once_cell::sync::Lazy::new(Default::default);
+
+fn asset_client(proxy: &str) -> Client {
+    let mut clients = ASSET_CLIENTS.lock().unwrap();
+    if let Some(client) = clients.get(proxy) {
+        return client.clone();
+    }
+    let mut builder = Client::builder();
+    if !proxy.is_empty() {
+        if let Ok(proxy) = reqwest::Proxy::all(proxy) {
+            builder = builder.proxy(proxy);
+        }
+    }
+    let client = builder.build().unwrap();
+    clients.insert(proxy.to_string(), client.clone());
+    client
+}
+
+pub fn start_asset_client_watcher() {
+    on_change(
+        &["grok.asset_proxy_url", "grok.base_proxy_url", "proxy.pool"],
+        || async {
+            ASSET_CLIENTS.lock().unwrap().clear();
+        },
+    );
+}
+
 impl BaseService {
     pub async fn new(proxy: Option<String>) -> Self {
         let proxy = proxy.unwrap_or_default();
-        let mut builder = Client::builder();
-        if !proxy.is_empty() {
-            if let Ok(proxy) = reqwest::Proxy::all(&proxy) {
-                builder = builder.proxy(proxy);
-            }
-        }
-        let client = builder.build().unwrap();
+        let client = asset_client(&proxy);
         let timeout = get_config("grok.timeout", 120u64).await;
         Self {
             proxy,
@@ -168,12 +201,7 @@ impl BaseService {
         headers.insert("Sec-Fetch-Dest", "empty".parse().unwrap());
         headers.insert("Sec-Fetch-Mode", "cors".parse().unwrap());
         headers.insert("Sec-Fetch-Site", "same-origin".parse().unwrap());
-        headers.insert(
-            "User-Agent",
-            "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/136.0.0.0 Safari/537.36"
-                .parse()
-                .unwrap(),
-        );
+        apply_browser_headers(&mut headers, token).await;
         let statsig = StatsigService::gen_id().await;
         headers.insert("x-statsig-id", statsig.parse().unwrap());
         headers.insert(
@@ -181,7 +209,7 @@ impl BaseService {
             Uuid::new_v4().to_string().parse().unwrap(),
         );
         let raw = token.strip_prefix("sso=").unwrap_or(token);
-        let cf: String = get_config("grok.cf_clearance", String::new()).await;
+        let cf = crate::services::grok::wreq_client::cf_clearance_for(token).await;
         let cookie = if cf.is_empty() {
             format!("sso={raw}")
         } else {
@@ -204,9 +232,9 @@ impl BaseService {
         headers.insert("Sec-Fetch-Site", "same-site".parse().unwrap());
         headers.insert("Sec-Fetch-User", "?1".parse().unwrap());
         headers.insert("Upgrade-Insecure-Requests", "1".parse().unwrap());
-        headers.insert("User-Agent", "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/136.0.0.0 Safari/537.36".parse().unwrap());
+        apply_browser_headers(&mut headers, token).await;
         let raw = token.strip_prefix("sso=").unwrap_or(token);
-        let cf: String = get_config("grok.cf_clearance", String::new()).await;
+        let cf = crate::services::grok::wreq_client::cf_clearance_for(token).await;
         let cookie = if cf.is_empty() {
             format!("sso={raw}")
         } else {
@@ -278,11 +306,8 @@ pub struct UploadService {
 
 impl UploadService {
     pub async fn new() -> Self {
-        let proxy: String = get_config("grok.asset_proxy_url", String::new()).await;
-        let base_proxy: String = get_config("grok.base_proxy_url", String::new()).await;
-        let proxy = if proxy.is_empty() { base_proxy } else { proxy };
         Self {
-            base: BaseService::new(Some(proxy)).await,
+            base: BaseService::new(select_proxy(ProxyPurpose::Asset).await).await,
         }
     }
 
@@ -305,6 +330,7 @@ impl UploadService {
         });
         let body = payload.to_string();
         let (status, _resp_headers, resp_body) = wreq_request(
+            token,
             &self.base.proxy,
             self.base.timeout,
             "POST",
@@ -346,11 +372,8 @@ pub struct ListService {
 
 impl ListService {
     pub async fn new() -> Self {
-        let proxy: String = get_config("grok.asset_proxy_url", String::new()).await;
-        let base_proxy: String = get_config("grok.base_proxy_url", String::new()).await;
-        let proxy = if proxy.is_empty() { base_proxy } else { proxy };
         Self {
-            base: BaseService::new(Some(proxy)).await,
+            base: BaseService::new(select_proxy(ProxyPurpose::Asset).await).await,
         }
     }
 
@@ -384,6 +407,7 @@ impl ListService {
             }
             let url = url.to_string();
             let (status, _resp_headers, resp_body) = wreq_request(
+                token,
                 &self.base.proxy,
                 self.base.timeout,
                 "GET",
@@ -433,11 +457,8 @@ pub struct DeleteService {
 
 impl DeleteService {
     pub async fn new() -> Self {
-        let proxy: String = get_config("grok.asset_proxy_url", String::new()).await;
-        let base_proxy: String = get_config("grok.base_proxy_url", String::new()).await;
-        let proxy = if proxy.is_empty() { base_proxy } else { proxy };
         Self {
-            base: BaseService::new(Some(proxy)).await,
+            base: BaseService::new(select_proxy(ProxyPurpose::Asset).await).await,
         }
     }
 
@@ -445,6 +466,7 @@ impl DeleteService {
         let headers = self.base.headers(token, "https://grok.com/files").await;
         let url = format!("{DELETE_API}/{asset_id}");
         let (status, _resp_headers, resp_body) = wreq_request(
+            token,
             &self.base.proxy,
             self.base.timeout,
             "DELETE",
@@ -496,10 +518,7 @@ pub struct DownloadService {
 
 impl DownloadService {
     pub async fn new() -> Self {
-        let proxy: String = get_config("grok.asset_proxy_url", String::new()).await;
-        let base_proxy: String = get_config("grok.base_proxy_url", String::new()).await;
-        let proxy = if proxy.is_empty() { base_proxy } else { proxy };
-        let base = BaseService::new(Some(proxy)).await;
+        let base = BaseService::new(select_proxy(ProxyPurpose::Asset).await).await;
         let base_dir = crate::core::config::project_root().join("data").join("tmp");
         let image_dir = base_dir.join("image");
         let video_dir = base_dir.join("video");
@@ -551,6 +570,7 @@ impl DownloadService {
         let url = format!("{DOWNLOAD_API}{path}");
         let headers = self.base.dl_headers(token, &path).await;
         let (status, resp_headers, resp_body) = wreq_request(
+            token,
             &self.base.proxy,
             self.base.timeout,
             "GET",
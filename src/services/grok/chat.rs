@@ -3,17 +3,18 @@ use std::pin::Pin;
 use futures::Stream;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use crate::core::config::get_config;
+use crate::core::config::get_model_config;
 use crate::core::exceptions::ApiError;
 use crate::services::grok::assets::UploadService;
-use crate::services::grok::model::ModelService;
+use crate::services::grok::model::{ModelInfo, ModelService};
 use crate::services::grok::statsig::StatsigService;
 use crate::services::grok::wreq_client::{
-    apply_headers, body_preview, build_client, line_stream_from_response,
+    apply_browser_headers, apply_headers, body_preview, build_client_for_token, cf_clearance_for,
+    line_stream_from_response,
 };
-use crate::services::token::TokenService;
+use crate::services::token::{TokenRoute, TokenService};
 
 const CHAT_API: &str = "https://grok.com/rest/app-chat/conversations/new";
 
@@ -176,7 +177,7 @@ impl ChatRequestBuilder {
         headers.insert("Sec-Fetch-Dest", "empty".parse().unwrap());
         headers.insert("Sec-Fetch-Mode", "cors".parse().unwrap());
         headers.insert("Sec-Fetch-Site", "same-origin".parse().unwrap());
-        headers.insert("User-Agent", "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/136.0.0.0 Safari/537.36".parse().unwrap());
+        apply_browser_headers(&mut headers, token).await;
         let statsig = StatsigService::gen_id().await;
         headers.insert("x-statsig-id", statsig.parse().unwrap());
         headers.insert(
@@ -184,7 +185,7 @@ impl ChatRequestBuilder {
             uuid::Uuid::new_v4().to_string().parse().unwrap(),
         );
         let raw = token.strip_prefix("sso=").unwrap_or(token);
-        let cf: String = get_config("grok.cf_clearance", String::new()).await;
+        let cf = cf_clearance_for(token).await;
         let cookie = if cf.is_empty() {
             format!("sso={raw}")
         } else {
@@ -196,18 +197,21 @@ impl ChatRequestBuilder {
 
     pub async fn build_payload(
         message: &str,
-        model: &str,
-        mode: &str,
+        model_info: &ModelInfo,
         think: Option<bool>,
         file_attachments: &[String],
         image_attachments: &[String],
     ) -> JsonValue {
-        let temporary: bool = get_config("grok.temporary", true).await;
-        let _think = think.unwrap_or(get_config("grok.thinking", false).await);
+        let model = &model_info.grok_model;
+        let temporary: bool = get_model_config(&model_info.model_id, "temporary", true).await;
+        let _think = match think {
+            Some(think) => think,
+            None => get_model_config(&model_info.model_id, "thinking", false).await,
+        };
         serde_json::json!({
             "temporary": temporary,
             "modelName": model,
-            "modelMode": mode,
+            "modelMode": model_info.model_mode,
             "message": message,
             "fileAttachments": file_attachments,
             "imageAttachments": image_attachments,
@@ -254,8 +258,7 @@ impl GrokChatService {
         &self,
         token: &str,
         message: &str,
-        model: &str,
-        mode: &str,
+        model_info: &ModelInfo,
         think: Option<bool>,
         _stream: bool,
         file_attachments: &[String],
@@ -264,8 +267,7 @@ impl GrokChatService {
         self.chat_via_wreq(
             token,
             message,
-            model,
-            mode,
+            model_info,
             think,
             file_attachments,
             image_attachments,
@@ -277,8 +279,7 @@ impl GrokChatService {
         &self,
         token: &str,
         message: &str,
-        model: &str,
-        mode: &str,
+        model_info: &ModelInfo,
         think: Option<bool>,
         file_attachments: &[String],
         image_attachments: &[String],
@@ -286,22 +287,20 @@ impl GrokChatService {
         let headers = ChatRequestBuilder::build_headers(token).await;
         let payload = ChatRequestBuilder::build_payload(
             message,
-            model,
-            mode,
+            model_info,
             think,
             file_attachments,
             image_attachments,
         )
         .await;
-        let timeout: u64 = get_config("grok.timeout", 120u64).await;
-        let proxy: String = get_config("grok.base_proxy_url", String::new()).await;
-        let client = build_client(Some(&proxy), timeout).await?;
+        let timeout: u64 = get_model_config(&model_info.model_id, "timeout", 120u64).await;
+        let client = build_client_for_token(token, None, timeout, None).await?;
         let request = apply_headers(client.post(CHAT_API), &headers)
             .timeout(Duration::from_secs(timeout))
             .body(payload.to_string());
 
-        let response = request
-            .send()
+        let response = client
+            .send(request)
             .await
             .map_err(|e| ApiError::upstream(format!("Chat request failed: {e}")))?;
 
@@ -328,7 +327,8 @@ impl GrokChatService {
             }
             return Err(ApiError::upstream(format!(
                 "Grok API request failed: {status_code}; content-type: {content_type}; body: {preview}"
-            )));
+            ))
+            .with_upstream_status(status_code));
         }
 
         Ok(line_stream_from_response(response))
@@ -358,20 +358,21 @@ impl GrokChatService {
             }
         }
 
-        let stream = request
-            .stream
-            .unwrap_or(get_config("grok.stream", true).await);
-        let think = request
-            .think
-            .or(Some(get_config("grok.thinking", false).await));
+        let stream = match request.stream {
+            Some(stream) => stream,
+            None => get_model_config(&request.model, "stream", true).await,
+        };
+        let think = match request.think {
+            Some(think) => think,
+            None => get_model_config(&request.model, "thinking", false).await,
+        };
 
         let response = self
             .chat(
                 token,
                 &message,
-                &model_info.grok_model,
-                &model_info.model_mode,
-                think,
+                &model_info,
+                Some(think),
                 stream,
                 &file_ids,
                 &image_ids,
@@ -389,8 +390,9 @@ impl ChatService {
         messages: Vec<JsonValue>,
         stream: Option<bool>,
         thinking: Option<String>,
+        route: &TokenRoute,
     ) -> Result<ChatResult, ApiError> {
-        let token = TokenService::get_token_for_model(model).await?;
+        let token = TokenService::get_token_for_model(model, route).await?;
         let think = match thinking.as_deref() {
             Some("enabled") => Some(true),
             Some("disabled") => Some(false),
@@ -403,13 +405,22 @@ impl ChatService {
             think,
         };
         let service = GrokChatService::new().await;
-        let (resp, is_stream, model_name) = service.chat_openai(&token, &chat_req).await?;
+        let started_at = Instant::now();
+        let (resp, is_stream, model_name) = match service.chat_openai(&token, &chat_req).await {
+            Ok(result) => result,
+            Err(err) => {
+                TokenService::record_error(&token, &err).await;
+                return Err(err);
+            }
+        };
+        TokenService::record_ttfb(&token, started_at.elapsed()).await;
         Ok(ChatResult::Stream {
             stream: resp,
             token,
             model: model_name,
             is_stream,
             think,
+            started_at,
         })
     }
 }
@@ -423,6 +434,7 @@ pub enum ChatResult {
         model: String,
         is_stream: bool,
         think: Option<bool>,
+        started_at: Instant,
     },
     Json(JsonValue),
 }
@@ -136,3 +136,59 @@ pub fn get_grpc_status(trailers: &HashMap<String, String>) -> GrpcStatus {
     let code = raw.parse::<i32>().unwrap_or(-1);
     GrpcStatus { code, message: msg }
 }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtoValue {
+    Varint(u64),
+    Bytes(Vec<u8>),
+}
+
+/// Minimal protobuf reader: returns varint and length-delimited fields in
+/// wire order, skipping fixed-width ones and stopping at malformed input.
+pub fn decode_proto_fields(data: &[u8]) -> Vec<(u32, ProtoValue)> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let Some((key, n)) = read_varint(&data[i..]) else {
+            break;
+        };
+        i += n;
+        let field = (key >> 3) as u32;
+        match key & 0x07 {
+            0 => {
+                let Some((value, n)) = read_varint(&data[i..]) else {
+                    break;
+                };
+                i += n;
+                out.push((field, ProtoValue::Varint(value)));
+            }
+            1 => i += 8,
+            2 => {
+                let Some((len, n)) = read_varint(&data[i..]) else {
+                    break;
+                };
+                i += n;
+                let end = i.saturating_add(len as usize);
+                if end > data.len() {
+                    break;
+                }
+                out.push((field, ProtoValue::Bytes(data[i..end].to_vec())));
+                i = end;
+            }
+            5 => i += 4,
+            _ => break,
+        }
+    }
+    out
+}
+
+fn read_varint(data: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (idx, byte) in data.iter().enumerate().take(10) {
+        value |= ((byte & 0x7F) as u64) << (7 * idx);
+        if byte & 0x80 == 0 {
+            return Some((value, idx + 1));
+        }
+    }
+    None
+}
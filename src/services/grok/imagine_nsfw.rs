@@ -1,26 +1,32 @@
 use std::collections::{HashMap, HashSet};
-use std::path::PathBuf;
-use std::sync::Arc;
 use std::time::Duration;
 
 use base64::Engine;
 use futures::{SinkExt, StreamExt};
-use once_cell::sync::Lazy;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use serde_json::{Value as JsonValue, json};
 use sha1::{Digest, Sha1};
-use tokio::sync::{Mutex, mpsc};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::{OnceCell, mpsc};
 use tokio::time::{Instant, timeout};
-use tokio_tungstenite::connect_async;
 use tokio_tungstenite::tungstenite::Message;
 use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::client::Request as WsRequest;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, client_async_tls, connect_async};
 
-use crate::core::config::{get_config, project_root};
+use crate::core::config::{get_config, get_model_config, project_root};
+use crate::services::grok::wreq_client::{
+    build_client_for_token, cf_clearance_for, user_agent_for,
+};
 use crate::services::token::get_token_manager;
+use crate::services::token::models::{ImagineState, TokenInfo};
 
 const GROK_WS_URL: &str = "wss://grok.com/ws/imagine/listen";
 const AGE_VERIFY_URL: &str = "https://grok.com/rest/auth/set-birth-date";
-const RESET_INTERVAL_SECS: f64 = 86_400.0;
+const AGE_VERIFY_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/133.0.0.0 Safari/537.36";
+const WS_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36";
+const LEGACY_STATE_FILE: &str = "imagine_nsfw_state.json";
 
 #[derive(Debug, Clone)]
 pub struct ImagineProgressEvent {
@@ -98,56 +104,30 @@ impl GenerationProgress {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct KeyUsage {
+#[derive(Debug, Default, Deserialize)]
+struct LegacyKeyUsage {
+    #[serde(default)]
     count: i32,
+    #[serde(default)]
     last_used: f64,
-    first_used: f64,
+    #[serde(default)]
     failed: bool,
+    #[serde(default)]
     age_verified: i32,
 }
 
-impl Default for KeyUsage {
-    fn default() -> Self {
-        Self {
-            count: 0,
-            last_used: 0.0,
-            first_used: now_secs(),
-            failed: false,
-            age_verified: 0,
-        }
-    }
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-struct RotationStateData {
+#[derive(Debug, Default, Deserialize)]
+struct LegacyRotationState {
+    #[serde(default)]
     last_reset: f64,
-    current_index: usize,
-    usage: HashMap<String, KeyUsage>,
-}
-
-#[derive(Debug)]
-struct RotationStore {
-    loaded: bool,
-    path: PathBuf,
-    state: RotationStateData,
-}
-
-impl RotationStore {
-    fn new() -> Self {
-        Self {
-            loaded: false,
-            path: project_root().join("data").join("imagine_nsfw_state.json"),
-            state: RotationStateData::default(),
-        }
-    }
+    #[serde(default)]
+    usage: HashMap<String, LegacyKeyUsage>,
 }
 
-static ROTATION_STORE: Lazy<Arc<Mutex<RotationStore>>> =
-    Lazy::new(|| Arc::new(Mutex::new(RotationStore::new())));
+static LEGACY_MIGRATED: OnceCell<()> = OnceCell::const_new();
 
-fn now_secs() -> f64 {
-    chrono::Utc::now().timestamp_millis() as f64 / 1000.0
+fn now_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
 }
 
 fn key_hash(token: &str) -> String {
@@ -161,192 +141,109 @@ fn sanitize_token(token: &str) -> String {
     token.trim().trim_start_matches("sso=").to_string()
 }
 
-async fn load_rotation_state(store: &mut RotationStore) {
-    if store.loaded {
-        return;
-    }
-    store.loaded = true;
-    match tokio::fs::read_to_string(&store.path).await {
-        Ok(content) => {
-            if let Ok(parsed) = serde_json::from_str::<RotationStateData>(&content) {
-                store.state = parsed;
-                tracing::info!("[ImagineNSFW] loaded rotation state");
-            }
-        }
-        Err(_) => {}
-    }
-}
-
-async fn save_rotation_state(store: &RotationStore) {
-    if let Some(parent) = store.path.parent() {
-        let _ = tokio::fs::create_dir_all(parent).await;
-    }
-    if let Ok(content) = serde_json::to_string_pretty(&store.state) {
-        let _ = tokio::fs::write(&store.path, content).await;
-    }
-}
-
-fn ensure_usage<'a>(state: &'a mut RotationStateData, token: &str) -> &'a mut KeyUsage {
-    let key = key_hash(token);
-    state.usage.entry(key).or_insert_with(KeyUsage::default)
-}
-
-fn get_usage(state: &RotationStateData, token: &str) -> KeyUsage {
-    state
-        .usage
-        .get(&key_hash(token))
-        .cloned()
-        .unwrap_or_default()
-}
-
-fn check_daily_reset(state: &mut RotationStateData) {
-    let now = now_secs();
-    if state.last_reset == 0.0 {
-        state.last_reset = now;
-        return;
-    }
-    if now - state.last_reset < RESET_INTERVAL_SECS {
-        return;
-    }
-    for usage in state.usage.values_mut() {
-        usage.count = 0;
-        usage.failed = false;
-    }
-    state.last_reset = now;
-    tracing::info!("[ImagineNSFW] daily usage reset");
-}
-
-fn get_available_tokens(
-    tokens: &[String],
-    state: &RotationStateData,
-    daily_limit: i32,
-) -> Vec<String> {
-    tokens
-        .iter()
-        .filter_map(|token| {
-            let usage = get_usage(state, token);
-            if usage.failed || usage.count >= daily_limit {
-                None
-            } else {
-                Some(token.clone())
+/// Folds the old `data/imagine_nsfw_state.json` rotation file into
+/// `TokenInfo.imagine` once, then renames it so it is not read again.
+async fn migrate_legacy_state() {
+    LEGACY_MIGRATED
+        .get_or_init(|| async {
+            let path = project_root().join("data").join(LEGACY_STATE_FILE);
+            let Ok(content) = tokio::fs::read_to_string(&path).await else {
+                return;
+            };
+            let legacy: LegacyRotationState = serde_json::from_str(&content).unwrap_or_default();
+            let window_started_at =
+                (legacy.last_reset > 0.0).then(|| (legacy.last_reset * 1000.0) as i64);
+            let mgr = get_token_manager().await;
+            let mut migrated = 0usize;
+            for info in mgr.pools().values().flat_map(|p| p.list()) {
+                let Some(usage) = legacy.usage.get(&key_hash(&info.token)) else {
+                    continue;
+                };
+                mgr.update_token(&info.token, |t| {
+                    t.imagine.daily_count = usage.count;
+                    t.imagine.window_started_at = window_started_at;
+                    t.imagine.last_used_at =
+                        (usage.last_used > 0.0).then(|| (usage.last_used * 1000.0) as i64);
+                    t.imagine.failed = usage.failed;
+                    t.imagine.age_verified = usage.age_verified != 0;
+                })
+                .await;
+                migrated += 1;
             }
+            let _ = tokio::fs::rename(&path, path.with_extension("json.migrated")).await;
+            tracing::info!("[ImagineNSFW] migrated rotation state for {migrated} token(s)");
         })
-        .collect()
+        .await;
 }
 
-async fn get_next_sso(tokens: &[String], daily_limit: i32) -> Option<String> {
-    let mut guard = ROTATION_STORE.lock().await;
-    load_rotation_state(&mut guard).await;
-    check_daily_reset(&mut guard.state);
-
-    for token in tokens {
-        let _ = ensure_usage(&mut guard.state, token);
-    }
+async fn get_next_sso(daily_limit: i32) -> Option<TokenInfo> {
+    let mgr = get_token_manager().await;
+    mgr.reload_if_stale().await;
+    let tokens = mgr.imagine_tokens();
+    let now = now_ms();
 
-    let available = get_available_tokens(tokens, &guard.state, daily_limit);
+    let available: Vec<&TokenInfo> = tokens
+        .iter()
+        .filter(|t| t.can_imagine(daily_limit, now))
+        .collect();
     if available.is_empty() {
-        let all_failed = tokens.iter().all(|t| get_usage(&guard.state, t).failed);
+        let all_failed = !tokens.is_empty() && tokens.iter().all(|t| t.imagine.is_failed(now));
         if all_failed {
-            for token in tokens {
-                ensure_usage(&mut guard.state, token).failed = false;
-            }
-            save_rotation_state(&guard).await;
+            let raws: Vec<String> = tokens.iter().map(|t| t.token.clone()).collect();
+            mgr.clear_imagine_failures(&raws).await;
             tracing::info!("[ImagineNSFW] reset failed list");
-            return tokens.first().cloned();
+            return tokens.into_iter().next();
         }
         return None;
     }
 
     // Hybrid strategy, same as imagine2api: remaining quota + least recently used factor.
-    let now = now_secs();
     let mut best_score = -1.0f64;
-    let mut selected = available[0].clone();
-    for token in available {
-        let usage = get_usage(&guard.state, &token);
-        let remaining = (daily_limit - usage.count).max(0) as f64;
-        let time_factor = if usage.last_used == 0.0 {
-            10.0
-        } else {
-            (((now - usage.last_used) / 60.0) * 0.1).min(10.0)
+    let mut selected = available[0];
+    for info in available {
+        let remaining = info.imagine.remaining(daily_limit, now) as f64;
+        let time_factor = match info.imagine.last_used_at {
+            None => 10.0,
+            Some(at) => (((now - at) as f64 / 60_000.0) * 0.1).min(10.0),
         };
         let score = remaining * (1.0 + time_factor);
         if score > best_score {
             best_score = score;
-            selected = token;
+            selected = info;
         }
     }
 
-    Some(selected)
+    Some(selected.clone())
+}
+
+async fn update_imagine(token: &str, f: impl FnOnce(&mut ImagineState)) {
+    get_token_manager()
+        .await
+        .update_token(token, |t| f(&mut t.imagine))
+        .await;
 }
 
 async fn mark_failed(token: &str, reason: &str) {
-    let mut guard = ROTATION_STORE.lock().await;
-    load_rotation_state(&mut guard).await;
-    ensure_usage(&mut guard.state, token).failed = true;
-    save_rotation_state(&guard).await;
+    update_imagine(token, |s| s.mark_failed(reason, now_ms())).await;
     tracing::warn!(
-        "[ImagineNSFW] mark failed {}... reason={}",
-        &token[..token.len().min(12)],
+        "[ImagineNSFW] mark failed {} reason={}",
+        crate::core::redact::fingerprint(token),
         reason
     );
 }
 
-async fn mark_success(token: &str) {
-    let mut guard = ROTATION_STORE.lock().await;
-    load_rotation_state(&mut guard).await;
-    ensure_usage(&mut guard.state, token).failed = false;
-    save_rotation_state(&guard).await;
-}
-
 async fn record_usage(token: &str) {
-    let mut guard = ROTATION_STORE.lock().await;
-    load_rotation_state(&mut guard).await;
-    let usage = ensure_usage(&mut guard.state, token);
-    usage.count += 1;
-    usage.last_used = now_secs();
-    save_rotation_state(&guard).await;
+    update_imagine(token, |s| s.record_use(now_ms())).await;
 }
 
-async fn get_age_verified(token: &str) -> i32 {
-    let mut guard = ROTATION_STORE.lock().await;
-    load_rotation_state(&mut guard).await;
-    get_usage(&guard.state, token).age_verified
-}
-
-async fn set_age_verified(token: &str, verified: i32) {
-    let mut guard = ROTATION_STORE.lock().await;
-    load_rotation_state(&mut guard).await;
-    ensure_usage(&mut guard.state, token).age_verified = verified;
-    save_rotation_state(&guard).await;
+async fn set_age_verified(token: &str) {
+    update_imagine(token, |s| s.age_verified = true).await;
     tracing::info!(
-        "[ImagineNSFW] set age verified {}... -> {}",
-        &token[..token.len().min(12)],
-        verified
+        "[ImagineNSFW] set age verified {}",
+        crate::core::redact::fingerprint(token)
     );
 }
 
-async fn get_sso_tokens() -> Vec<String> {
-    let mgr = get_token_manager().await;
-    let mut guard = mgr.lock().await;
-    guard.reload_if_stale().await;
-
-    // Keep parity with imagine2api key-file behavior: NSFW generator can use all loaded SSO.
-    let mut out = Vec::new();
-    let mut seen = HashSet::new();
-    for pool in guard.pools.values() {
-        for info in pool.list() {
-            let raw = sanitize_token(&info.token);
-            if raw.is_empty() {
-                continue;
-            }
-            if seen.insert(raw.clone()) {
-                out.push(raw);
-            }
-        }
-    }
-    out
-}
-
 fn size_to_aspect_ratio(size: &str) -> &'static str {
     match size {
         "1024x1024" => "1:1",
@@ -389,44 +286,36 @@ fn is_final_image(url: &str, blob_size: usize) -> bool {
     path.ends_with(".jpg") && blob_size > 100_000
 }
 
-async fn verify_age(token: &str) -> bool {
-    let cf_clearance: String = get_config("grok.cf_clearance", String::new()).await;
-    if cf_clearance.trim().is_empty() {
+async fn verify_age(token: &str, timeout_secs: u64) -> bool {
+    let cf_clearance = cf_clearance_for(token).await;
+    if cf_clearance.is_empty() {
         tracing::warn!("[ImagineNSFW] cf_clearance not configured; skip age verify");
         return false;
     }
 
-    let timeout_secs: u64 = get_config("grok.timeout", 120u64).await;
-    let proxy: String = get_config("grok.base_proxy_url", String::new()).await;
-
     let raw = sanitize_token(token);
-    let cookie = format!(
-        "sso={raw}; sso-rw={raw}; cf_clearance={}",
-        cf_clearance.trim()
-    );
+    let cookie = format!("sso={raw}; sso-rw={raw}; cf_clearance={cf_clearance}");
+    let user_agent = user_agent_for(&raw, AGE_VERIFY_USER_AGENT).await;
 
-    let client =
-        match crate::services::grok::wreq_client::build_client(Some(&proxy), timeout_secs).await {
-            Ok(v) => v,
-            Err(err) => {
-                tracing::warn!("[ImagineNSFW] build wreq client failed: {err}");
-                return false;
-            }
-        };
+    let client = match build_client_for_token(&raw, None, timeout_secs, None).await {
+        Ok(v) => v,
+        Err(err) => {
+            tracing::warn!("[ImagineNSFW] build wreq client failed: {err}");
+            return false;
+        }
+    };
 
-    let response = match client
+    let request = client
         .post(AGE_VERIFY_URL)
         .timeout(Duration::from_secs(timeout_secs.max(1)))
-        .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/133.0.0.0 Safari/537.36")
+        .header("User-Agent", user_agent)
         .header("Origin", "https://grok.com")
         .header("Referer", "https://grok.com/")
         .header("Accept", "*/*")
         .header("Cookie", cookie)
         .header("Content-Type", "application/json")
-        .body(r#"{"birthDate":"2001-01-01T16:00:00.000Z"}"#)
-        .send()
-        .await
-    {
+        .body(r#"{"birthDate":"2001-01-01T16:00:00.000Z"}"#);
+    let response = match client.send(request).await {
         Ok(v) => v,
         Err(err) => {
             tracing::warn!("[ImagineNSFW] age verify request failed: {err}");
@@ -512,12 +401,79 @@ async fn save_final_images(progress: GenerationProgress, n: usize) -> (Vec<Strin
     (result_urls, result_b64)
 }
 
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+async fn connect_ws(request: WsRequest, proxy: Option<&str>) -> Result<WsStream, String> {
+    let Some(proxy) = proxy else {
+        return connect_async(request)
+            .await
+            .map(|(ws, _)| ws)
+            .map_err(|e| e.to_string());
+    };
+    let proxy_url = url::Url::parse(proxy).map_err(|e| format!("invalid proxy url: {e}"))?;
+    if proxy_url.scheme() != "http" {
+        return Err(format!(
+            "unsupported websocket proxy scheme: {}",
+            proxy_url.scheme()
+        ));
+    }
+    let proxy_host = proxy_url.host_str().ok_or("proxy url missing host")?;
+    let proxy_port = proxy_url.port_or_known_default().unwrap_or(80);
+    let target_host = request.uri().host().ok_or("websocket url missing host")?;
+    let target_port = request.uri().port_u16().unwrap_or(443);
+
+    let mut stream = TcpStream::connect((proxy_host, proxy_port))
+        .await
+        .map_err(|e| format!("proxy connect failed: {e}"))?;
+    let mut connect = format!(
+        "CONNECT {target_host}:{target_port} HTTP/1.1\r\nHost: {target_host}:{target_port}\r\n"
+    );
+    if !proxy_url.username().is_empty() {
+        let credentials = format!(
+            "{}:{}",
+            proxy_url.username(),
+            proxy_url.password().unwrap_or("")
+        );
+        let encoded = base64::engine::general_purpose::STANDARD.encode(credentials);
+        connect.push_str(&format!("Proxy-Authorization: Basic {encoded}\r\n"));
+    }
+    connect.push_str("\r\n");
+    stream
+        .write_all(connect.as_bytes())
+        .await
+        .map_err(|e| format!("proxy handshake failed: {e}"))?;
+
+    let mut response = Vec::new();
+    let mut buf = [0u8; 512];
+    while !response.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream
+            .read(&mut buf)
+            .await
+            .map_err(|e| format!("proxy handshake failed: {e}"))?;
+        if n == 0 || response.len() > 8192 {
+            return Err("proxy closed during CONNECT".to_string());
+        }
+        response.extend_from_slice(&buf[..n]);
+    }
+    let status_line = String::from_utf8_lossy(&response);
+    let status_line = status_line.lines().next().unwrap_or("");
+    if status_line.split_whitespace().nth(1) != Some("200") {
+        return Err(format!("proxy CONNECT rejected: {status_line}"));
+    }
+
+    client_async_tls(request, stream)
+        .await
+        .map(|(ws, _)| ws)
+        .map_err(|e| e.to_string())
+}
+
 async fn do_generate(
     sso: &str,
     prompt: &str,
     aspect_ratio: &str,
     n: usize,
     enable_nsfw: bool,
+    timeout_secs: u64,
     progress_tx: Option<mpsc::UnboundedSender<ImagineProgressEvent>>,
 ) -> ImagineResult {
     let request_id = uuid::Uuid::new_v4().to_string();
@@ -533,13 +489,20 @@ async fn do_generate(
         }
     };
 
-    if let Ok(val) = format!("sso={raw}; sso-rw={raw}").parse() {
+    let cf_clearance = cf_clearance_for(&raw).await;
+    let cookie = if cf_clearance.is_empty() {
+        format!("sso={raw}; sso-rw={raw}")
+    } else {
+        format!("sso={raw}; sso-rw={raw}; cf_clearance={cf_clearance}")
+    };
+    if let Ok(val) = cookie.parse() {
         request.headers_mut().insert("Cookie", val);
     }
     if let Ok(val) = "https://grok.com".parse() {
         request.headers_mut().insert("Origin", val);
     }
-    if let Ok(val) = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36".parse() {
+    let user_agent = user_agent_for(&raw, WS_USER_AGENT).await;
+    if let Ok(val) = user_agent.parse() {
         request.headers_mut().insert("User-Agent", val);
     }
     if let Ok(val) = "zh-CN,zh;q=0.9,en;q=0.8".parse() {
@@ -553,9 +516,8 @@ async fn do_generate(
         .headers_mut()
         .insert("Pragma", axum::http::HeaderValue::from_static("no-cache"));
 
-    let timeout_secs: u64 = get_config("grok.timeout", 120u64).await;
-
-    let (mut ws, _) = match connect_async(request).await {
+    let proxy = get_token_manager().await.get_binding(&raw).proxy_url;
+    let mut ws = match connect_ws(request, proxy.as_deref()).await {
         Ok(v) => v,
         Err(err) => {
             let err_str = err.to_string();
@@ -731,15 +693,13 @@ async fn do_generate(
 }
 
 pub async fn generate(
+    model: &str,
     prompt: &str,
     size: Option<&str>,
     n: Option<u32>,
     progress_tx: Option<mpsc::UnboundedSender<ImagineProgressEvent>>,
 ) -> ImagineResult {
-    let tokens = get_sso_tokens().await;
-    if tokens.is_empty() {
-        return ImagineResult::failed("no_available_sso", "没有可用的 SSO");
-    }
+    migrate_legacy_state().await;
 
     let default_count: u32 = get_config("grok.imagine_default_image_count", 4u32).await;
     let mut target_n = n.unwrap_or(default_count.max(1));
@@ -754,20 +714,20 @@ pub async fn generate(
     let max_blocked_retries: usize = get_config("grok.imagine_blocked_retry", 3usize).await;
     let max_retries: usize = get_config("grok.imagine_max_retries", 5usize).await.max(1);
     let aspect_ratio = size_to_aspect_ratio(size.unwrap_or("1024x1536"));
+    let timeout_secs: u64 = get_model_config(model, "timeout", 120u64).await;
 
     let mut blocked_retries = 0usize;
     let mut last_error: Option<ImagineResult> = None;
 
     for _attempt in 0..max_retries {
-        let Some(current_sso) = get_next_sso(&tokens, daily_limit).await else {
+        let Some(current) = get_next_sso(daily_limit).await else {
             return last_error
                 .unwrap_or_else(|| ImagineResult::failed("no_available_sso", "没有可用的 SSO"));
         };
+        let current_sso = sanitize_token(&current.token);
 
-        if get_age_verified(&current_sso).await == 0 {
-            if verify_age(&current_sso).await {
-                set_age_verified(&current_sso, 1).await;
-            }
+        if !current.imagine.age_verified && verify_age(&current_sso, timeout_secs).await {
+            set_age_verified(&current_sso).await;
         }
 
         let result = do_generate(
@@ -776,12 +736,12 @@ pub async fn generate(
             aspect_ratio,
             target_n as usize,
             true,
+            timeout_secs,
             progress_tx.clone(),
         )
         .await;
 
         if result.success {
-            mark_success(&current_sso).await;
             record_usage(&current_sso).await;
             return result;
         }
@@ -1,35 +1,119 @@
 use std::pin::Pin;
-use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use futures::Stream;
 use serde_json::Value as JsonValue;
-use tokio::sync::Semaphore;
+use tokio::sync::{Semaphore, SemaphorePermit};
 
-use crate::core::config::get_config;
+use crate::core::config::{get_config, get_model_config, on_change};
 use crate::core::exceptions::ApiError;
 use crate::services::grok::assets::UploadService;
 use crate::services::grok::chat::MessageExtractor;
 use crate::services::grok::model::ModelService;
 use crate::services::grok::statsig::StatsigService;
 use crate::services::grok::wreq_client::{
-    apply_headers, body_preview, build_client, line_stream_from_response,
+    apply_browser_headers, apply_headers, body_preview, build_client_for_token, cf_clearance_for,
+    line_stream_from_response,
 };
-use crate::services::token::TokenService;
+use crate::services::token::{TokenRoute, TokenService};
 
 const CREATE_POST_API: &str = "https://grok.com/rest/media/post/create";
 const CHAT_API: &str = "https://grok.com/rest/app-chat/conversations/new";
 
-static MEDIA_SEM: once_cell::sync::Lazy<Arc<Semaphore>> =
-    once_cell::sync::Lazy::new(|| Arc::new(Semaphore::new(50)));
+const DEFAULT_MEDIA_CONCURRENCY: usize = 50;
+
+static MEDIA_SEM: once_cell::sync::Lazy<MediaLimiter> =
+    once_cell::sync::Lazy::new(|| MediaLimiter::new(DEFAULT_MEDIA_CONCURRENCY));
+
+/// Video generation slots, resized when `performance.media_max_concurrent` changes.
+struct MediaLimiter {
+    sem: Semaphore,
+    sizes: std::sync::Mutex<MediaSizes>,
+}
+
+/// `issued` counts permits that exist, free or held; it only exceeds `target`
+/// while running jobs still hold permits from before a shrink.
+struct MediaSizes {
+    target: usize,
+    issued: usize,
+}
+
+struct MediaPermit {
+    permit: Option<SemaphorePermit<'static>>,
+}
+
+impl Drop for MediaPermit {
+    fn drop(&mut self) {
+        let Some(permit) = self.permit.take() else {
+            return;
+        };
+        let mut sizes = MEDIA_SEM.sizes.lock().unwrap();
+        if sizes.issued > sizes.target {
+            sizes.issued -= 1;
+            permit.forget();
+        } else {
+            // Released under the lock so a concurrent `resize` sees it as free.
+            drop(permit);
+        }
+    }
+}
+
+impl MediaLimiter {
+    fn new(size: usize) -> Self {
+        Self {
+            sem: Semaphore::new(size),
+            sizes: std::sync::Mutex::new(MediaSizes {
+                target: size,
+                issued: size,
+            }),
+        }
+    }
+
+    fn resize(&self, size: usize) {
+        let size = size.max(1);
+        let mut sizes = self.sizes.lock().unwrap();
+        sizes.target = size;
+        if size > sizes.issued {
+            self.sem.add_permits(size - sizes.issued);
+            sizes.issued = size;
+        } else if size < sizes.issued {
+            // Free permits go now; held ones are retired by `MediaPermit` on release.
+            sizes.issued -= self.sem.forget_permits(sizes.issued - size);
+        }
+    }
+}
+
+async fn acquire_media_slot() -> MediaPermit {
+    let permit = MEDIA_SEM.sem.acquire().await.unwrap();
+    MediaPermit {
+        permit: Some(permit),
+    }
+}
+
+/// Sizes the media semaphore from config and follows later changes.
+pub fn start_media_limit_watcher() {
+    on_change(&["performance.media_max_concurrent"], || async {
+        let size: usize = get_config(
+            "performance.media_max_concurrent",
+            DEFAULT_MEDIA_CONCURRENCY,
+        )
+        .await;
+        MEDIA_SEM.resize(size);
+    });
+}
 
 pub type LineStream = Pin<Box<dyn Stream<Item = String> + Send>>;
 
-pub struct VideoService;
+pub struct VideoService {
+    /// Public model id, used to look up per-model config.
+    model: String,
+}
 
 impl VideoService {
-    pub async fn new() -> Self {
-        Self
+    pub async fn new(model: &str) -> Self {
+        Self {
+            model: model.to_string(),
+        }
     }
 
     async fn build_headers(&self, token: &str, referer: &str) -> reqwest::header::HeaderMap {
@@ -61,7 +145,7 @@ impl VideoService {
         headers.insert("Sec-Fetch-Dest", "empty".parse().unwrap());
         headers.insert("Sec-Fetch-Mode", "cors".parse().unwrap());
         headers.insert("Sec-Fetch-Site", "same-origin".parse().unwrap());
-        headers.insert("User-Agent", "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/136.0.0.0 Safari/537.36".parse().unwrap());
+        apply_browser_headers(&mut headers, token).await;
         let statsig = StatsigService::gen_id().await;
         headers.insert("x-statsig-id", statsig.parse().unwrap());
         headers.insert(
@@ -69,7 +153,7 @@ impl VideoService {
             uuid::Uuid::new_v4().to_string().parse().unwrap(),
         );
         let raw = token.strip_prefix("sso=").unwrap_or(token);
-        let cf: String = get_config("grok.cf_clearance", String::new()).await;
+        let cf = cf_clearance_for(token).await;
         let cookie = if cf.is_empty() {
             format!("sso={raw}")
         } else {
@@ -83,7 +167,7 @@ impl VideoService {
         let headers = self.build_headers(token, "https://grok.com/imagine").await;
         let payload = serde_json::json!({"mediaType": "MEDIA_POST_TYPE_VIDEO", "prompt": prompt});
         let value = self
-            .wreq_json(CREATE_POST_API, headers, &payload, 30)
+            .wreq_json(token, CREATE_POST_API, headers, &payload, 30)
             .await?;
         Ok(value
             .get("post")
@@ -98,7 +182,7 @@ impl VideoService {
         let payload =
             serde_json::json!({"mediaType": "MEDIA_POST_TYPE_IMAGE", "mediaUrl": image_url});
         let value = self
-            .wreq_json(CREATE_POST_API, headers, &payload, 30)
+            .wreq_json(token, CREATE_POST_API, headers, &payload, 30)
             .await?;
         Ok(value
             .get("post")
@@ -151,7 +235,7 @@ impl VideoService {
         resolution: &str,
         preset: &str,
     ) -> Result<LineStream, ApiError> {
-        let _permit = MEDIA_SEM.clone().acquire_owned().await.unwrap();
+        let _permit = acquire_media_slot().await;
         let post_id = self.create_post(token, prompt).await?;
         let headers = self.build_headers(token, "https://grok.com/imagine").await;
         let payload = self
@@ -164,8 +248,9 @@ impl VideoService {
                 preset,
             )
             .await;
-        let timeout: u64 = get_config("grok.timeout", 300u64).await;
-        self.wreq_stream(CHAT_API, headers, &payload, timeout).await
+        let timeout: u64 = get_model_config(&self.model, "timeout", 300u64).await;
+        self.wreq_stream(token, CHAT_API, headers, &payload, timeout)
+            .await
     }
 
     async fn generate_from_image(
@@ -178,7 +263,7 @@ impl VideoService {
         resolution: &str,
         preset: &str,
     ) -> Result<LineStream, ApiError> {
-        let _permit = MEDIA_SEM.clone().acquire_owned().await.unwrap();
+        let _permit = acquire_media_slot().await;
         let post_id = self.create_image_post(token, image_url).await?;
         let headers = self.build_headers(token, "https://grok.com/imagine").await;
         let payload = self
@@ -191,23 +276,25 @@ impl VideoService {
                 preset,
             )
             .await;
-        let timeout: u64 = get_config("grok.timeout", 300u64).await;
-        self.wreq_stream(CHAT_API, headers, &payload, timeout).await
+        let timeout: u64 = get_model_config(&self.model, "timeout", 300u64).await;
+        self.wreq_stream(token, CHAT_API, headers, &payload, timeout)
+            .await
     }
 
     async fn wreq_json(
         &self,
+        token: &str,
         url: &str,
         headers: reqwest::header::HeaderMap,
         payload: &JsonValue,
         timeout: u64,
     ) -> Result<JsonValue, ApiError> {
-        let proxy: String = get_config("grok.base_proxy_url", String::new()).await;
-        let client = build_client(Some(&proxy), timeout).await?;
-        let response = apply_headers(client.post(url), &headers)
+        let client = build_client_for_token(token, None, timeout, None).await?;
+        let request = apply_headers(client.post(url), &headers)
             .timeout(Duration::from_secs(timeout.max(1)))
-            .body(payload.to_string())
-            .send()
+            .body(payload.to_string());
+        let response = client
+            .send(request)
             .await
             .map_err(|e| ApiError::upstream(format!("Media request failed: {e}")))?;
 
@@ -227,7 +314,8 @@ impl VideoService {
             let preview = body_preview(&body, 220);
             return Err(ApiError::upstream(format!(
                 "Media request failed: {status}; content-type: {content_type}; body: {preview}"
-            )));
+            ))
+            .with_upstream_status(status));
         }
 
         serde_json::from_str(&body).map_err(|e| {
@@ -240,17 +328,18 @@ impl VideoService {
 
     async fn wreq_stream(
         &self,
+        token: &str,
         url: &str,
         headers: reqwest::header::HeaderMap,
         payload: &JsonValue,
         timeout: u64,
     ) -> Result<LineStream, ApiError> {
-        let proxy: String = get_config("grok.base_proxy_url", String::new()).await;
-        let client = build_client(Some(&proxy), timeout).await?;
-        let response = apply_headers(client.post(url), &headers)
+        let client = build_client_for_token(token, None, timeout, None).await?;
+        let request = apply_headers(client.post(url), &headers)
             .timeout(Duration::from_secs(timeout.max(1)))
-            .body(payload.to_string())
-            .send()
+            .body(payload.to_string());
+        let response = client
+            .send(request)
             .await
             .map_err(|e| ApiError::upstream(format!("Media request failed: {e}")))?;
 
@@ -274,7 +363,8 @@ impl VideoService {
             }
             return Err(ApiError::upstream(format!(
                 "Media request failed: {status_code}; content-type: {content_type}; body: {preview}"
-            )));
+            ))
+            .with_upstream_status(status_code));
         }
 
         Ok(line_stream_from_response(response))
@@ -289,8 +379,9 @@ impl VideoService {
         video_length: i32,
         resolution: &str,
         preset: &str,
+        route: &TokenRoute,
     ) -> Result<VideoResult, ApiError> {
-        let token = TokenService::get_token_for_model(model).await?;
+        let token = TokenService::get_token_for_model(model, route).await?;
         let think = match thinking.as_deref() {
             Some("enabled") => Some(true),
             Some("disabled") => Some(false),
@@ -312,10 +403,14 @@ impl VideoService {
             }
         }
 
-        let service = VideoService::new().await;
-        let is_stream = stream.unwrap_or(get_config("grok.stream", true).await);
+        let service = VideoService::new(model).await;
+        let is_stream = match stream {
+            Some(stream) => stream,
+            None => get_model_config(model, "stream", true).await,
+        };
 
-        let line_stream = if let Some(url) = image_url {
+        let started_at = Instant::now();
+        let result = if let Some(url) = image_url {
             service
                 .generate_from_image(
                     &token,
@@ -326,7 +421,7 @@ impl VideoService {
                     resolution,
                     preset,
                 )
-                .await?
+                .await
         } else {
             service
                 .generate(
@@ -337,8 +432,16 @@ impl VideoService {
                     resolution,
                     preset,
                 )
-                .await?
+                .await
+        };
+        let line_stream = match result {
+            Ok(line_stream) => line_stream,
+            Err(err) => {
+                TokenService::record_error(&token, &err).await;
+                return Err(err);
+            }
         };
+        TokenService::record_ttfb(&token, started_at.elapsed()).await;
 
         Ok(VideoResult::Stream {
             stream: line_stream,
@@ -346,6 +449,7 @@ impl VideoService {
             model: model.to_string(),
             think,
             is_stream,
+            started_at,
         })
     }
 }
@@ -357,6 +461,7 @@ pub enum VideoResult {
         model: String,
         think: Option<bool>,
         is_stream: bool,
+        started_at: Instant,
     },
     Json(JsonValue),
 }
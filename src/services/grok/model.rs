@@ -7,6 +7,15 @@ pub enum Tier {
     Super,
 }
 
+impl Tier {
+    pub fn pool_name(&self) -> &'static str {
+        match self {
+            Tier::Basic => "ssoBasic",
+            Tier::Super => "ssoSuper",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum Cost {
@@ -118,11 +127,14 @@ impl ModelService {
     }
 
     pub fn pool_for_model(model_id: &str) -> String {
-        if let Some(m) = Self::get(model_id) {
-            if m.tier == Tier::Super {
-                return "ssoSuper".to_string();
-            }
-        }
-        "ssoBasic".to_string()
+        Self::get(model_id)
+            .map(|m| m.tier)
+            .unwrap_or(Tier::Basic)
+            .pool_name()
+            .to_string()
+    }
+
+    pub fn tier_probe_model() -> Option<ModelInfo> {
+        Self::list().into_iter().find(|m| m.tier == Tier::Super)
     }
 }
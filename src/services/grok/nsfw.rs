@@ -5,14 +5,18 @@ use urlencoding::decode;
 use crate::core::config::get_config;
 use crate::core::exceptions::ApiError;
 use crate::services::grok::grpc_web::{
-    encode_grpc_web_payload, get_grpc_status, parse_grpc_web_response,
+    ProtoValue, decode_proto_fields, encode_grpc_web_payload, get_grpc_status,
+    parse_grpc_web_response,
 };
 use crate::services::grok::wreq_client::{
-    apply_headers, body_preview as body_preview_text, build_client_with_emulation,
+    apply_headers, body_preview as body_preview_text, build_client_for_token, cf_clearance_for,
+    user_agent_for,
 };
 
 const NSFW_API: &str = "https://grok.com/auth_mgmt.AuthManagement/UpdateUserFeatureControls";
+const NSFW_CONTROLS_API: &str = "https://grok.com/auth_mgmt.AuthManagement/GetUserFeatureControls";
 const AGE_VERIFY_API: &str = "https://grok.com/rest/auth/set-birth-date";
+const AGE_VERIFY_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/133.0.0.0 Safari/537.36";
 const NSFW_FALLBACK_EMULATION: &str = "chrome_116";
 const NSFW_FEATURES_PATH: &str = "features";
 const NSFW_FEATURES_ENABLED_PATH: &str = "features.enabled";
@@ -29,6 +33,18 @@ pub struct NsfwResult {
     pub error: Option<String>,
 }
 
+impl NsfwResult {
+    fn from_error(err: ApiError) -> Self {
+        Self {
+            success: false,
+            http_status: 0,
+            grpc_status: None,
+            grpc_message: None,
+            error: Some(err.to_string()),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct NsfwService;
 
@@ -46,12 +62,12 @@ impl NsfwService {
         );
         headers.insert("origin", "https://grok.com".parse().unwrap());
         headers.insert("referer", "https://grok.com/".parse().unwrap());
-        headers.insert(
-            "user-agent",
-            "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36"
-                .parse()
-                .unwrap(),
-        );
+        let user_agent = user_agent_for(
+            token,
+            "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36",
+        )
+        .await;
+        headers.insert("user-agent", user_agent.parse().unwrap());
         headers.insert("x-grpc-web", "1".parse().unwrap());
         headers.insert("x-user-agent", "connect-es/2.1.1".parse().unwrap());
         let cookie = build_cookie(token).await;
@@ -102,14 +118,67 @@ impl NsfwService {
     async fn try_enable(&self, token: &str, emulation_override: Option<&str>) -> NsfwResult {
         match self.enable_via_wreq(token, emulation_override).await {
             Ok(result) => result,
-            Err(err) => NsfwResult {
-                success: false,
-                http_status: 0,
-                grpc_status: None,
-                grpc_message: None,
-                error: Some(err.to_string()),
-            },
+            Err(err) => NsfwResult::from_error(err),
+        }
+    }
+
+    pub async fn verify_age(&self, token: &str) -> NsfwResult {
+        match self.verify_age_via_rest(token, None).await {
+            Ok(result) => result,
+            Err(err) => NsfwResult::from_error(err),
+        }
+    }
+
+    /// Reads the account feature controls back and reports whether the NSFW
+    /// flag (`features.enabled`) is set upstream.
+    pub async fn verify(&self, token: &str) -> Result<bool, ApiError> {
+        let headers = self.build_headers(token).await;
+        let timeout: u64 = get_config("grok.timeout", 30u64).await;
+        let client = build_client_for_token(token, None, timeout, None).await?;
+        let request = apply_headers(client.post(NSFW_CONTROLS_API), &headers)
+            .timeout(Duration::from_secs(timeout.max(1)))
+            .body(encode_grpc_web_payload(&[]));
+        let response = client
+            .send(request)
+            .await
+            .map_err(|e| ApiError::upstream(format!("NSFW verify request failed: {e}")))?;
+
+        let status = response.status().as_u16();
+        let content_type = response
+            .headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("<unknown>")
+            .to_string();
+        let grpc_status_header = response
+            .headers()
+            .get("grpc-status")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|e| ApiError::upstream(format!("NSFW verify read failed: {e}")))?;
+
+        if status != 200 {
+            return Err(ApiError::upstream(format!(
+                "NSFW verify HTTP {status}; body: {}",
+                body_preview(&bytes)
+            )));
+        }
+
+        let (messages, mut trailers) = parse_grpc_web_response(&bytes, Some(&content_type), None);
+        if let Some(code) = grpc_status_header {
+            trailers.entry("grpc-status".to_string()).or_insert(code);
         }
+        let grpc = get_grpc_status(&trailers);
+        if grpc.code != -1 && !grpc.ok() {
+            return Err(ApiError::upstream(format!(
+                "NSFW verify gRPC error: code={}, message={}",
+                grpc.code, grpc.message
+            )));
+        }
+        Ok(messages.first().is_some_and(|m| features_enabled(m)))
     }
 
     async fn enable_via_wreq(
@@ -214,13 +283,12 @@ impl NsfwService {
         let headers = self.build_headers(token).await;
         let payload = encode_grpc_web_payload(proto_payload);
         let timeout: u64 = get_config("grok.timeout", 30u64).await;
-        let proxy: String = get_config("grok.base_proxy_url", String::new()).await;
-
-        let client = build_client_with_emulation(Some(&proxy), timeout, emulation_override).await?;
-        let response = apply_headers(client.post(NSFW_API), &headers)
+        let client = build_client_for_token(token, None, timeout, emulation_override).await?;
+        let request = apply_headers(client.post(NSFW_API), &headers)
             .timeout(Duration::from_secs(timeout.max(1)))
-            .body(payload)
-            .send()
+            .body(payload);
+        let response = client
+            .send(request)
             .await
             .map_err(|e| ApiError::upstream(format!("NSFW request failed: {e}")))?;
 
@@ -317,24 +385,22 @@ impl NsfwService {
         emulation_override: Option<&str>,
     ) -> Result<NsfwResult, ApiError> {
         let timeout: u64 = get_config("grok.timeout", 30u64).await;
-        let proxy: String = get_config("grok.base_proxy_url", String::new()).await;
         let cookie = build_cookie(token).await;
+        let user_agent = user_agent_for(token, AGE_VERIFY_USER_AGENT).await;
 
-        let client = build_client_with_emulation(Some(&proxy), timeout, emulation_override).await?;
-        let response = client
+        let client = build_client_for_token(token, None, timeout, emulation_override).await?;
+        let request = client
             .post(AGE_VERIFY_API)
             .timeout(Duration::from_secs(timeout.max(1)))
-            .header(
-                "User-Agent",
-                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/133.0.0.0 Safari/537.36",
-            )
+            .header("User-Agent", user_agent)
             .header("Origin", "https://grok.com")
             .header("Referer", "https://grok.com/")
             .header("Accept", "*/*")
             .header("Cookie", cookie)
             .header("Content-Type", "application/json")
-            .body(r#"{"birthDate":"2001-01-01T16:00:00.000Z"}"#)
-            .send()
+            .body(r#"{"birthDate":"2001-01-01T16:00:00.000Z"}"#);
+        let response = client
+            .send(request)
             .await
             .map_err(|e| ApiError::upstream(format!("NSFW age verify request failed: {e}")))?;
 
@@ -379,11 +445,11 @@ impl NsfwService {
 
 async fn build_cookie(token: &str) -> String {
     let raw = token.strip_prefix("sso=").unwrap_or(token);
-    let cf: String = get_config("grok.cf_clearance", String::new()).await;
-    if cf.trim().is_empty() {
+    let cf = cf_clearance_for(token).await;
+    if cf.is_empty() {
         format!("sso={raw}; sso-rw={raw}")
     } else {
-        format!("sso={raw}; sso-rw={raw}; cf_clearance={}", cf.trim())
+        format!("sso={raw}; sso-rw={raw}; cf_clearance={cf}")
     }
 }
 
@@ -413,8 +479,26 @@ fn build_proto_payload(mask_field_number: u8, mask_path: &str) -> Vec<u8> {
     out
 }
 
+/// `UserFeatureControls { features (1) { enabled (1) } }`, mirroring the
+/// shape written by `build_proto_payload`.
+fn features_enabled(message: &[u8]) -> bool {
+    decode_proto_fields(message)
+        .into_iter()
+        .filter_map(|(field, value)| match (field, value) {
+            (1, ProtoValue::Bytes(features)) => Some(features),
+            _ => None,
+        })
+        .any(|features| {
+            decode_proto_fields(&features)
+                .into_iter()
+                .any(|(field, value)| {
+                    field == 1 && matches!(value, ProtoValue::Varint(v) if v != 0)
+                })
+        })
+}
+
 fn body_preview(bytes: &[u8]) -> String {
-    String::from_utf8_lossy(bytes)
+    crate::core::redact::redact(&String::from_utf8_lossy(bytes))
         .chars()
         .take(220)
         .flat_map(|ch| ch.escape_default())
@@ -7,7 +7,7 @@ use bytes::Bytes;
 use futures::Stream;
 use serde_json::Value as JsonValue;
 
-use crate::core::config::get_config;
+use crate::core::config::{get_config, get_model_config};
 use crate::services::grok::assets::DownloadService;
 
 fn now_ts() -> i64 {
@@ -101,9 +101,10 @@ impl StreamProcessor {
     pub async fn new(model: &str, token: &str, think: Option<bool>) -> Self {
         let show = match think {
             Some(v) => v,
-            None => get_config("grok.thinking", false).await,
+            None => get_model_config(model, "thinking", false).await,
         };
-        let filter_tags: Vec<String> = get_config("grok.filter_tags", Vec::<String>::new()).await;
+        let filter_tags: Vec<String> =
+            get_model_config(model, "filter_tags", Vec::<String>::new()).await;
         let image_format: String = get_config("app.image_format", "url".to_string()).await;
         Self {
             base: BaseProcessor::new(model, token).await,
@@ -344,7 +345,7 @@ impl VideoStreamProcessor {
     pub async fn new(model: &str, token: &str, think: Option<bool>) -> Self {
         let show = match think {
             Some(v) => v,
-            None => get_config("grok.thinking", false).await,
+            None => get_model_config(model, "thinking", false).await,
         };
         Self {
             base: BaseProcessor::new(model, token).await,
@@ -1,6 +1,6 @@
 use std::future::Future;
 
-use crate::core::config::get_config;
+use crate::core::config::get_model_config;
 
 #[derive(Default)]
 pub struct RetryContext {
@@ -10,10 +10,10 @@ pub struct RetryContext {
 }
 
 impl RetryContext {
-    pub async fn new() -> Self {
-        let max_retry: u32 = get_config("grok.max_retry", 1u32).await;
+    pub async fn new(model: &str) -> Self {
+        let max_retry: u32 = get_model_config(model, "max_retry", 1u32).await;
         let retry_codes: Vec<u16> =
-            get_config("grok.retry_status_codes", vec![401u16, 429u16, 403u16]).await;
+            get_model_config(model, "retry_status_codes", vec![401u16, 429u16, 403u16]).await;
         Self {
             attempt: 0,
             max_retry,
@@ -27,6 +27,7 @@ impl RetryContext {
 }
 
 pub async fn retry_on_status<F, Fut, T, E>(
+    model: &str,
     func: F,
     extract_status: impl Fn(&E) -> Option<u16>,
 ) -> Result<T, E>
@@ -34,7 +35,7 @@ where
     F: Fn() -> Fut,
     Fut: Future<Output = Result<T, E>>,
 {
-    let mut ctx = RetryContext::new().await;
+    let mut ctx = RetryContext::new(model).await;
     loop {
         match func().await {
             Ok(v) => return Ok(v),
@@ -4,8 +4,11 @@ use serde_json::Value as JsonValue;
 
 use crate::core::config::get_config;
 use crate::core::exceptions::ApiError;
+use crate::services::grok::model::{ModelService, Tier};
 use crate::services::grok::statsig::StatsigService;
-use crate::services::grok::wreq_client::{apply_headers, build_client_with_emulation};
+use crate::services::grok::wreq_client::{
+    apply_browser_headers, apply_headers, build_client_for_token, cf_clearance_for,
+};
 
 const LIMITS_API: &str = "https://grok.com/rest/rate-limits";
 
@@ -45,12 +48,7 @@ impl UsageService {
         headers.insert("Sec-Fetch-Dest", "empty".parse().unwrap());
         headers.insert("Sec-Fetch-Mode", "cors".parse().unwrap());
         headers.insert("Sec-Fetch-Site", "same-origin".parse().unwrap());
-        headers.insert(
-            "User-Agent",
-            "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/136.0.0.0 Safari/537.36"
-                .parse()
-                .unwrap(),
-        );
+        apply_browser_headers(&mut headers, token).await;
         let statsig = StatsigService::gen_id().await;
         headers.insert("x-statsig-id", statsig.parse().unwrap());
         headers.insert(
@@ -58,7 +56,7 @@ impl UsageService {
             uuid::Uuid::new_v4().to_string().parse().unwrap(),
         );
         let raw = token.strip_prefix("sso=").unwrap_or(token);
-        let cf: String = get_config("grok.cf_clearance", String::new()).await;
+        let cf = cf_clearance_for(token).await;
         let cookie = if cf.is_empty() {
             format!("sso={raw}")
         } else {
@@ -75,7 +73,6 @@ impl UsageService {
             "modelName": model_name,
         });
         let timeout: u64 = get_config("grok.timeout", 10u64).await;
-        let proxy: String = get_config("grok.base_proxy_url", String::new()).await;
         let usage_emulation: String = get_config("grok.wreq_emulation_usage", String::new()).await;
         let emulation_override = if usage_emulation.trim().is_empty() {
             None
@@ -83,11 +80,12 @@ impl UsageService {
             Some(usage_emulation.trim())
         };
 
-        let client = build_client_with_emulation(Some(&proxy), timeout, emulation_override).await?;
-        let response = apply_headers(client.post(LIMITS_API), &headers)
+        let client = build_client_for_token(token, None, timeout, emulation_override).await?;
+        let request = apply_headers(client.post(LIMITS_API), &headers)
             .timeout(Duration::from_secs(timeout.max(1)))
-            .body(payload.to_string())
-            .send()
+            .body(payload.to_string());
+        let response = client
+            .send(request)
             .await
             .map_err(|e| ApiError::upstream(format!("Usage request failed: {e}")))?;
 
@@ -109,7 +107,8 @@ impl UsageService {
             let preview = body_preview(&body_text);
             return Err(ApiError::upstream(format!(
                 "Failed to get usage stats: {status}; content-type: {content_type}; body: {preview}"
-            )));
+            ))
+            .with_upstream_status(status));
         }
 
         let normalized = normalize_json_text(&body_text);
@@ -135,6 +134,54 @@ impl UsageService {
     pub async fn get(&self, token: &str, model_name: &str) -> Result<JsonValue, ApiError> {
         self.get_via_wreq(token, model_name).await
     }
+
+    pub async fn detect_tier(&self, token: &str) -> Result<Tier, ApiError> {
+        let probe = ModelService::tier_probe_model()
+            .ok_or_else(|| ApiError::server("No Super-tier model available for tier probe"))?;
+        let result = self.get(token, &probe.model_id).await?;
+        Ok(tier_from_limits(&result))
+    }
+}
+
+fn tier_from_limits(result: &JsonValue) -> Tier {
+    let allowance = [
+        "totalTokens",
+        "totalQueries",
+        "remainingTokens",
+        "remainingQueries",
+    ]
+    .iter()
+    .filter_map(|key| result.get(*key).and_then(|v| v.as_i64()))
+    .max()
+    .unwrap_or(0);
+    if allowance > 0 {
+        Tier::Super
+    } else {
+        Tier::Basic
+    }
+}
+
+pub fn recover_after_secs(result: &JsonValue) -> Option<i64> {
+    let wait = [
+        result.get("waitTimeSeconds"),
+        result
+            .get("lowEffortRateLimits")
+            .and_then(|v| v.get("waitTimeSeconds")),
+        result
+            .get("highEffortRateLimits")
+            .and_then(|v| v.get("waitTimeSeconds")),
+    ]
+    .into_iter()
+    .flatten()
+    .filter_map(|v| v.as_i64())
+    .max()
+    .filter(|secs| *secs > 0);
+    wait.or_else(|| {
+        result
+            .get("windowSizeSeconds")
+            .and_then(|v| v.as_i64())
+            .filter(|secs| *secs > 0)
+    })
 }
 
 fn normalize_json_text(raw: &str) -> String {
@@ -154,7 +201,8 @@ fn normalize_json_text(raw: &str) -> String {
 }
 
 fn body_preview(text: &str) -> String {
-    text.chars()
+    crate::core::redact::redact(text)
+        .chars()
         .take(200)
         .flat_map(|ch| ch.escape_default())
         .collect::<String>()
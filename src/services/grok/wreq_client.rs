@@ -8,10 +8,8 @@ use wreq_util::Emulation;
 
 use crate::core::config::get_config;
 use crate::core::exceptions::ApiError;
-
-pub async fn build_client(proxy: Option<&str>, timeout_secs: u64) -> Result<Client, ApiError> {
-    build_client_with_emulation(proxy, timeout_secs, None).await
-}
+use crate::services::proxy::{ProxyPurpose, record_request, select_proxy};
+use crate::services::token::get_token_manager;
 
 pub async fn build_client_with_emulation(
     proxy: Option<&str>,
@@ -44,116 +42,256 @@ pub async fn build_client_with_emulation(
         .map_err(|e| ApiError::upstream(format!("Build wreq client failed: {e}")))
 }
 
-fn parse_emulation(raw: &str) -> Emulation {
-    let text = raw.trim().to_ascii_lowercase();
-    if text.is_empty() {
-        return Emulation::Chrome136;
+/// A client together with the proxy it connects through, so request
+/// outcomes can be reported back to the proxy pool.
+pub struct TokenClient {
+    client: Client,
+    proxy: Option<String>,
+}
+
+impl std::ops::Deref for TokenClient {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        &self.client
     }
+}
 
-    let normalized = text.replace('-', "").replace('_', "");
-
-    if normalized.starts_with("chrome") {
-        let version: String = normalized
-            .chars()
-            .skip_while(|c| !c.is_ascii_digit())
-            .take_while(|c| c.is_ascii_digit())
-            .collect();
-        return match version.as_str() {
-            "100" => Emulation::Chrome100,
-            "101" => Emulation::Chrome101,
-            "104" => Emulation::Chrome104,
-            "105" => Emulation::Chrome105,
-            "106" => Emulation::Chrome106,
-            "107" => Emulation::Chrome107,
-            "108" => Emulation::Chrome108,
-            "109" => Emulation::Chrome109,
-            "110" => Emulation::Chrome110,
-            "114" => Emulation::Chrome114,
-            "116" => Emulation::Chrome116,
-            "117" => Emulation::Chrome117,
-            "118" => Emulation::Chrome118,
-            "119" => Emulation::Chrome119,
-            "120" => Emulation::Chrome120,
-            "123" => Emulation::Chrome123,
-            "124" => Emulation::Chrome124,
-            "126" => Emulation::Chrome126,
-            "127" => Emulation::Chrome127,
-            "128" => Emulation::Chrome128,
-            "129" => Emulation::Chrome129,
-            "130" => Emulation::Chrome130,
-            "131" => Emulation::Chrome131,
-            "132" => Emulation::Chrome132,
-            "133" => Emulation::Chrome133,
-            "134" => Emulation::Chrome134,
-            "135" => Emulation::Chrome135,
-            "136" => Emulation::Chrome136,
-            "137" => Emulation::Chrome137,
-            "138" => Emulation::Chrome138,
-            "139" => Emulation::Chrome139,
-            "140" => Emulation::Chrome140,
-            "141" => Emulation::Chrome141,
-            "142" => Emulation::Chrome142,
-            "143" => Emulation::Chrome143,
-            _ => Emulation::Chrome136,
-        };
+impl TokenClient {
+    pub async fn send(&self, request: RequestBuilder) -> wreq::Result<wreq::Response> {
+        let result = request.send().await;
+        if let Some(proxy) = self.proxy.as_deref().filter(|p| !p.trim().is_empty()) {
+            let error = match &result {
+                Err(err) if is_transport_error(err) => Some(err.to_string()),
+                Err(_) => return result,
+                Ok(_) => None,
+            };
+            record_request(proxy.trim(), error).await;
+        }
+        result
     }
+}
 
-    if normalized.starts_with("edge") {
-        let version: String = normalized
-            .chars()
-            .skip_while(|c| !c.is_ascii_digit())
-            .take_while(|c| c.is_ascii_digit())
-            .collect();
-        return match version.as_str() {
-            "101" => Emulation::Edge101,
-            "122" => Emulation::Edge122,
-            "127" => Emulation::Edge127,
-            "131" => Emulation::Edge131,
-            "134" => Emulation::Edge134,
-            "135" => Emulation::Edge135,
-            "136" => Emulation::Edge136,
-            "137" => Emulation::Edge137,
-            "138" => Emulation::Edge138,
-            "139" => Emulation::Edge139,
-            "140" => Emulation::Edge140,
-            "141" => Emulation::Edge141,
-            "142" => Emulation::Edge142,
-            _ => Emulation::Edge136,
-        };
+fn is_transport_error(err: &wreq::Error) -> bool {
+    err.is_connect() || err.is_proxy_connect() || err.is_connection_reset() || err.is_timeout()
+}
+
+/// Uses the token's bound proxy, then `proxy`, then a base proxy from the
+/// pool; `Some("")` connects directly.
+pub async fn build_client_for_token(
+    token: &str,
+    proxy: Option<&str>,
+    timeout_secs: u64,
+    emulation_override: Option<&str>,
+) -> Result<TokenClient, ApiError> {
+    let binding = get_token_manager().await.get_binding(token);
+    let proxy = match binding.proxy_url.as_deref().or(proxy) {
+        Some(proxy) => Some(proxy.to_string()),
+        None => select_proxy(ProxyPurpose::Base).await,
+    };
+    let client = build_client_with_emulation(
+        proxy.as_deref(),
+        timeout_secs,
+        binding.emulation.as_deref().or(emulation_override),
+    )
+    .await?;
+    Ok(TokenClient { client, proxy })
+}
+
+pub async fn cf_clearance_for(token: &str) -> String {
+    if let Some(cf) = get_token_manager().await.get_binding(token).cf_clearance {
+        return cf;
     }
+    let cf: String = get_config("grok.cf_clearance", String::new()).await;
+    cf.trim().to_string()
+}
 
-    if normalized.starts_with("firefox") {
-        let version: String = normalized
-            .chars()
-            .skip_while(|c| !c.is_ascii_digit())
-            .take_while(|c| c.is_ascii_digit())
-            .collect();
-        return match version.as_str() {
-            "109" => Emulation::Firefox109,
-            "117" => Emulation::Firefox117,
-            "128" => Emulation::Firefox128,
-            "133" => Emulation::Firefox133,
-            "135" => Emulation::Firefox135,
-            "136" => Emulation::Firefox136,
-            "139" => Emulation::Firefox139,
-            "142" => Emulation::Firefox142,
-            "143" => Emulation::Firefox143,
-            "144" => Emulation::Firefox144,
-            "145" => Emulation::Firefox145,
-            "146" => Emulation::Firefox146,
-            _ => Emulation::Firefox136,
-        };
+/// Every supported emulation by config name; the single source for
+/// validation, the config schema and `parse_emulation`.
+const EMULATIONS: &[(&str, Emulation)] = &[
+    ("chrome_100", Emulation::Chrome100),
+    ("chrome_101", Emulation::Chrome101),
+    ("chrome_104", Emulation::Chrome104),
+    ("chrome_105", Emulation::Chrome105),
+    ("chrome_106", Emulation::Chrome106),
+    ("chrome_107", Emulation::Chrome107),
+    ("chrome_108", Emulation::Chrome108),
+    ("chrome_109", Emulation::Chrome109),
+    ("chrome_110", Emulation::Chrome110),
+    ("chrome_114", Emulation::Chrome114),
+    ("chrome_116", Emulation::Chrome116),
+    ("chrome_117", Emulation::Chrome117),
+    ("chrome_118", Emulation::Chrome118),
+    ("chrome_119", Emulation::Chrome119),
+    ("chrome_120", Emulation::Chrome120),
+    ("chrome_123", Emulation::Chrome123),
+    ("chrome_124", Emulation::Chrome124),
+    ("chrome_126", Emulation::Chrome126),
+    ("chrome_127", Emulation::Chrome127),
+    ("chrome_128", Emulation::Chrome128),
+    ("chrome_129", Emulation::Chrome129),
+    ("chrome_130", Emulation::Chrome130),
+    ("chrome_131", Emulation::Chrome131),
+    ("chrome_132", Emulation::Chrome132),
+    ("chrome_133", Emulation::Chrome133),
+    ("chrome_134", Emulation::Chrome134),
+    ("chrome_135", Emulation::Chrome135),
+    ("chrome_136", Emulation::Chrome136),
+    ("chrome_137", Emulation::Chrome137),
+    ("chrome_138", Emulation::Chrome138),
+    ("chrome_139", Emulation::Chrome139),
+    ("chrome_140", Emulation::Chrome140),
+    ("chrome_141", Emulation::Chrome141),
+    ("chrome_142", Emulation::Chrome142),
+    ("chrome_143", Emulation::Chrome143),
+    ("edge_101", Emulation::Edge101),
+    ("edge_122", Emulation::Edge122),
+    ("edge_127", Emulation::Edge127),
+    ("edge_131", Emulation::Edge131),
+    ("edge_134", Emulation::Edge134),
+    ("edge_135", Emulation::Edge135),
+    ("edge_136", Emulation::Edge136),
+    ("edge_137", Emulation::Edge137),
+    ("edge_138", Emulation::Edge138),
+    ("edge_139", Emulation::Edge139),
+    ("edge_140", Emulation::Edge140),
+    ("edge_141", Emulation::Edge141),
+    ("edge_142", Emulation::Edge142),
+    ("firefox_109", Emulation::Firefox109),
+    ("firefox_117", Emulation::Firefox117),
+    ("firefox_128", Emulation::Firefox128),
+    ("firefox_133", Emulation::Firefox133),
+    ("firefox_135", Emulation::Firefox135),
+    ("firefox_136", Emulation::Firefox136),
+    ("firefox_139", Emulation::Firefox139),
+    ("firefox_142", Emulation::Firefox142),
+    ("firefox_143", Emulation::Firefox143),
+    ("firefox_144", Emulation::Firefox144),
+    ("firefox_145", Emulation::Firefox145),
+    ("firefox_146", Emulation::Firefox146),
+    ("safari_15_3", Emulation::Safari15_3),
+    ("safari_15_5", Emulation::Safari15_5),
+    ("safari_16", Emulation::Safari16),
+    ("safari_16_5", Emulation::Safari16_5),
+    ("safari_17_0", Emulation::Safari17_0),
+    ("safari_17_2_1", Emulation::Safari17_2_1),
+    ("safari_17_4_1", Emulation::Safari17_4_1),
+];
+
+pub fn emulation_names() -> impl Iterator<Item = &'static str> {
+    EMULATIONS.iter().map(|(name, _)| *name)
+}
+
+pub fn is_known_emulation(raw: &str) -> bool {
+    canonical_emulation(raw).is_some()
+}
+
+fn lookup_emulation(raw: &str) -> Option<&'static (&'static str, Emulation)> {
+    let normalized = raw.trim().to_ascii_lowercase().replace(['-', '_'], "");
+    EMULATIONS
+        .iter()
+        .find(|(name, _)| name.replace('_', "") == normalized)
+}
+
+fn canonical_emulation(raw: &str) -> Option<&'static str> {
+    lookup_emulation(raw).map(|(name, _)| *name)
+}
+
+pub const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/136.0.0.0 Safari/537.36";
+
+/// User-Agent and `Sec-Ch-Ua` of a known emulation; only Chromium sends the latter.
+fn browser_identity(emulation: &str) -> Option<(String, Option<String>)> {
+    let name = canonical_emulation(emulation)?;
+    let (family, version) = name.split_once('_')?;
+    let major = version.split('_').next().unwrap_or(version);
+    let brands = |brand: &str| {
+        format!("\"{brand}\";v=\"{major}\", \"Chromium\";v=\"{major}\", \"Not(A:Brand\";v=\"24\"")
+    };
+    let chromium = format!(
+        "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/{major}.0.0.0 Safari/537.36"
+    );
+    Some(match family {
+        "chrome" => (chromium, Some(brands("Google Chrome"))),
+        "edge" => (
+            format!("{chromium} Edg/{major}.0.0.0"),
+            Some(brands("Microsoft Edge")),
+        ),
+        "firefox" => (
+            format!(
+                "Mozilla/5.0 (Macintosh; Intel Mac OS X 10.15; rv:{major}.0) Gecko/20100101 Firefox/{major}.0"
+            ),
+            None,
+        ),
+        _ => {
+            let version = match version.replace('_', ".") {
+                v if v.contains('.') => v,
+                v => format!("{v}.0"),
+            };
+            (
+                format!(
+                    "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/{version} Safari/605.1.15"
+                ),
+                None,
+            )
+        }
+    })
+}
+
+async fn bound_identity(token: &str) -> Option<(String, Option<String>)> {
+    let emulation = get_token_manager().await.get_binding(token).emulation?;
+    browser_identity(&emulation)
+}
+
+/// The bound emulation's User-Agent, or `fallback` for unbound tokens.
+pub async fn user_agent_for(token: &str, fallback: &str) -> String {
+    match bound_identity(token).await {
+        Some((user_agent, _)) => user_agent,
+        None => fallback.to_string(),
+    }
+}
+
+/// Rewrites the browser identity headers to match the token's bound
+/// emulation; non-Chromium emulations drop the client hints.
+pub async fn apply_browser_headers(headers: &mut ReqwestHeaderMap, token: &str) {
+    let Some((user_agent, sec_ch_ua)) = bound_identity(token).await else {
+        headers.insert("User-Agent", DEFAULT_USER_AGENT.parse().unwrap());
+        return;
+    };
+    if let Ok(value) = user_agent.parse() {
+        headers.insert("User-Agent", value);
+    }
+    match sec_ch_ua {
+        Some(brands) if headers.contains_key("Sec-Ch-Ua") => {
+            if let Ok(value) = brands.parse() {
+                headers.insert("Sec-Ch-Ua", value);
+            }
+        }
+        Some(_) => {}
+        None => {
+            let hints: Vec<_> = headers
+                .keys()
+                .filter(|name| name.as_str().starts_with("sec-ch-ua"))
+                .cloned()
+                .collect();
+            for name in hints {
+                headers.remove(name);
+            }
+        }
     }
+}
 
-    match normalized.as_str() {
-        "safari153" => Emulation::Safari15_3,
-        "safari155" => Emulation::Safari15_5,
-        "safari16" => Emulation::Safari16,
-        "safari165" => Emulation::Safari16_5,
-        "safari170" => Emulation::Safari17_0,
-        "safari1721" => Emulation::Safari17_2_1,
-        "safari1741" => Emulation::Safari17_4_1,
-        _ => Emulation::Chrome136,
+/// Unknown versions fall back to the family's 136 build, anything else to Chrome 136.
+fn parse_emulation(raw: &str) -> Emulation {
+    if let Some((_, emulation)) = lookup_emulation(raw) {
+        return *emulation;
+    }
+    let text = raw.trim().to_ascii_lowercase();
+    if text.starts_with("edge") {
+        Emulation::Edge136
+    } else if text.starts_with("firefox") {
+        Emulation::Firefox136
+    } else {
+        Emulation::Chrome136
     }
 }
 
@@ -222,7 +360,8 @@ pub fn body_preview_from_bytes(bytes: &[u8], max_chars: usize) -> String {
 }
 
 pub fn body_preview(text: &str, max_chars: usize) -> String {
-    text.chars()
+    crate::core::redact::redact(text)
+        .chars()
         .take(max_chars)
         .flat_map(|ch| ch.escape_default())
         .collect::<String>()
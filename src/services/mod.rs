@@ -1,2 +1,3 @@
 pub mod grok;
+pub mod proxy;
 pub mod token;
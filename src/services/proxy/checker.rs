@@ -0,0 +1,85 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::future::join_all;
+use tokio::task::JoinHandle;
+
+use crate::core::config::get_config;
+use crate::services::grok::wreq_client::build_client_with_emulation;
+use crate::services::proxy::pool::ProxyEntry;
+use crate::services::proxy::{eject_policy, get_proxy_pool};
+
+const DEFAULT_PROBE_URL: &str = "https://grok.com/";
+const MIN_PROBE_INTERVAL_SEC: u64 = 5;
+
+static CHECKER: std::sync::Mutex<Option<JoinHandle<()>>> = std::sync::Mutex::new(None);
+
+pub fn start_proxy_checker() {
+    let mut guard = CHECKER.lock().unwrap_or_else(|e| e.into_inner());
+    if guard.is_some() {
+        return;
+    }
+    *guard = Some(tokio::spawn(async move {
+        loop {
+            let interval: u64 = get_config("proxy.probe_interval_sec", 60u64).await;
+            if interval > 0 {
+                probe_all().await;
+            }
+            let wait = interval.max(MIN_PROBE_INTERVAL_SEC);
+            tokio::time::sleep(Duration::from_secs(wait)).await;
+        }
+    }));
+}
+
+/// Probes every proxy whose back-off has elapsed and returns how many passed.
+pub async fn probe_all() -> usize {
+    let pool = get_proxy_pool().await;
+    let now = chrono::Utc::now().timestamp_millis();
+    let due: Vec<Arc<ProxyEntry>> = pool
+        .entries()
+        .iter()
+        .filter(|e| e.due_for_probe(now))
+        .cloned()
+        .collect();
+    if due.is_empty() {
+        return 0;
+    }
+
+    let probe_url: String = get_config("proxy.probe_url", DEFAULT_PROBE_URL.to_string()).await;
+    let timeout: u64 = get_config("proxy.probe_timeout_sec", 10u64).await;
+    let policy = eject_policy().await;
+
+    let results = join_all(due.iter().map(|e| probe(e.url(), &probe_url, timeout))).await;
+
+    let mut passed = 0;
+    for (entry, result) in due.iter().zip(results) {
+        match result {
+            Ok(latency_ms) => {
+                passed += 1;
+                pool.record_success(entry.url(), Some(latency_ms));
+            }
+            Err(err) => pool.record_failure(entry.url(), err, &policy),
+        }
+    }
+    passed
+}
+
+/// Any HTTP response below 500 counts as reachable: Cloudflare challenges
+/// still prove the proxy forwarded the request.
+async fn probe(proxy: &str, probe_url: &str, timeout: u64) -> Result<u64, String> {
+    let client = build_client_with_emulation(Some(proxy), timeout, None)
+        .await
+        .map_err(|e| e.body.message)?;
+    let started = Instant::now();
+    let response = client
+        .get(probe_url)
+        .timeout(Duration::from_secs(timeout.max(1)))
+        .send()
+        .await
+        .map_err(|e| format!("probe failed: {e}"))?;
+    let status = response.status().as_u16();
+    if status >= 500 {
+        return Err(format!("probe returned HTTP {status}"));
+    }
+    Ok(started.elapsed().as_millis() as u64)
+}
@@ -0,0 +1,117 @@
+pub mod checker;
+pub mod pool;
+
+use std::sync::Arc;
+
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use tokio::sync::OnceCell;
+
+use crate::core::config::{get_config, get_config_value, on_change};
+
+pub use pool::{EjectPolicy, ProxyPool, ProxyPurpose, ProxySpec, ProxyStrategy};
+
+static POOL: OnceCell<Arc<ProxyPool>> = OnceCell::const_new();
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ProxyItem {
+    Url(String),
+    Spec(ProxySpec),
+}
+
+/// Builds the pool from `proxy.pool`, appending the legacy
+/// `grok.base_proxy_url` / `grok.asset_proxy_url` keys when they are set.
+async fn configured_specs() -> Vec<ProxySpec> {
+    let mut specs: Vec<ProxySpec> = Vec::new();
+    if let Some(JsonValue::Array(items)) = get_config_value("proxy.pool").await {
+        for item in items {
+            let spec = match serde_json::from_value::<ProxyItem>(item) {
+                Ok(ProxyItem::Url(url)) => ProxySpec::new(url, Vec::new()),
+                Ok(ProxyItem::Spec(spec)) => spec,
+                Err(err) => {
+                    tracing::warn!("Ignoring invalid proxy.pool entry: {err}");
+                    continue;
+                }
+            };
+            push_spec(&mut specs, spec);
+        }
+    }
+
+    let base: String = get_config("grok.base_proxy_url", String::new()).await;
+    push_spec(&mut specs, ProxySpec::new(base, Vec::new()));
+    let asset: String = get_config("grok.asset_proxy_url", String::new()).await;
+    push_spec(
+        &mut specs,
+        ProxySpec::new(asset, vec![pool::ASSET_TAG.to_string()]),
+    );
+    specs
+}
+
+fn push_spec(specs: &mut Vec<ProxySpec>, mut spec: ProxySpec) {
+    spec.url = spec.url.trim().to_string();
+    if spec.url.is_empty() || specs.iter().any(|s| s.url == spec.url) {
+        return;
+    }
+    spec.tags = spec
+        .tags
+        .into_iter()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect();
+    specs.push(spec);
+}
+
+pub async fn get_proxy_pool() -> Arc<ProxyPool> {
+    POOL.get_or_init(|| async { Arc::new(ProxyPool::new()) })
+        .await
+        .clone()
+}
+
+/// Builds the pool from config and rebuilds it whenever a proxy key changes.
+pub fn start_proxy_pool_watcher() {
+    on_change(
+        &["proxy.pool", "grok.base_proxy_url", "grok.asset_proxy_url"],
+        || async {
+            get_proxy_pool().await.sync(configured_specs().await);
+        },
+    );
+}
+
+pub async fn eject_policy() -> EjectPolicy {
+    let defaults = EjectPolicy::default();
+    EjectPolicy {
+        fail_threshold: get_config("proxy.fail_threshold", defaults.fail_threshold).await,
+        base_sec: get_config("proxy.eject_base_sec", defaults.base_sec).await,
+        max_sec: get_config("proxy.eject_max_sec", defaults.max_sec).await,
+    }
+}
+
+/// Picks an outbound proxy for `purpose`; `None` means connect directly.
+pub async fn select_proxy(purpose: ProxyPurpose) -> Option<String> {
+    let strategy: String = get_config("proxy.strategy", String::new()).await;
+    get_proxy_pool()
+        .await
+        .select(purpose, ProxyStrategy::parse(&strategy))
+}
+
+/// Feeds the outcome of a request sent through `url` back into the pool;
+/// `error` is set only for transport failures.
+pub async fn record_request(url: &str, error: Option<String>) {
+    let pool = get_proxy_pool().await;
+    match error {
+        None => pool.record_success(url, None),
+        Some(error) => pool.record_failure(url, error, &eject_policy().await),
+    }
+}
+
+pub fn mask_proxy_url(raw: &str) -> String {
+    match url::Url::parse(raw) {
+        Ok(mut url) if !url.username().is_empty() || url.password().is_some() => {
+            let _ = url.set_username("***");
+            let _ = url.set_password(None);
+            url.to_string()
+        }
+        _ => raw.to_string(),
+    }
+}
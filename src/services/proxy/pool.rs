@@ -0,0 +1,340 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+pub const ASSET_TAG: &str = "asset";
+pub const BASE_TAG: &str = "base";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyPurpose {
+    Base,
+    Asset,
+}
+
+impl ProxyPurpose {
+    fn tag(self) -> &'static str {
+        match self {
+            ProxyPurpose::Base => BASE_TAG,
+            ProxyPurpose::Asset => ASSET_TAG,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProxyStrategy {
+    #[default]
+    Weighted,
+    RoundRobin,
+}
+
+impl ProxyStrategy {
+    pub fn parse(raw: &str) -> Self {
+        match raw.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "round_robin" | "rr" => ProxyStrategy::RoundRobin,
+            _ => ProxyStrategy::Weighted,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProxySpec {
+    pub url: String,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+impl ProxySpec {
+    pub fn new(url: impl Into<String>, tags: Vec<String>) -> Self {
+        Self {
+            url: url.into(),
+            weight: default_weight(),
+            tags,
+        }
+    }
+
+    /// Entries tagged `base` or `asset` only serve that purpose; untagged
+    /// entries serve both.
+    fn serves(&self, purpose: ProxyPurpose) -> bool {
+        let scoped = self.tags.iter().any(|t| t == BASE_TAG || t == ASSET_TAG);
+        !scoped || self.tags.iter().any(|t| t == purpose.tag())
+    }
+
+    fn prefers(&self, purpose: ProxyPurpose) -> bool {
+        self.tags.iter().any(|t| t == purpose.tag())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EjectPolicy {
+    pub fail_threshold: u32,
+    pub base_sec: i64,
+    pub max_sec: i64,
+}
+
+impl Default for EjectPolicy {
+    fn default() -> Self {
+        Self {
+            fail_threshold: 3,
+            base_sec: 30,
+            max_sec: 600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProxyHealth {
+    pub successes: u64,
+    pub failures: u64,
+    pub consecutive_failures: u32,
+    pub ejections: u32,
+    pub ejected_until: Option<i64>,
+    pub backoff_sec: i64,
+    pub latency_ms: Option<u64>,
+    pub last_checked_at: Option<i64>,
+    pub last_error: Option<String>,
+}
+
+impl ProxyHealth {
+    pub fn is_ejected(&self, now: i64) -> bool {
+        self.ejected_until.is_some_and(|until| until > now)
+    }
+
+    fn record_success(&mut self, latency_ms: Option<u64>, now: i64) {
+        self.last_checked_at = Some(now);
+        self.successes += 1;
+        self.consecutive_failures = 0;
+        self.ejected_until = None;
+        self.backoff_sec = 0;
+        self.last_error = None;
+        if latency_ms.is_some() {
+            self.latency_ms = latency_ms;
+        }
+    }
+
+    /// Returns true when this failure ejected the proxy.
+    fn record_failure(&mut self, error: String, policy: &EjectPolicy, now: i64) -> bool {
+        self.last_checked_at = Some(now);
+        self.failures += 1;
+        self.consecutive_failures += 1;
+        self.last_error = Some(error);
+        if self.is_ejected(now) || self.consecutive_failures < policy.fail_threshold.max(1) {
+            return false;
+        }
+        self.backoff_sec = if self.backoff_sec == 0 {
+            policy.base_sec.max(1)
+        } else {
+            (self.backoff_sec * 2).min(policy.max_sec.max(policy.base_sec))
+        };
+        self.ejected_until = Some(now + self.backoff_sec * 1000);
+        self.ejections += 1;
+        true
+    }
+}
+
+#[derive(Debug)]
+pub struct ProxyEntry {
+    spec: ProxySpec,
+    health: Mutex<ProxyHealth>,
+}
+
+impl ProxyEntry {
+    fn new(spec: ProxySpec, health: ProxyHealth) -> Self {
+        Self {
+            spec,
+            health: Mutex::new(health),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ProxyHealth> {
+        self.health.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn url(&self) -> &str {
+        &self.spec.url
+    }
+
+    pub fn health(&self) -> ProxyHealth {
+        self.lock().clone()
+    }
+
+    /// Ejected entries become eligible for a probe once their back-off has
+    /// elapsed; a successful probe reinstates them.
+    pub fn due_for_probe(&self, now: i64) -> bool {
+        !self.lock().is_ejected(now)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProxyStatus {
+    pub url: String,
+    pub weight: u32,
+    pub tags: Vec<String>,
+    pub available: bool,
+    #[serde(flatten)]
+    pub health: ProxyHealth,
+}
+
+#[derive(Debug, Default)]
+pub struct ProxyPool {
+    entries: std::sync::RwLock<Arc<Vec<Arc<ProxyEntry>>>>,
+    cursor: AtomicUsize,
+}
+
+impl ProxyPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn entries(&self) -> Arc<Vec<Arc<ProxyEntry>>> {
+        self.entries
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Replaces the configured proxies, keeping health state for URLs that
+    /// are still present.
+    pub fn sync(&self, specs: Vec<ProxySpec>) {
+        let mut guard = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        if guard.iter().map(|e| &e.spec).eq(specs.iter()) {
+            return;
+        }
+        let next: Vec<Arc<ProxyEntry>> = specs
+            .into_iter()
+            .map(|spec| {
+                let health = guard
+                    .iter()
+                    .find(|e| e.spec.url == spec.url)
+                    .map(|e| e.health())
+                    .unwrap_or_default();
+                Arc::new(ProxyEntry::new(spec, health))
+            })
+            .collect();
+        *guard = Arc::new(next);
+    }
+
+    pub fn select(&self, purpose: ProxyPurpose, strategy: ProxyStrategy) -> Option<String> {
+        let entries = self.entries();
+        let now = chrono::Utc::now().timestamp_millis();
+        let serving: Vec<&Arc<ProxyEntry>> = entries
+            .iter()
+            .filter(|e| e.spec.weight > 0 && e.spec.serves(purpose))
+            .collect();
+        if serving.is_empty() {
+            return None;
+        }
+
+        let available: Vec<&Arc<ProxyEntry>> = serving
+            .iter()
+            .copied()
+            .filter(|e| !e.lock().is_ejected(now))
+            .collect();
+        if available.is_empty() {
+            // Every proxy is ejected: use the one closest to reinstatement
+            // rather than failing the request outright.
+            return serving
+                .iter()
+                .min_by_key(|e| e.lock().ejected_until.unwrap_or(0))
+                .map(|e| e.spec.url.clone());
+        }
+
+        let preferred: Vec<&Arc<ProxyEntry>> = available
+            .iter()
+            .copied()
+            .filter(|e| e.spec.prefers(purpose))
+            .collect();
+        let candidates = if preferred.is_empty() {
+            available
+        } else {
+            preferred
+        };
+
+        let picked = match strategy {
+            ProxyStrategy::RoundRobin => {
+                let idx = self.cursor.fetch_add(1, Ordering::Relaxed) % candidates.len();
+                candidates[idx]
+            }
+            ProxyStrategy::Weighted => {
+                let total: u64 = candidates.iter().map(|e| e.spec.weight as u64).sum();
+                let mut roll = rand::thread_rng().gen_range(0..total);
+                candidates
+                    .iter()
+                    .copied()
+                    .find(|e| {
+                        let weight = e.spec.weight as u64;
+                        if roll < weight {
+                            return true;
+                        }
+                        roll -= weight;
+                        false
+                    })
+                    .unwrap_or(candidates[0])
+            }
+        };
+        Some(picked.spec.url.clone())
+    }
+
+    pub fn record_success(&self, url: &str, latency_ms: Option<u64>) {
+        if let Some(entry) = self.entries().iter().find(|e| e.spec.url == url) {
+            let now = chrono::Utc::now().timestamp_millis();
+            entry.lock().record_success(latency_ms, now);
+        }
+    }
+
+    pub fn record_failure(&self, url: &str, error: impl Into<String>, policy: &EjectPolicy) {
+        let Some(entry) = self.entries().iter().find(|e| e.spec.url == url).cloned() else {
+            return;
+        };
+        let now = chrono::Utc::now().timestamp_millis();
+        let mut health = entry.lock();
+        if health.record_failure(error.into(), policy, now) {
+            tracing::warn!(
+                "Proxy {} ejected for {}s after {} consecutive failures",
+                crate::services::proxy::mask_proxy_url(url),
+                health.backoff_sec,
+                health.consecutive_failures
+            );
+        }
+    }
+
+    /// Clears ejection and failure counters. Accepts either the raw URL or
+    /// the masked form shown by `status`; returns false when nothing matched.
+    pub fn reset(&self, url: &str) -> bool {
+        let entries = self.entries();
+        let found = entries.iter().find(|e| {
+            e.spec.url == url || crate::services::proxy::mask_proxy_url(&e.spec.url) == url
+        });
+        match found {
+            Some(entry) => {
+                *entry.lock() = ProxyHealth::default();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn status(&self) -> Vec<ProxyStatus> {
+        let now = chrono::Utc::now().timestamp_millis();
+        self.entries()
+            .iter()
+            .map(|e| {
+                let health = e.health();
+                ProxyStatus {
+                    url: crate::services::proxy::mask_proxy_url(&e.spec.url),
+                    weight: e.spec.weight,
+                    tags: e.spec.tags.clone(),
+                    available: e.spec.weight > 0 && !health.is_ejected(now),
+                    health,
+                }
+            })
+            .collect()
+    }
+}
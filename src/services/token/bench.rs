@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::services::token::manager::TokenManager;
+use crate::services::token::models::{EffortType, SelectionStrategy, TagFilter, TokenInfo};
+use crate::services::token::pool::TokenPool;
+
+const TOKENS: usize = 500;
+const ITERATIONS: usize = 20_000;
+
+fn build_manager() -> Arc<TokenManager> {
+    let mut pool = TokenPool::new("ssoBasic");
+    for i in 0..TOKENS {
+        let mut info = TokenInfo::new(format!("bench-token-{i}"));
+        info.quota = i32::MAX;
+        pool.add(info);
+    }
+    Arc::new(TokenManager::with_pools(HashMap::from([(
+        "ssoBasic".to_string(),
+        pool,
+    )])))
+}
+
+/// The design before per-token slots: one manager-wide async mutex, taken
+/// once to select and again to consume.
+async fn select_and_consume_locked(mgr: &tokio::sync::Mutex<Arc<TokenManager>>, tags: &TagFilter) {
+    let token = mgr
+        .lock()
+        .await
+        .get_token("ssoBasic", tags, SelectionStrategy::Quota)
+        .expect("token available");
+    mgr.lock().await.consume(&token, EffortType::Low).await;
+}
+
+async fn select_and_consume(mgr: &TokenManager, tags: &TagFilter) {
+    let token = mgr
+        .get_token("ssoBasic", tags, SelectionStrategy::Quota)
+        .expect("token available");
+    mgr.consume(&token, EffortType::Low).await;
+}
+
+// cargo test --release concurrent_selection_throughput -- --ignored --nocapture
+#[tokio::test(flavor = "multi_thread")]
+#[ignore]
+async fn concurrent_selection_throughput() {
+    for locked in [true, false] {
+        for workers in [1usize, 2, 4, 8, 16] {
+            let mgr = build_manager();
+            let global = Arc::new(tokio::sync::Mutex::new(mgr.clone()));
+            let started = Instant::now();
+            let handles: Vec<_> = (0..workers)
+                .map(|_| {
+                    let mgr = mgr.clone();
+                    let global = global.clone();
+                    tokio::spawn(async move {
+                        let tags = TagFilter::default();
+                        for _ in 0..ITERATIONS {
+                            if locked {
+                                select_and_consume_locked(&global, &tags).await;
+                            } else {
+                                select_and_consume(&mgr, &tags).await;
+                            }
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.await.expect("worker panicked");
+            }
+            let elapsed = started.elapsed();
+            let total = workers * ITERATIONS;
+            println!(
+                "path={:<6} workers={workers:>2} selections={total:>7} elapsed={:>8.1}ms throughput={:>10.0}/s",
+                if locked { "mutex" } else { "slots" },
+                elapsed.as_secs_f64() * 1000.0,
+                total as f64 / elapsed.as_secs_f64()
+            );
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use tokio::sync::broadcast;
+
+use crate::services::token::models::TokenStatus;
+use crate::services::token::transfer::mask_token;
+
+const BUS_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum TokenEventKind {
+    #[serde(rename = "token.expired")]
+    Expired,
+    #[serde(rename = "token.cooling")]
+    Cooling,
+    #[serde(rename = "token.recovered")]
+    Recovered,
+    #[serde(rename = "token.failed_threshold")]
+    FailedThreshold,
+    #[serde(rename = "token.quota_sync_failed")]
+    QuotaSyncFailed,
+    #[serde(rename = "pool.low_active")]
+    PoolLowActive,
+}
+
+impl TokenEventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TokenEventKind::Expired => "token.expired",
+            TokenEventKind::Cooling => "token.cooling",
+            TokenEventKind::Recovered => "token.recovered",
+            TokenEventKind::FailedThreshold => "token.failed_threshold",
+            TokenEventKind::QuotaSyncFailed => "token.quota_sync_failed",
+            TokenEventKind::PoolLowActive => "pool.low_active",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenEvent {
+    pub id: String,
+    pub event: TokenEventKind,
+    pub timestamp: i64,
+    pub pool: Option<String>,
+    pub token: Option<String>,
+    pub data: JsonValue,
+}
+
+static BUS: Lazy<broadcast::Sender<TokenEvent>> = Lazy::new(|| broadcast::channel(BUS_CAPACITY).0);
+
+static LOW_POOLS: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+pub fn subscribe() -> broadcast::Receiver<TokenEvent> {
+    BUS.subscribe()
+}
+
+/// Tokens are masked before they leave the process.
+pub fn emit(kind: TokenEventKind, pool: Option<&str>, token: Option<&str>, data: JsonValue) {
+    let event = TokenEvent {
+        id: uuid::Uuid::new_v4().to_string(),
+        event: kind,
+        timestamp: chrono::Utc::now().timestamp_millis(),
+        pool: pool.map(|p| p.to_string()),
+        token: token.map(mask_token),
+        data,
+    };
+    tracing::info!(
+        "Token event {} pool={} token={}",
+        kind.as_str(),
+        event.pool.as_deref().unwrap_or("-"),
+        event.token.as_deref().unwrap_or("-")
+    );
+    let _ = BUS.send(event);
+}
+
+pub fn emit_status_change(pool: &str, token: &str, before: &TokenStatus, after: &TokenStatus) {
+    let kind = match (before, after) {
+        (_, TokenStatus::Expired) => TokenEventKind::Expired,
+        (_, TokenStatus::Cooling) => TokenEventKind::Cooling,
+        (TokenStatus::Cooling | TokenStatus::Expired, TokenStatus::Active) => {
+            TokenEventKind::Recovered
+        }
+        _ => return,
+    };
+    emit(
+        kind,
+        Some(pool),
+        Some(token),
+        serde_json::json!({"from": before, "to": after}),
+    );
+}
+
+/// Edge-triggered: fires once when a pool drops below `threshold` active
+/// tokens and re-arms after it climbs back.
+pub fn check_pool_level(pool: &str, active: usize, threshold: usize) {
+    let mut low = LOW_POOLS.lock().unwrap_or_else(|e| e.into_inner());
+    if threshold == 0 || active >= threshold {
+        low.remove(pool);
+        return;
+    }
+    if low.insert(pool.to_string()) {
+        emit(
+            TokenEventKind::PoolLowActive,
+            Some(pool),
+            None,
+            serde_json::json!({"active": active, "threshold": threshold}),
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use serde::Serialize;
+
+use crate::core::exceptions::ApiError;
+
+const EWMA_ALPHA: f64 = 0.2;
+const MIN_SAMPLES: u32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    Auth,
+    RateLimit,
+    Upstream,
+    Network,
+}
+
+impl ErrorClass {
+    pub fn from_error(err: &ApiError) -> Self {
+        match err.upstream_status {
+            Some(401) | Some(403) => ErrorClass::Auth,
+            Some(429) => ErrorClass::RateLimit,
+            Some(_) => ErrorClass::Upstream,
+            None => ErrorClass::Network,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ErrorCounts {
+    pub auth: u32,
+    pub rate_limit: u32,
+    pub upstream: u32,
+    pub network: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TokenHealth {
+    pub samples: u32,
+    pub ttfb_ms: f64,
+    pub latency_ms: f64,
+    pub error_rate: f64,
+    pub errors: ErrorCounts,
+    pub quarantined_until: Option<i64>,
+}
+
+impl TokenHealth {
+    fn ewma(current: f64, sample: f64, first: bool) -> f64 {
+        if first {
+            sample
+        } else {
+            current + EWMA_ALPHA * (sample - current)
+        }
+    }
+
+    /// A released token starts over, so one error right after the
+    /// quarantine ends cannot send it straight back.
+    fn release_expired(&mut self) {
+        let now = chrono::Utc::now().timestamp_millis();
+        if self.quarantined_until.is_some_and(|until| until <= now) {
+            self.quarantined_until = None;
+            self.samples = 0;
+            self.ttfb_ms = 0.0;
+            self.error_rate = 0.0;
+        }
+    }
+
+    pub fn record_success(&mut self, ttfb_ms: f64) {
+        self.release_expired();
+        let first = self.samples == 0;
+        self.ttfb_ms = Self::ewma(self.ttfb_ms, ttfb_ms, first);
+        self.error_rate = Self::ewma(self.error_rate, 0.0, first);
+        self.samples += 1;
+    }
+
+    pub fn record_latency(&mut self, latency_ms: f64) {
+        let first = self.latency_ms == 0.0;
+        self.latency_ms = Self::ewma(self.latency_ms, latency_ms, first);
+    }
+
+    pub fn record_error(&mut self, class: ErrorClass) {
+        self.release_expired();
+        let first = self.samples == 0;
+        self.error_rate = Self::ewma(self.error_rate, 1.0, first);
+        self.samples += 1;
+        match class {
+            ErrorClass::Auth => self.errors.auth += 1,
+            ErrorClass::RateLimit => self.errors.rate_limit += 1,
+            ErrorClass::Upstream => self.errors.upstream += 1,
+            ErrorClass::Network => self.errors.network += 1,
+        }
+    }
+
+    pub fn score(&self) -> f64 {
+        if self.samples == 0 {
+            return 100.0;
+        }
+        let latency_penalty = (self.ttfb_ms / 1000.0 * 5.0).min(30.0);
+        ((1.0 - self.error_rate) * 100.0 - latency_penalty).clamp(0.0, 100.0)
+    }
+
+    pub fn is_quarantined(&self, now: i64) -> bool {
+        self.quarantined_until.is_some_and(|until| until > now)
+    }
+
+    pub fn update_quarantine(&mut self, threshold: f64, duration_sec: i64) -> bool {
+        let now = chrono::Utc::now().timestamp_millis();
+        if self.is_quarantined(now) || self.samples < MIN_SAMPLES || self.score() >= threshold {
+            return false;
+        }
+        self.quarantined_until = Some(now + duration_sec * 1000);
+        true
+    }
+}
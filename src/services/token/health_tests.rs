@@ -0,0 +1,58 @@
+use crate::services::token::health::{ErrorClass, TokenHealth};
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+#[test]
+fn score_starts_full_and_drops_with_errors_and_latency() {
+    let mut health = TokenHealth::default();
+    assert_eq!(health.score(), 100.0);
+
+    health.record_success(200.0);
+    assert_eq!(health.score(), 99.0);
+
+    health.record_error(ErrorClass::Upstream);
+    assert!((health.error_rate - 0.2).abs() < 1e-9);
+    assert_eq!(health.errors.upstream, 1);
+
+    let mut slow = TokenHealth::default();
+    slow.record_success(60_000.0);
+    assert_eq!(slow.score(), 70.0);
+}
+
+#[test]
+fn quarantine_needs_enough_samples_and_a_low_score() {
+    let mut health = TokenHealth::default();
+    for _ in 0..4 {
+        health.record_error(ErrorClass::Network);
+    }
+    assert!(!health.update_quarantine(30.0, 60));
+
+    health.record_error(ErrorClass::Network);
+    assert!(health.update_quarantine(30.0, 60));
+    assert!(health.is_quarantined(now()));
+    assert!(!health.update_quarantine(30.0, 60));
+
+    let mut healthy = TokenHealth::default();
+    for _ in 0..10 {
+        healthy.record_success(100.0);
+    }
+    assert!(!healthy.update_quarantine(30.0, 60));
+}
+
+#[test]
+fn release_resets_the_score() {
+    let mut health = TokenHealth::default();
+    for _ in 0..5 {
+        health.record_error(ErrorClass::Auth);
+    }
+    assert!(health.update_quarantine(30.0, 60));
+    health.quarantined_until = Some(now() - 1);
+
+    health.record_error(ErrorClass::Auth);
+    assert_eq!(health.samples, 1);
+    assert_eq!(health.quarantined_until, None);
+    assert!(!health.update_quarantine(30.0, 60));
+    assert_eq!(health.errors.auth, 6);
+}
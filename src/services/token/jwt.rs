@@ -0,0 +1,33 @@
+use base64::Engine;
+use serde_json::Value as JsonValue;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SsoClaims {
+    pub expires_at: Option<i64>,
+    pub issued_at: Option<i64>,
+    pub account_id: Option<String>,
+    pub session_id: Option<String>,
+}
+
+pub fn decode_claims(token: &str) -> Option<SsoClaims> {
+    let payload = token.trim_start_matches("sso=").split('.').nth(1)?;
+    let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .ok()?;
+    let claims: JsonValue = serde_json::from_slice(&bytes).ok()?;
+    let obj = claims.as_object()?;
+    let seconds = |key: &str| obj.get(key).and_then(|v| v.as_i64()).map(|s| s * 1000);
+    let text = |keys: &[&str]| {
+        keys.iter().find_map(|key| match obj.get(*key)? {
+            JsonValue::String(s) if !s.is_empty() => Some(s.clone()),
+            JsonValue::Number(n) => Some(n.to_string()),
+            _ => None,
+        })
+    };
+    Some(SsoClaims {
+        expires_at: seconds("exp"),
+        issued_at: seconds("iat"),
+        account_id: text(&["user_id", "account_id", "uid", "sub"]),
+        session_id: text(&["session_id", "sid", "jti"]),
+    })
+}
@@ -0,0 +1,55 @@
+use base64::Engine;
+use base64::engine::general_purpose::{URL_SAFE, URL_SAFE_NO_PAD};
+
+use crate::services::token::jwt::{SsoClaims, decode_claims};
+
+fn jwt(payload: &str) -> String {
+    format!(
+        "eyJhbGciOiJIUzI1NiJ9.{}.c2ln",
+        URL_SAFE_NO_PAD.encode(payload)
+    )
+}
+
+#[test]
+fn reads_expiry_and_identity_claims() {
+    let token = jwt(r#"{"exp": 1700000000, "iat": 1600000000, "user_id": "u1", "sid": 42}"#);
+    let expected = SsoClaims {
+        expires_at: Some(1_700_000_000_000),
+        issued_at: Some(1_600_000_000_000),
+        account_id: Some("u1".to_string()),
+        session_id: Some("42".to_string()),
+    };
+    assert_eq!(decode_claims(&token), Some(expected.clone()));
+    assert_eq!(decode_claims(&format!("sso={token}")), Some(expected));
+}
+
+#[test]
+fn accepts_padded_payloads() {
+    // One and two padding characters respectively.
+    for payload in [r#"{"sub":"a"}"#, r#"{"sub":"abc"}"#] {
+        let padded = URL_SAFE.encode(payload);
+        assert!(padded.ends_with('='), "{padded}");
+        let token = format!("h.{padded}.s");
+        let claims = decode_claims(&token).unwrap();
+        assert!(claims.account_id.is_some(), "{token}");
+    }
+}
+
+#[test]
+fn missing_claims_are_none() {
+    let claims = decode_claims(&jwt(r#"{"sub": "", "exp": "soon"}"#)).unwrap();
+    assert_eq!(claims, SsoClaims::default());
+}
+
+#[test]
+fn rejects_non_jwt_input() {
+    for token in [
+        "",
+        "plain-session-cookie",
+        "a.!!!.c",
+        &jwt("not json"),
+        &jwt("[1, 2]"),
+    ] {
+        assert_eq!(decode_claims(token), None, "{token}");
+    }
+}
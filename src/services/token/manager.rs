@@ -1,245 +1,679 @@
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
 
 use serde_json::Value as JsonValue;
 use tokio::sync::Mutex;
+use tokio::sync::Notify;
 use tokio::sync::OnceCell;
+use tokio::sync::broadcast;
 
-use crate::core::config::get_config;
-use crate::core::storage::{Storage, get_storage};
-use crate::services::grok::usage::UsageService;
+use crate::core::config::{get_config, on_change};
+use crate::core::exceptions::ApiError;
+use crate::core::redact::{fingerprint, is_fingerprint};
+use crate::core::storage::{Storage, StorageChange, StorageError, get_storage};
+use crate::services::grok::model::Tier;
+use crate::services::grok::usage::{UsageService, recover_after_secs};
+use crate::services::token::events::{self, TokenEventKind};
+use crate::services::token::health::{ErrorClass, TokenHealth};
 use crate::services::token::models::{
-    DEFAULT_QUOTA, EffortType, FAIL_THRESHOLD, TokenInfo, TokenPoolStats, TokenStatus,
+    DEFAULT_QUOTA, EffortType, FAIL_THRESHOLD, ImagineState, NsfwStatus, SelectionStrategy,
+    TagFilter, TokenBinding, TokenInfo, TokenPoolStats, TokenStatus,
 };
-use crate::services::token::pool::TokenPool;
+use crate::services::token::pool::{TokenPool, TokenSlot};
+use crate::services::token::scheduler::notify_recover_scheduled;
+
+type PoolMap = HashMap<String, TokenPool>;
 
 #[derive(Debug)]
 pub struct TokenManager {
-    pub pools: HashMap<String, TokenPool>,
-    initialized: bool,
-    last_reload_at: Instant,
+    pools: RwLock<Arc<PoolMap>>,
+    last_reload_at: std::sync::Mutex<Instant>,
+    reloading: AtomicBool,
+    /// A change notice arrived while local edits were unsaved.
+    reload_pending: AtomicBool,
+    /// The token document as last read from or written to storage; the
+    /// base of the three-way merge in `flush_tokens`.
+    synced: std::sync::Mutex<JsonValue>,
+    dirty_gen: AtomicU64,
+    saved_gen: AtomicU64,
+    /// Active tokens per pool, moved by each status transition so level
+    /// checks never rescan the pools.
+    active: std::sync::Mutex<HashMap<String, usize>>,
+    low_active_threshold: AtomicUsize,
 }
 
 impl TokenManager {
     pub fn new() -> Self {
         Self {
-            pools: HashMap::new(),
-            initialized: false,
-            last_reload_at: Instant::now(),
+            pools: RwLock::new(Arc::new(HashMap::new())),
+            last_reload_at: std::sync::Mutex::new(Instant::now()),
+            reloading: AtomicBool::new(false),
+            reload_pending: AtomicBool::new(false),
+            synced: std::sync::Mutex::new(JsonValue::Object(Default::default())),
+            dirty_gen: AtomicU64::new(0),
+            saved_gen: AtomicU64::new(0),
+            active: std::sync::Mutex::new(HashMap::new()),
+            low_active_threshold: AtomicUsize::new(0),
         }
     }
 
-    pub async fn load(&mut self) {
-        if self.initialized {
+    #[cfg(test)]
+    pub fn with_pools(pools: PoolMap) -> Self {
+        let mgr = Self::new();
+        mgr.recount_active(&pools);
+        *mgr.pools.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(pools);
+        mgr
+    }
+
+    pub fn pools(&self) -> Arc<PoolMap> {
+        self.pools.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn modify_pools<R>(&self, f: impl FnOnce(&mut PoolMap) -> R) -> R {
+        let mut guard = self.pools.write().unwrap_or_else(|e| e.into_inner());
+        let mut next = (**guard).clone();
+        let result = f(&mut next);
+        *guard = Arc::new(next);
+        result
+    }
+
+    fn find(&self, raw: &str) -> Option<(String, Arc<TokenSlot>)> {
+        self.pools()
+            .iter()
+            .find_map(|(name, pool)| pool.slot(raw).map(|s| (name.clone(), s.clone())))
+    }
+
+    /// Applies `f` to a slot and publishes lifecycle events for any status
+    /// transition it caused.
+    fn update_tracked<R>(
+        &self,
+        pool_name: &str,
+        slot: &TokenSlot,
+        f: impl FnOnce(&mut TokenInfo) -> R,
+    ) -> R {
+        let (before, after, result) = slot.update(|t| {
+            let before = t.status.clone();
+            let result = f(t);
+            (before, t.status.clone(), result)
+        });
+        if before != after {
+            events::emit_status_change(pool_name, slot.token(), &before, &after);
+            self.track_transition(pool_name, &before, &after);
+        }
+        result
+    }
+
+    fn track_transition(&self, pool_name: &str, before: &TokenStatus, after: &TokenStatus) {
+        self.shift_active(pool_name, before, -1);
+        self.shift_active(pool_name, after, 1);
+    }
+
+    fn shift_active(&self, pool_name: &str, status: &TokenStatus, delta: isize) {
+        if *status != TokenStatus::Active {
             return;
         }
-        let storage = get_storage();
-        let data = storage
-            .load_tokens()
-            .await
-            .unwrap_or(JsonValue::Object(Default::default()));
-        let mut pools: HashMap<String, TokenPool> = HashMap::new();
+        let active = {
+            let mut counts = self.active.lock().unwrap_or_else(|e| e.into_inner());
+            let count = counts.entry(pool_name.to_string()).or_default();
+            *count = count.saturating_add_signed(delta);
+            *count
+        };
+        let threshold = self.low_active_threshold.load(Ordering::Relaxed);
+        events::check_pool_level(pool_name, active, threshold);
+    }
+
+    /// Full recount, only for loads and whole-file replacements.
+    fn recount_active(&self, pools: &PoolMap) {
+        let counts = pools
+            .iter()
+            .map(|(name, pool)| (name.clone(), pool.stats().active))
+            .collect();
+        *self.active.lock().unwrap_or_else(|e| e.into_inner()) = counts;
+        self.check_pool_levels();
+    }
+
+    fn check_pool_levels(&self) {
+        let counts = self
+            .active
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let threshold = self.low_active_threshold.load(Ordering::Relaxed);
+        for (pool, active) in counts {
+            events::check_pool_level(&pool, active, threshold);
+        }
+    }
+
+    pub fn set_low_active_threshold(&self, threshold: usize) {
+        self.low_active_threshold
+            .store(threshold, Ordering::Relaxed);
+        self.check_pool_levels();
+    }
+
+    fn build_pools(&self, data: &JsonValue) -> PoolMap {
+        let old = self.pools();
+        let mut pools: PoolMap = HashMap::new();
         if let Some(obj) = data.as_object() {
             for (pool_name, list) in obj {
                 let mut pool = TokenPool::new(pool_name);
                 if let Some(arr) = list.as_array() {
                     for token_val in arr {
                         if let Some(token_info) = token_from_value(token_val) {
-                            pool.add(token_info);
+                            let health = old
+                                .values()
+                                .find_map(|p| p.slot(&token_info.token))
+                                .map(|s| s.health())
+                                .unwrap_or_default();
+                            pool.add_slot(Arc::new(TokenSlot::with_health(token_info, health)));
                         }
                     }
                 }
                 pools.insert(pool_name.to_string(), pool);
             }
         }
-        self.pools = pools;
-        self.initialized = true;
-        self.last_reload_at = Instant::now();
-        let total: usize = self.pools.values().map(|p| p.count()).sum();
+        pools
+    }
+
+    async fn load(&self, expected_gen: Option<u64>) {
+        let generation = self.dirty_gen.load(Ordering::SeqCst);
+        let remote = match get_storage().load_tokens().await {
+            Ok(data) => data,
+            Err(err) => {
+                tracing::warn!("Token load failed, keeping current pools: {err}");
+                return;
+            }
+        };
+        let synced = document(&self.build_pools(&remote));
+        // Unflushed edits are merged over the snapshot and stay dirty, so
+        // the flusher still saves them.
+        let pools = if self.has_unsaved() {
+            let base = self
+                .synced
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone();
+            self.build_pools(&merge_tokens(&base, &self.snapshot(), &synced))
+        } else {
+            self.build_pools(&synced)
+        };
+        let total: usize = pools.values().map(|p| p.count()).sum();
+        let pool_count = pools.len();
+        {
+            let mut guard = self.pools.write().unwrap_or_else(|e| e.into_inner());
+            let expected = expected_gen.unwrap_or(generation);
+            if self.dirty_gen.load(Ordering::SeqCst) != expected {
+                self.reload_pending.store(true, Ordering::SeqCst);
+                return;
+            }
+            self.recount_active(&pools);
+            *guard = Arc::new(pools);
+            *self.synced.lock().unwrap_or_else(|e| e.into_inner()) = synced;
+        }
+        *self
+            .last_reload_at
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = Instant::now();
         tracing::info!(
-            "TokenManager initialized: {} pools with {} tokens",
-            self.pools.len(),
+            "TokenManager loaded: {} pools with {} tokens",
+            pool_count,
             total
         );
+        self.expire_by_claims();
+    }
+
+    /// Replaces every pool with `data`, the admin's whole-file edit, as a
+    /// local change so `flush_tokens` saves it under the same lock and merge
+    /// as any other edit.
+    pub fn replace_all(&self, data: &JsonValue) {
+        let pools = self.build_pools(data);
+        self.recount_active(&pools);
+        self.modify_pools(|current| *current = pools);
+        self.save();
     }
 
-    pub async fn reload(&mut self) {
-        self.initialized = false;
-        self.load().await;
+    pub async fn reload(&self) {
+        self.load(None).await;
     }
 
-    pub async fn reload_if_stale(&mut self) {
+    pub async fn reload_if_stale(&self) {
         let interval: f64 = get_config("token.reload_interval_sec", 30f64).await;
-        if interval <= 0.0 {
+        if interval <= 0.0 || self.has_unsaved() {
             return;
         }
-        if self.last_reload_at.elapsed() < Duration::from_secs_f64(interval) {
+        let elapsed = self
+            .last_reload_at
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .elapsed();
+        if elapsed < Duration::from_secs_f64(interval) {
             return;
         }
-        self.reload().await;
+        self.reload_unless_dirty().await;
+    }
+
+    /// Picks up tokens written elsewhere unless local changes are still
+    /// waiting to be flushed.
+    pub async fn reload_unless_dirty(&self) {
+        if self.has_unsaved() {
+            self.reload_pending.store(true, Ordering::SeqCst);
+            return;
+        }
+        if self.reloading.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let generation = self.dirty_gen.load(Ordering::SeqCst);
+        self.load(Some(generation)).await;
+        self.reloading.store(false, Ordering::SeqCst);
+    }
+
+    fn save(&self) {
+        self.dirty_gen.fetch_add(1, Ordering::SeqCst);
+        FLUSH_NOTIFY.notify_one();
+    }
+
+    fn has_unsaved(&self) -> bool {
+        self.dirty_gen.load(Ordering::SeqCst) != self.saved_gen.load(Ordering::SeqCst)
+    }
+
+    pub fn snapshot(&self) -> JsonValue {
+        document(&self.pools())
+    }
+
+    pub fn get_token(
+        &self,
+        pool_name: &str,
+        tags: &TagFilter,
+        strategy: SelectionStrategy,
+    ) -> Option<String> {
+        let pools = self.pools();
+        let token = pools.get(pool_name)?.select(tags, strategy)?;
+        Some(token.trim_start_matches("sso=").to_string())
+    }
+
+    pub fn can_use(&self, pool_name: &str, token_str: &str, tags: &TagFilter) -> bool {
+        let raw = token_str.trim_start_matches("sso=");
+        let now = chrono::Utc::now().timestamp_millis();
+        self.pools()
+            .get(pool_name)
+            .and_then(|p| p.slot(raw).map(|s| s.is_usable(tags, now)))
+            .unwrap_or(false)
+    }
+
+    pub async fn record_ttfb(&self, token_str: &str, ttfb: Duration) {
+        let raw = token_str.trim_start_matches("sso=");
+        if let Some((_, slot)) = self.find(raw) {
+            slot.update_health(|h| h.record_success(ttfb.as_secs_f64() * 1000.0));
+            check_quarantine(&slot).await;
+        }
+    }
+
+    pub fn record_latency(&self, token_str: &str, latency: Duration) {
+        let raw = token_str.trim_start_matches("sso=");
+        if let Some((_, slot)) = self.find(raw) {
+            slot.update_health(|h| h.record_latency(latency.as_secs_f64() * 1000.0));
+        }
+    }
+
+    pub async fn record_error(&self, token_str: &str, class: ErrorClass) {
+        let raw = token_str.trim_start_matches("sso=");
+        if let Some((_, slot)) = self.find(raw) {
+            slot.update_health(|h| h.record_error(class));
+            check_quarantine(&slot).await;
+        }
     }
 
-    async fn save(&self) {
-        let storage = get_storage();
-        let mut map = serde_json::Map::new();
-        for (name, pool) in &self.pools {
-            let tokens: Vec<JsonValue> = pool
-                .list()
-                .into_iter()
-                .map(|t| serde_json::to_value(t).unwrap_or(JsonValue::Null))
-                .collect();
-            map.insert(name.clone(), JsonValue::Array(tokens));
+    pub fn get_binding(&self, token_str: &str) -> TokenBinding {
+        let raw = token_str.trim_start_matches("sso=");
+        self.find(raw)
+            .map(|(_, slot)| slot.read(|t| t.binding()))
+            .unwrap_or_default()
+    }
+
+    pub fn get_info(&self, token_str: &str) -> Option<TokenInfo> {
+        let raw = token_str.trim_start_matches("sso=");
+        self.find(raw).map(|(_, slot)| slot.info())
+    }
+
+    /// Maps a token reference from the admin UI back to the stored token.
+    /// Admin responses only carry fingerprints, so both forms are accepted;
+    /// unknown fingerprints are returned unchanged and simply match nothing.
+    pub fn resolve(&self, token_str: &str) -> String {
+        let raw = token_str.trim().trim_start_matches("sso=");
+        if !is_fingerprint(raw) {
+            return raw.to_string();
         }
-        let data = JsonValue::Object(map);
-        let _ = storage
-            .with_lock("tokens_save", 10, || async {
-                storage.save_tokens(&data).await
-            })
-            .await;
+        self.pools()
+            .values()
+            .flat_map(|pool| pool.slots().iter())
+            .find(|slot| fingerprint(slot.token()) == raw)
+            .map_or_else(|| raw.to_string(), |slot| slot.token().to_string())
     }
 
-    pub fn get_token(&self, pool_name: &str) -> Option<String> {
-        let pool = self.pools.get(pool_name)?;
-        let token = pool.select()?;
-        Some(token.token.trim_start_matches("sso=").to_string())
+    pub fn get_health(&self, token_str: &str) -> Option<TokenHealth> {
+        let raw = token_str.trim_start_matches("sso=");
+        self.find(raw).map(|(_, slot)| slot.health())
     }
 
-    pub async fn consume(&mut self, token_str: &str, effort: EffortType) -> bool {
+    pub async fn consume(&self, token_str: &str, effort: EffortType) -> bool {
         let raw = token_str.trim_start_matches("sso=");
-        for pool in self.pools.values_mut() {
-            if let Some(token) = pool.get_mut(raw) {
-                token.consume(&effort);
-                self.save().await;
-                return true;
+        match self.find(raw) {
+            Some((pool_name, slot)) => {
+                self.update_tracked(&pool_name, &slot, |t| t.consume(&effort));
+                self.save();
+                true
             }
+            None => false,
         }
-        false
     }
 
     pub async fn sync_usage(
-        &mut self,
+        &self,
         token_str: &str,
         model_name: &str,
         fallback_effort: EffortType,
         consume_on_fail: bool,
         is_usage: bool,
     ) -> bool {
-        let raw = token_str.trim_start_matches("sso=");
-        let mut pool_name: Option<String> = None;
-        for (name, pool) in &self.pools {
-            if pool.get(raw).is_some() {
-                pool_name = Some(name.clone());
-                break;
-            }
+        if self.find(token_str.trim_start_matches("sso=")).is_none() {
+            return false;
         }
-        let pool_name = match pool_name {
-            Some(p) => p,
-            None => return false,
+        if let Ok(true) = self.try_sync_usage(token_str, model_name, is_usage).await {
+            return true;
+        }
+        if consume_on_fail {
+            self.consume(token_str, fallback_effort).await
+        } else {
+            false
+        }
+    }
+
+    /// `sync_usage` without the local fallback; the error carries the
+    /// upstream status so callers can tell rejected tokens from outages.
+    pub async fn try_sync_usage(
+        &self,
+        token_str: &str,
+        model_name: &str,
+        is_usage: bool,
+    ) -> Result<bool, ApiError> {
+        let raw = token_str.trim_start_matches("sso=");
+        let Some((pool_name, slot)) = self.find(raw) else {
+            return Ok(false);
         };
         let usage_service = UsageService::new().await;
         match usage_service.get(token_str, model_name).await {
             Ok(result) => {
-                if let Some(remain) = result.get("remainingTokens").and_then(|v| v.as_i64()) {
-                    if let Some(token) = self.pools.get_mut(&pool_name).and_then(|p| p.get_mut(raw))
-                    {
+                let Some(remain) = result.get("remainingTokens").and_then(|v| v.as_i64()) else {
+                    return Ok(false);
+                };
+                let (old_quota, new_quota, scheduled) =
+                    self.update_tracked(&pool_name, &slot, |token| {
                         let old_quota = token.quota;
                         token.update_quota(remain as i32);
                         token.record_success(is_usage);
-                        tracing::info!(
-                            "Token {} synced quota {} -> {}",
-                            &raw[..raw.len().min(8)],
-                            old_quota,
-                            token.quota
-                        );
-                        self.save().await;
-                        return true;
-                    }
+                        let scheduled = token.set_recover_after(recover_after_secs(&result));
+                        (old_quota, token.quota, scheduled)
+                    });
+                if scheduled {
+                    notify_recover_scheduled();
                 }
+                tracing::info!(
+                    "Token {} synced quota {} -> {}",
+                    fingerprint(raw),
+                    old_quota,
+                    new_quota
+                );
+                self.save();
+                Ok(true)
             }
             Err(err) => {
-                tracing::warn!(
-                    "Token {} API sync failed: {}",
-                    &raw[..raw.len().min(8)],
-                    err
+                tracing::warn!("Token {} API sync failed: {}", fingerprint(raw), err);
+                events::emit(
+                    TokenEventKind::QuotaSyncFailed,
+                    Some(&pool_name),
+                    Some(raw),
+                    serde_json::json!({"model": model_name, "error": err.to_string()}),
                 );
+                Err(err)
             }
         }
-        if consume_on_fail {
-            self.consume(token_str, fallback_effort).await
-        } else {
-            false
-        }
     }
 
-    pub async fn record_fail(&mut self, token_str: &str, status_code: u16, reason: &str) -> bool {
+    pub async fn record_fail(&self, token_str: &str, status_code: u16, reason: &str) -> bool {
         let raw = token_str.trim_start_matches("sso=");
-        for pool in self.pools.values_mut() {
-            if let Some(token) = pool.get_mut(raw) {
-                token.record_fail(status_code, reason);
-                self.save().await;
-                return true;
+        match self.find(raw) {
+            Some((pool_name, slot)) => {
+                let (before, after, fail_count) = slot.update(|t| {
+                    let before = t.status.clone();
+                    t.record_fail(status_code, reason);
+                    (before, t.status.clone(), t.fail_count)
+                });
+                // Reaching the threshold is the only transition here; it is
+                // reported as `token.failed_threshold` instead of `token.expired`.
+                if before != after {
+                    events::emit(
+                        TokenEventKind::FailedThreshold,
+                        Some(&pool_name),
+                        Some(raw),
+                        serde_json::json!({
+                            "from": before,
+                            "to": after,
+                            "fail_count": fail_count,
+                            "threshold": FAIL_THRESHOLD,
+                            "status_code": status_code,
+                            "reason": reason,
+                        }),
+                    );
+                    self.track_transition(&pool_name, &before, &after);
+                }
+                self.save();
+                true
             }
+            None => false,
         }
-        false
     }
 
-    pub async fn add(&mut self, token: &str, pool_name: &str) -> bool {
+    pub async fn add(&self, token: &str, pool_name: &str) -> Option<TokenInfo> {
         let raw = token.trim_start_matches("sso=");
-        let pool = self
-            .pools
-            .entry(pool_name.to_string())
-            .or_insert_with(|| TokenPool::new(pool_name));
-        if pool.get(raw).is_some() {
-            return false;
+        let added = self.modify_pools(|pools| {
+            if pools.values().any(|p| p.slot(raw).is_some()) {
+                return None;
+            }
+            let info = TokenInfo::new(raw.to_string());
+            pools
+                .entry(pool_name.to_string())
+                .or_insert_with(|| TokenPool::new(pool_name))
+                .add(info.clone());
+            Some(info)
+        });
+        if let Some(info) = &added {
+            self.shift_active(pool_name, &info.status, 1);
+            if let Some(account_id) = &info.account_id {
+                let siblings = self.shared_accounts().get(account_id).map(|t| t.len());
+                if let Some(count) = siblings {
+                    tracing::warn!(
+                        "Token {} shares account {} with {} other token(s)",
+                        fingerprint(raw),
+                        account_id,
+                        count - 1
+                    );
+                }
+            }
+            self.save();
         }
-        pool.add(TokenInfo::new(raw.to_string()));
-        self.save().await;
-        true
+        added
     }
 
-    pub async fn remove(&mut self, token: &str) -> bool {
-        for pool in self.pools.values_mut() {
-            if pool.remove(token) {
-                self.save().await;
-                return true;
+    pub fn shared_accounts(&self) -> HashMap<String, Vec<String>> {
+        let mut accounts: HashMap<String, Vec<String>> = HashMap::new();
+        for pool in self.pools().values() {
+            for info in pool.list() {
+                if let Some(account_id) = info.account_id {
+                    accounts.entry(account_id).or_default().push(info.token);
+                }
             }
         }
-        false
+        accounts.retain(|_, tokens| tokens.len() > 1);
+        accounts
     }
 
-    pub async fn reset_all(&mut self) {
-        for pool in self.pools.values_mut() {
-            for token in pool.list() {
-                if let Some(tok) = pool.get_mut(&token.token) {
-                    tok.reset();
+    pub fn expire_by_claims(&self) -> usize {
+        let now = chrono::Utc::now().timestamp_millis();
+        let mut expired = 0;
+        for (name, pool) in self.pools().iter() {
+            for slot in pool.slots() {
+                if self.update_tracked(name, slot, |t| t.expire_if_past(now)) {
+                    expired += 1;
                 }
             }
         }
-        self.save().await;
+        if expired > 0 {
+            tracing::info!("Marked {} token(s) expired by JWT exp claim", expired);
+            self.save();
+        }
+        expired
+    }
+
+    pub async fn update_token(
+        &self,
+        token_str: &str,
+        f: impl FnOnce(&mut TokenInfo),
+    ) -> Option<TokenInfo> {
+        let raw = token_str.trim_start_matches("sso=");
+        let (pool_name, slot) = self.find(raw)?;
+        let info = self.update_tracked(&pool_name, &slot, |t| {
+            f(t);
+            t.clone()
+        });
+        self.save();
+        Some(info)
+    }
+
+    pub async fn move_token(&self, token_str: &str, pool_name: &str) -> Option<TokenInfo> {
+        let raw = token_str.trim_start_matches("sso=");
+        let (from, slot) = self.modify_pools(|pools| {
+            let current = pools
+                .iter()
+                .find(|(_, p)| p.slot(raw).is_some())
+                .map(|(name, _)| name.clone())?;
+            if current == pool_name {
+                let slot = pools.get(&current).and_then(|p| p.slot(raw)).cloned()?;
+                return Some((current, slot));
+            }
+            let slot = pools.get_mut(&current)?.remove(raw)?;
+            pools
+                .entry(pool_name.to_string())
+                .or_insert_with(|| TokenPool::new(pool_name))
+                .add_slot(slot.clone());
+            Some((current, slot))
+        })?;
+        let info = slot.info();
+        if from != pool_name {
+            self.shift_active(&from, &info.status, -1);
+            self.shift_active(pool_name, &info.status, 1);
+        }
+        self.save();
+        Some(info)
+    }
+
+    pub fn tokens_needing_tier_check(&self, interval_hours: i64) -> Vec<String> {
+        self.pools()
+            .values()
+            .flat_map(|p| p.list())
+            .filter(|t| t.need_tier_check(interval_hours))
+            .map(|t| t.token)
+            .collect()
     }
 
-    pub async fn reset_token(&mut self, token_str: &str) -> bool {
+    pub async fn apply_tier(&self, token_str: &str, tier: Tier) -> bool {
         let raw = token_str.trim_start_matches("sso=");
-        for pool in self.pools.values_mut() {
-            if let Some(token) = pool.get_mut(raw) {
-                token.reset();
-                self.save().await;
-                return true;
+        let Some((current_pool, slot)) = self.find(raw) else {
+            return false;
+        };
+        let managed = [Tier::Basic.pool_name(), Tier::Super.pool_name()];
+        let target_pool = tier.pool_name();
+        slot.update(|tok| tok.record_tier_check(tier));
+        if current_pool != target_pool && managed.contains(&current_pool.as_str()) {
+            let moved = self.modify_pools(|pools| {
+                let Some(slot) = pools.get_mut(&current_pool).and_then(|p| p.remove(raw)) else {
+                    return false;
+                };
+                pools
+                    .entry(target_pool.to_string())
+                    .or_insert_with(|| TokenPool::new(target_pool))
+                    .add_slot(slot);
+                true
+            });
+            if moved {
+                let status = slot.read(|t| t.status.clone());
+                self.shift_active(&current_pool, &status, -1);
+                self.shift_active(target_pool, &status, 1);
+                tracing::info!(
+                    "Token {} moved {} -> {}",
+                    fingerprint(raw),
+                    current_pool,
+                    target_pool
+                );
             }
         }
-        false
+        self.save();
+        true
+    }
+
+    pub fn record_tier_failure(&self, token_str: &str, interval_hours: i64) {
+        let raw = token_str.trim_start_matches("sso=");
+        if let Some((_, slot)) = self.find(raw) {
+            slot.update(|tok| tok.record_tier_failure(interval_hours));
+            self.save();
+        }
+    }
+
+    pub async fn remove(&self, token: &str) -> Option<TokenInfo> {
+        let raw = token.trim_start_matches("sso=");
+        let (pool_name, removed) = self.modify_pools(|pools| {
+            pools
+                .iter_mut()
+                .find_map(|(name, pool)| pool.remove(raw).map(|slot| (name.clone(), slot)))
+        })?;
+        let info = removed.info();
+        self.shift_active(&pool_name, &info.status, -1);
+        self.save();
+        Some(info)
+    }
+
+    pub async fn reset_all(&self) {
+        for (name, pool) in self.pools().iter() {
+            for slot in pool.slots() {
+                self.update_tracked(name, slot, |t| t.reset());
+            }
+        }
+        self.save();
+    }
+
+    pub async fn reset_token(&self, token_str: &str) -> Option<TokenInfo> {
+        self.update_token(token_str, |t| t.reset()).await
     }
 
     pub fn get_stats(&self) -> HashMap<String, TokenPoolStats> {
         let mut stats = HashMap::new();
-        for (name, pool) in &self.pools {
+        for (name, pool) in self.pools().iter() {
             stats.insert(name.clone(), pool.stats());
         }
         stats
     }
 
+    pub fn next_recover_at(&self) -> Option<i64> {
+        self.pools()
+            .values()
+            .flat_map(|p| p.list())
+            .filter(|t| t.status == TokenStatus::Cooling)
+            .filter_map(|t| t.recover_at)
+            .min()
+    }
+
     pub fn get_pool_tokens(&self, pool_name: &str) -> Vec<TokenInfo> {
-        self.pools
+        self.pools()
             .get(pool_name)
             .map(|p| p.list())
             .unwrap_or_default()
@@ -247,50 +681,104 @@ impl TokenManager {
 
     pub fn has_tag(&self, token: &str, tag: &str) -> bool {
         let raw = token.trim_start_matches("sso=");
-        for pool in self.pools.values() {
-            if let Some(tok) = pool.get(raw) {
-                return tok.tags.iter().any(|t| t == tag);
+        self.find(raw)
+            .map(|(_, slot)| slot.read(|t| t.tags.iter().any(|t| t == tag)))
+            .unwrap_or(false)
+    }
+
+    pub async fn add_tag(&self, token: &str, tag: &str) -> bool {
+        let raw = token.trim_start_matches("sso=");
+        let Some((_, slot)) = self.find(raw) else {
+            return false;
+        };
+        let changed = slot.update(|tok| {
+            if tok.tags.iter().any(|t| t == tag) {
+                return false;
             }
+            tok.tags.push(tag.to_string());
+            true
+        });
+        if changed {
+            self.save();
         }
-        false
+        true
     }
 
-    pub async fn add_tag(&mut self, token: &str, tag: &str) -> bool {
+    pub async fn record_nsfw(&self, token: &str, status: NsfwStatus, age_verified: bool) -> bool {
         let raw = token.trim_start_matches("sso=");
-        for pool in self.pools.values_mut() {
-            if let Some(tok) = pool.get_mut(raw) {
-                if !tok.tags.contains(&tag.to_string()) {
-                    tok.tags.push(tag.to_string());
-                    self.save().await;
-                }
-                return true;
+        let Some((_, slot)) = self.find(raw) else {
+            return false;
+        };
+        let enabled = status.enabled;
+        slot.update(|tok| {
+            tok.imagine.nsfw = status;
+            tok.imagine.age_verified |= age_verified;
+        });
+        self.save();
+        if enabled {
+            self.add_tag(raw, "nsfw").await;
+        }
+        true
+    }
+
+    pub fn tokens_needing_nsfw(&self, retry_hours: i64) -> Vec<String> {
+        let now = chrono::Utc::now().timestamp_millis();
+        self.pools()
+            .values()
+            .flat_map(|p| p.list())
+            .filter(|t| t.need_nsfw_attempt(retry_hours, now))
+            .map(|t| t.token)
+            .collect()
+    }
+
+    /// Unique tokens the Imagine generator may rotate through, ignoring the
+    /// daily limit so callers can tell "all failed" from "all exhausted".
+    pub fn imagine_tokens(&self) -> Vec<TokenInfo> {
+        let now = chrono::Utc::now().timestamp_millis();
+        let mut seen = std::collections::HashSet::new();
+        self.pools()
+            .values()
+            .flat_map(|p| p.list())
+            .filter(|t| matches!(t.status, TokenStatus::Active | TokenStatus::Cooling))
+            .filter(|t| !t.is_past_expiry(now))
+            .filter(|t| seen.insert(t.token.clone()))
+            .collect()
+    }
+
+    pub async fn clear_imagine_failures(&self, tokens: &[String]) {
+        for token in tokens {
+            if let Some((_, slot)) = self.find(token) {
+                slot.update(|t| {
+                    t.imagine.failed = false;
+                    t.imagine.failed_reason = None;
+                });
             }
         }
-        false
+        self.save();
     }
 
-    pub async fn mark_asset_clear(&mut self, token: &str) -> bool {
+    pub async fn mark_asset_clear(&self, token: &str) -> bool {
         let raw = token.trim_start_matches("sso=");
-        for pool in self.pools.values_mut() {
-            if let Some(tok) = pool.get_mut(raw) {
-                tok.last_asset_clear_at = Some(chrono::Utc::now().timestamp_millis());
-                self.save().await;
-                return true;
+        match self.find(raw) {
+            Some((_, slot)) => {
+                slot.update(|t| {
+                    t.last_asset_clear_at = Some(chrono::Utc::now().timestamp_millis())
+                });
+                self.save();
+                true
             }
+            None => false,
         }
-        false
     }
 
-    pub async fn refresh_cooling_tokens(&mut self) -> HashMap<&'static str, i32> {
+    pub async fn refresh_cooling_tokens(&self) -> HashMap<&'static str, i32> {
         let interval_hours: i64 = get_config("token.refresh_interval_hours", 8i64).await;
-        let mut to_refresh = Vec::new();
-        for pool in self.pools.values() {
-            for token in pool.list() {
-                if token.need_refresh(interval_hours) {
-                    to_refresh.push(token.token.clone());
-                }
-            }
-        }
+        let to_refresh: Vec<(String, Arc<TokenSlot>)> = self
+            .pools()
+            .iter()
+            .flat_map(|(name, p)| p.slots().iter().map(|s| (name.clone(), s.clone())))
+            .filter(|(_, s)| s.read(|t| t.need_refresh(interval_hours)))
+            .collect();
         if to_refresh.is_empty() {
             return HashMap::from([
                 ("checked", 0),
@@ -303,40 +791,39 @@ impl TokenManager {
         let mut refreshed = 0;
         let mut recovered = 0;
         let mut expired = 0;
-        for token_str in to_refresh {
-            if let Ok(result) = usage.get(&token_str, "grok-3").await {
-                if let Some(remain) = result.get("remainingTokens").and_then(|v| v.as_i64()) {
-                    if let Some(pool) = self
-                        .pools
-                        .values_mut()
-                        .find(|p| p.get(&token_str).is_some())
-                    {
-                        if let Some(tok) = pool.get_mut(&token_str) {
+        for (pool_name, slot) in to_refresh {
+            match usage.get(slot.token(), "grok-3").await {
+                Ok(result) => {
+                    if let Some(remain) = result.get("remainingTokens").and_then(|v| v.as_i64()) {
+                        let was_recovered = self.update_tracked(&pool_name, &slot, |tok| {
                             let old_quota = tok.quota;
                             tok.update_quota(remain as i32);
+                            tok.set_recover_after(recover_after_secs(&result));
                             tok.mark_synced();
-                            if old_quota == 0 && tok.quota > 0 {
-                                recovered += 1;
-                            }
+                            old_quota == 0 && tok.quota > 0
+                        });
+                        if was_recovered {
+                            recovered += 1;
                         }
                     }
                 }
-            } else {
-                if let Some(pool) = self
-                    .pools
-                    .values_mut()
-                    .find(|p| p.get(&token_str).is_some())
-                {
-                    if let Some(tok) = pool.get_mut(&token_str) {
+                Err(err) => {
+                    events::emit(
+                        TokenEventKind::QuotaSyncFailed,
+                        Some(&pool_name),
+                        Some(slot.token()),
+                        serde_json::json!({"model": "grok-3", "error": err.to_string()}),
+                    );
+                    self.update_tracked(&pool_name, &slot, |tok| {
                         tok.status = TokenStatus::Expired;
                         tok.mark_synced();
-                        expired += 1;
-                    }
+                    });
+                    expired += 1;
                 }
             }
             refreshed += 1;
         }
-        self.save().await;
+        self.save();
         HashMap::from([
             ("checked", refreshed),
             ("refreshed", refreshed),
@@ -346,6 +833,24 @@ impl TokenManager {
     }
 }
 
+async fn check_quarantine(slot: &TokenSlot) {
+    let threshold: f64 = get_config("token.health_quarantine_score", 30f64).await;
+    let duration_sec: i64 = get_config("token.health_quarantine_sec", 300i64).await;
+    let quarantined = slot.update_health(|h| {
+        h.update_quarantine(threshold, duration_sec)
+            .then(|| h.score())
+    });
+    if let Some(score) = quarantined {
+        let raw = slot.token();
+        tracing::warn!(
+            "Token {} quarantined for {}s, health score {:.1}",
+            fingerprint(raw),
+            duration_sec,
+            score
+        );
+    }
+}
+
 fn token_from_value(v: &JsonValue) -> Option<TokenInfo> {
     let obj = v.as_object()?;
     let token = obj
@@ -383,6 +888,7 @@ fn token_from_value(v: &JsonValue) -> Option<TokenInfo> {
         .and_then(|v| v.as_str())
         .map(|s| s.to_string());
     let last_sync_at = obj.get("last_sync_at").and_then(|v| v.as_i64());
+    let recover_at = obj.get("recover_at").and_then(|v| v.as_i64());
     let tags = obj
         .get("tags")
         .and_then(|v| v.as_array())
@@ -398,8 +904,27 @@ fn token_from_value(v: &JsonValue) -> Option<TokenInfo> {
         .unwrap_or("")
         .to_string();
     let last_asset_clear_at = obj.get("last_asset_clear_at").and_then(|v| v.as_i64());
+    let tier = obj
+        .get("tier")
+        .and_then(|v| serde_json::from_value::<Tier>(v.clone()).ok());
+    let tier_checked_at = obj.get("tier_checked_at").and_then(|v| v.as_i64());
+    let tier_failures = obj
+        .get("tier_failures")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0) as u32;
+    let tier_retry_at = obj.get("tier_retry_at").and_then(|v| v.as_i64());
+    let imagine = obj
+        .get("imagine")
+        .and_then(|v| serde_json::from_value::<ImagineState>(v.clone()).ok())
+        .unwrap_or_default();
+    let text = |key: &str| {
+        obj.get(key)
+            .and_then(|v| v.as_str())
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+    };
 
-    Some(TokenInfo {
+    let mut info = TokenInfo {
         token: token.trim_start_matches("sso=").to_string(),
         status,
         quota,
@@ -410,22 +935,193 @@ fn token_from_value(v: &JsonValue) -> Option<TokenInfo> {
         last_fail_at,
         last_fail_reason,
         last_sync_at,
+        recover_at,
         tags,
         note,
         last_asset_clear_at,
-    })
+        tier,
+        tier_checked_at,
+        tier_failures,
+        tier_retry_at,
+        expires_at: None,
+        issued_at: None,
+        account_id: None,
+        session_id: None,
+        proxy_url: text("proxy_url"),
+        emulation: text("emulation"),
+        cf_clearance: text("cf_clearance"),
+        imagine,
+    };
+    info.apply_claims();
+    Some(info)
 }
 
-static MANAGER: OnceCell<std::sync::Arc<Mutex<TokenManager>>> = OnceCell::const_new();
+static MANAGER: OnceCell<Arc<TokenManager>> = OnceCell::const_new();
 
-pub async fn get_token_manager() -> std::sync::Arc<Mutex<TokenManager>> {
-    let mgr = MANAGER
-        .get_or_init(|| async { std::sync::Arc::new(Mutex::new(TokenManager::new())) })
-        .await
-        .clone();
+static FLUSH_NOTIFY: Notify = Notify::const_new();
+static FLUSH_LOCK: Mutex<()> = Mutex::const_new(());
+
+pub async fn flush_tokens() -> Result<(), StorageError> {
+    let _flush = FLUSH_LOCK.lock().await;
+    let mgr = get_token_manager().await;
+    if !mgr.has_unsaved() {
+        return Ok(());
+    }
+    let generation = mgr.dirty_gen.load(Ordering::SeqCst);
+    let local = mgr.snapshot();
+    let base = mgr.synced.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let storage = get_storage();
+    let result = storage
+        .with_lock("tokens_save", 10, || async {
+            let remote = storage.load_tokens().await?;
+            let merged = merge_tokens(&base, &local, &remote);
+            storage.save_tokens(&merged).await?;
+            Ok(merged)
+        })
+        .await;
+    match result {
+        Ok(merged) => {
+            mgr.saved_gen.fetch_max(generation, Ordering::SeqCst);
+            if merged != local {
+                mgr.reload_pending.store(true, Ordering::SeqCst);
+            }
+            *mgr.synced.lock().unwrap_or_else(|e| e.into_inner()) = merged;
+            if mgr.reload_pending.swap(false, Ordering::SeqCst) {
+                mgr.reload_unless_dirty().await;
+            }
+            Ok(())
+        }
+        Err(err) => {
+            tracing::warn!("Token flush failed: {err}");
+            FLUSH_NOTIFY.notify_one();
+            Err(err)
+        }
+    }
+}
+
+fn document(pools: &PoolMap) -> JsonValue {
+    let mut map = serde_json::Map::new();
+    for (name, pool) in pools.iter() {
+        let tokens: Vec<JsonValue> = pool
+            .list()
+            .into_iter()
+            .map(|t| serde_json::to_value(t).unwrap_or(JsonValue::Null))
+            .collect();
+        map.insert(name.clone(), JsonValue::Array(tokens));
+    }
+    JsonValue::Object(map)
+}
+
+fn index(doc: &JsonValue) -> Vec<(&str, &str, &JsonValue)> {
+    let mut out = Vec::new();
+    for (pool, list) in doc.as_object().into_iter().flatten() {
+        for item in list.as_array().into_iter().flatten() {
+            if let Some(token) = item.get("token").and_then(|v| v.as_str()) {
+                out.push((token, pool.as_str(), item));
+            }
+        }
+    }
+    out
+}
+
+/// Three-way merge by token: tokens this replica added or changed since
+/// `base` keep the local version, tokens it removed stay removed, and
+/// everything else follows `remote`.
+pub(crate) fn merge_tokens(base: &JsonValue, local: &JsonValue, remote: &JsonValue) -> JsonValue {
+    let base: HashMap<&str, (&str, &JsonValue)> = index(base)
+        .into_iter()
+        .map(|(token, pool, item)| (token, (pool, item)))
+        .collect();
+    let remote_items = index(remote);
+    let remote_by_token: HashMap<&str, (&str, &JsonValue)> = remote_items
+        .iter()
+        .map(|&(token, pool, item)| (token, (pool, item)))
+        .collect();
+    let local_items = index(local);
+
+    let mut merged = serde_json::Map::new();
+    for pool in [local, remote]
+        .iter()
+        .filter_map(|doc| doc.as_object())
+        .flat_map(|obj| obj.keys())
     {
-        let mut guard = mgr.lock().await;
-        guard.load().await;
+        merged
+            .entry(pool.clone())
+            .or_insert_with(|| JsonValue::Array(Vec::new()));
     }
-    mgr
+    let mut push = |pool: &str, item: &JsonValue| {
+        if let Some(list) = merged
+            .entry(pool.to_string())
+            .or_insert_with(|| JsonValue::Array(Vec::new()))
+            .as_array_mut()
+        {
+            list.push(item.clone());
+        }
+    };
+    for &(token, pool, item) in &local_items {
+        if base.get(token) != Some(&(pool, item)) {
+            push(pool, item);
+        } else if let Some(&(pool, item)) = remote_by_token.get(token) {
+            push(pool, item);
+        }
+    }
+    for &(token, pool, item) in &remote_items {
+        let known = base.contains_key(token) || local_items.iter().any(|(t, _, _)| *t == token);
+        if !known {
+            push(pool, item);
+        }
+    }
+    JsonValue::Object(merged)
+}
+
+/// Reloads tokens as soon as another replica saves them, when the storage
+/// backend can report changes.
+fn start_change_watcher() {
+    let Some(mut changes) = get_storage().subscribe_changes() else {
+        return;
+    };
+    tokio::spawn(async move {
+        loop {
+            match changes.recv().await {
+                Ok(StorageChange::Tokens) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+            get_token_manager().await.reload_unless_dirty().await;
+        }
+    });
+}
+
+fn start_flusher() {
+    tokio::spawn(async {
+        loop {
+            FLUSH_NOTIFY.notified().await;
+            let delay_ms: u64 = get_config("token.save_delay_ms", 500u64).await;
+            tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+            let _ = flush_tokens().await;
+        }
+    });
+}
+
+/// Applies `token.pool_low_active` now and whenever it changes.
+pub fn start_pool_level_watcher() {
+    on_change(&["token.pool_low_active"], || async {
+        let threshold: usize = get_config("token.pool_low_active", 0usize).await;
+        get_token_manager()
+            .await
+            .set_low_active_threshold(threshold);
+    });
+}
+
+pub async fn get_token_manager() -> Arc<TokenManager> {
+    MANAGER
+        .get_or_init(|| async {
+            start_flusher();
+            start_change_watcher();
+            let mgr = TokenManager::new();
+            mgr.load(None).await;
+            Arc::new(mgr)
+        })
+        .await
+        .clone()
 }
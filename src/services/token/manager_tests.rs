@@ -0,0 +1,57 @@
+use serde_json::{Value as JsonValue, json};
+
+use crate::services::token::manager::merge_tokens;
+
+fn token(name: &str, quota: u64) -> JsonValue {
+    json!({ "token": name, "quota": quota })
+}
+
+fn names(doc: &JsonValue, pool: &str) -> Vec<String> {
+    let mut out: Vec<String> = doc[pool]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["token"].as_str().unwrap().to_string())
+        .collect();
+    out.sort();
+    out
+}
+
+#[test]
+fn keeps_tokens_added_on_either_side() {
+    let base = json!({ "ssoBasic": [token("a", 80)] });
+    let local = json!({ "ssoBasic": [token("a", 80), token("b", 80)] });
+    let remote = json!({ "ssoBasic": [token("a", 80), token("c", 80)], "ssoSuper": [] });
+    let merged = merge_tokens(&base, &local, &remote);
+    assert_eq!(names(&merged, "ssoBasic"), ["a", "b", "c"]);
+    assert_eq!(merged["ssoSuper"], json!([]));
+}
+
+#[test]
+fn local_edits_win_and_untouched_tokens_follow_remote() {
+    let base = json!({ "ssoBasic": [token("a", 80), token("b", 80)] });
+    let local = json!({ "ssoBasic": [token("a", 10), token("b", 80)] });
+    let remote = json!({ "ssoBasic": [token("a", 80), token("b", 20)] });
+    let merged = merge_tokens(&base, &local, &remote);
+    assert_eq!(
+        merged,
+        json!({ "ssoBasic": [token("a", 10), token("b", 20)] })
+    );
+}
+
+#[test]
+fn removals_on_either_side_stick() {
+    let base = json!({ "ssoBasic": [token("a", 80), token("b", 80)] });
+    let local = json!({ "ssoBasic": [token("b", 80)] });
+    let remote = json!({ "ssoBasic": [token("a", 80)] });
+    let merged = merge_tokens(&base, &local, &remote);
+    assert_eq!(merged, json!({ "ssoBasic": [] }));
+}
+
+#[test]
+fn moving_a_token_between_pools_is_a_local_edit() {
+    let base = json!({ "ssoBasic": [token("a", 80)], "ssoSuper": [] });
+    let local = json!({ "ssoBasic": [], "ssoSuper": [token("a", 80)] });
+    let merged = merge_tokens(&base, &local, &base);
+    assert_eq!(merged, local);
+}
@@ -1,9 +1,30 @@
+#[cfg(test)]
+mod bench;
+pub mod events;
+pub mod health;
+#[cfg(test)]
+mod health_tests;
+pub mod jwt;
+#[cfg(test)]
+mod jwt_tests;
 pub mod manager;
+#[cfg(test)]
+mod manager_tests;
 pub mod models;
+#[cfg(test)]
+mod models_tests;
+pub mod nsfw;
 pub mod pool;
 pub mod scheduler;
 pub mod service;
+pub mod tier;
+pub mod transfer;
+#[cfg(test)]
+mod transfer_tests;
+pub mod webhook;
+#[cfg(test)]
+mod webhook_tests;
 
 pub use manager::get_token_manager;
-pub use models::{EffortType, TokenInfo, TokenStatus};
+pub use models::{EffortType, TagFilter, TokenInfo, TokenRoute, TokenStatus};
 pub use service::TokenService;
@@ -1,5 +1,8 @@
 use serde::{Deserialize, Serialize};
 
+use crate::services::grok::model::Tier;
+use crate::services::token::jwt::decode_claims;
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum TokenStatus {
@@ -25,6 +28,78 @@ pub fn effort_cost(effort: &EffortType) -> i32 {
 
 pub const DEFAULT_QUOTA: i32 = 80;
 pub const FAIL_THRESHOLD: i32 = 5;
+pub const IMAGINE_WINDOW_MS: i64 = 86_400_000;
+pub const TIER_RETRY_BASE_MS: i64 = 300_000;
+
+/// Imagine NSFW generation counters; the daily window starts at the first
+/// generation after the previous window elapsed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ImagineState {
+    pub daily_count: i32,
+    pub window_started_at: Option<i64>,
+    pub last_used_at: Option<i64>,
+    pub failed: bool,
+    pub failed_reason: Option<String>,
+    pub age_verified: bool,
+    pub nsfw: NsfwStatus,
+}
+
+/// Outcome of the last NSFW enable attempt. `verified` is only set when the
+/// feature controls read back from upstream confirm the flag.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct NsfwStatus {
+    pub enabled: bool,
+    pub verified: bool,
+    pub attempted_at: Option<i64>,
+    pub verified_at: Option<i64>,
+    pub error: Option<String>,
+}
+
+impl ImagineState {
+    fn window_expired(&self, now: i64) -> bool {
+        self.window_started_at
+            .is_some_and(|start| now - start >= IMAGINE_WINDOW_MS)
+    }
+
+    pub fn is_failed(&self, now: i64) -> bool {
+        self.failed && !self.window_expired(now)
+    }
+
+    pub fn remaining(&self, daily_limit: i32, now: i64) -> i32 {
+        let used = if self.window_expired(now) {
+            0
+        } else {
+            self.daily_count
+        };
+        (daily_limit - used).max(0)
+    }
+
+    pub fn record_use(&mut self, now: i64) {
+        if self.window_expired(now) {
+            self.daily_count = 0;
+            self.window_started_at = None;
+        }
+        self.window_started_at.get_or_insert(now);
+        self.daily_count += 1;
+        self.last_used_at = Some(now);
+        self.failed = false;
+        self.failed_reason = None;
+    }
+
+    /// Failures are cleared together with the daily count when the window
+    /// rolls over.
+    pub fn mark_failed(&mut self, reason: &str, now: i64) {
+        if self.window_expired(now) {
+            self.daily_count = 0;
+            self.window_started_at = None;
+        }
+        self.window_started_at.get_or_insert(now);
+        self.failed = true;
+        self.failed_reason = Some(reason.to_string());
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenInfo {
@@ -41,15 +116,33 @@ pub struct TokenInfo {
     pub last_fail_reason: Option<String>,
 
     pub last_sync_at: Option<i64>,
+    pub recover_at: Option<i64>,
 
     pub tags: Vec<String>,
     pub note: String,
     pub last_asset_clear_at: Option<i64>,
+
+    pub tier: Option<Tier>,
+    pub tier_checked_at: Option<i64>,
+    /// Consecutive failed tier probes; retries back off until one succeeds.
+    pub tier_failures: u32,
+    pub tier_retry_at: Option<i64>,
+
+    pub expires_at: Option<i64>,
+    pub issued_at: Option<i64>,
+    pub account_id: Option<String>,
+    pub session_id: Option<String>,
+
+    pub proxy_url: Option<String>,
+    pub emulation: Option<String>,
+    pub cf_clearance: Option<String>,
+
+    pub imagine: ImagineState,
 }
 
 impl TokenInfo {
     pub fn new(token: String) -> Self {
-        Self {
+        let mut info = Self {
             token,
             status: TokenStatus::Active,
             quota: DEFAULT_QUOTA,
@@ -60,14 +153,82 @@ impl TokenInfo {
             last_fail_at: None,
             last_fail_reason: None,
             last_sync_at: None,
+            recover_at: None,
             tags: Vec::new(),
             note: String::new(),
             last_asset_clear_at: None,
+            tier: None,
+            tier_checked_at: None,
+            tier_failures: 0,
+            tier_retry_at: None,
+            expires_at: None,
+            issued_at: None,
+            account_id: None,
+            session_id: None,
+            proxy_url: None,
+            emulation: None,
+            cf_clearance: None,
+            imagine: ImagineState::default(),
+        };
+        info.apply_claims();
+        info
+    }
+
+    pub fn apply_claims(&mut self) {
+        if let Some(claims) = decode_claims(&self.token) {
+            self.expires_at = claims.expires_at;
+            self.issued_at = claims.issued_at;
+            self.account_id = claims.account_id;
+            self.session_id = claims.session_id;
         }
     }
 
+    pub fn binding(&self) -> TokenBinding {
+        TokenBinding {
+            proxy_url: self.proxy_url.clone(),
+            emulation: self.emulation.clone(),
+            cf_clearance: self.cf_clearance.clone(),
+        }
+    }
+
+    pub fn is_past_expiry(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|at| at <= now)
+    }
+
+    pub fn expire_if_past(&mut self, now: i64) -> bool {
+        if !self.is_past_expiry(now)
+            || matches!(self.status, TokenStatus::Expired | TokenStatus::Disabled)
+        {
+            return false;
+        }
+        self.status = TokenStatus::Expired;
+        self.last_fail_reason = Some("jwt expired".to_string());
+        true
+    }
+
+    /// Imagine has its own daily limit, so cooling tokens stay eligible;
+    /// disabled and expired ones do not.
+    pub fn can_imagine(&self, daily_limit: i32, now: i64) -> bool {
+        matches!(self.status, TokenStatus::Active | TokenStatus::Cooling)
+            && !self.is_past_expiry(now)
+            && !self.imagine.is_failed(now)
+            && self.imagine.remaining(daily_limit, now) > 0
+    }
+
+    pub fn need_nsfw_attempt(&self, retry_hours: i64, now: i64) -> bool {
+        self.status == TokenStatus::Active
+            && !self.imagine.nsfw.enabled
+            && self
+                .imagine
+                .nsfw
+                .attempted_at
+                .is_none_or(|at| now - at >= retry_hours * 3600 * 1000)
+    }
+
     pub fn is_available(&self) -> bool {
-        self.status == TokenStatus::Active && self.quota > 0
+        self.status == TokenStatus::Active
+            && self.quota > 0
+            && !self.is_past_expiry(chrono::Utc::now().timestamp_millis())
     }
 
     pub fn consume(&mut self, effort: &EffortType) -> i32 {
@@ -90,18 +251,42 @@ impl TokenInfo {
         self.quota = new_quota.max(0);
         if self.quota == 0 {
             self.status = TokenStatus::Cooling;
-        } else if matches!(self.status, TokenStatus::Cooling | TokenStatus::Expired) {
-            self.status = TokenStatus::Active;
+        } else {
+            self.recover_at = None;
+            if matches!(self.status, TokenStatus::Cooling | TokenStatus::Expired) {
+                self.status = TokenStatus::Active;
+            }
         }
     }
 
+    pub fn set_recover_after(&mut self, wait_secs: Option<i64>) -> bool {
+        self.recover_at = match wait_secs {
+            Some(secs) if secs > 0 && self.quota == 0 => {
+                Some(chrono::Utc::now().timestamp_millis() + secs * 1000)
+            }
+            _ => None,
+        };
+        self.recover_at.is_some()
+    }
+
     pub fn reset(&mut self) {
         self.quota = DEFAULT_QUOTA;
         self.status = TokenStatus::Active;
+        self.recover_at = None;
         self.fail_count = 0;
         self.last_fail_reason = None;
     }
 
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.status = if !enabled {
+            TokenStatus::Disabled
+        } else if self.quota > 0 {
+            TokenStatus::Active
+        } else {
+            TokenStatus::Cooling
+        };
+    }
+
     pub fn record_fail(&mut self, status_code: u16, reason: &str) {
         if status_code != 401 {
             return;
@@ -133,19 +318,136 @@ impl TokenInfo {
         if self.status != TokenStatus::Cooling {
             return false;
         }
+        let now = chrono::Utc::now().timestamp_millis();
+        if let Some(recover_at) = self.recover_at {
+            return now >= recover_at;
+        }
         if self.last_sync_at.is_none() {
             return true;
         }
-        let now = chrono::Utc::now().timestamp_millis();
         let interval_ms = interval_hours * 3600 * 1000;
         now - self.last_sync_at.unwrap_or(0) >= interval_ms
     }
 
+    pub fn need_tier_check(&self, interval_hours: i64) -> bool {
+        let now = chrono::Utc::now().timestamp_millis();
+        if self.tier_retry_at.is_some_and(|at| now < at) {
+            return false;
+        }
+        match self.tier_checked_at {
+            None => true,
+            Some(checked_at) => now - checked_at >= interval_hours * 3600 * 1000,
+        }
+    }
+
+    pub fn record_tier_check(&mut self, tier: Tier) {
+        self.tier = Some(tier);
+        self.tier_checked_at = Some(chrono::Utc::now().timestamp_millis());
+        self.tier_failures = 0;
+        self.tier_retry_at = None;
+    }
+
+    /// Retries double from `TIER_RETRY_BASE_MS`, capped at the regular interval.
+    pub fn record_tier_failure(&mut self, interval_hours: i64) {
+        self.tier_failures = self.tier_failures.saturating_add(1);
+        let exponent = (self.tier_failures - 1).min(16);
+        let backoff = (TIER_RETRY_BASE_MS << exponent).min(interval_hours.max(1) * 3600 * 1000);
+        self.tier_retry_at = Some(chrono::Utc::now().timestamp_millis() + backoff);
+    }
+
     pub fn mark_synced(&mut self) {
         self.last_sync_at = Some(chrono::Utc::now().timestamp_millis());
     }
 }
 
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TokenBinding {
+    pub proxy_url: Option<String>,
+    pub emulation: Option<String>,
+    pub cf_clearance: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagFilter {
+    pub required: Vec<String>,
+    pub excluded: Vec<String>,
+}
+
+impl TagFilter {
+    pub fn parse(expr: &str) -> Self {
+        let mut filter = Self::default();
+        for part in expr.split(',') {
+            let part = part.trim();
+            if let Some(tag) = part.strip_prefix('-').or_else(|| part.strip_prefix('!')) {
+                let tag = tag.trim();
+                if !tag.is_empty() && !filter.excluded.iter().any(|t| t == tag) {
+                    filter.excluded.push(tag.to_string());
+                }
+            } else if !part.is_empty() && !filter.required.iter().any(|t| t == part) {
+                filter.required.push(part.to_string());
+            }
+        }
+        filter
+    }
+
+    pub fn merge(mut self, other: TagFilter) -> Self {
+        for tag in other.required {
+            if !self.required.contains(&tag) {
+                self.required.push(tag);
+            }
+        }
+        for tag in other.excluded {
+            if !self.excluded.contains(&tag) {
+                self.excluded.push(tag);
+            }
+        }
+        self
+    }
+
+    /// Reserved tags stay excluded unless the key's policy requires them, so
+    /// the header can only narrow what the policy allows.
+    pub fn for_request(policy: Option<&str>, header: Option<&str>, reserved: &[String]) -> Self {
+        let policy = policy.map(TagFilter::parse).unwrap_or_default();
+        let withheld = reserved
+            .iter()
+            .filter(|tag| !policy.required.contains(tag))
+            .cloned()
+            .collect();
+        policy
+            .merge(header.map(TagFilter::parse).unwrap_or_default())
+            .merge(TagFilter {
+                required: Vec::new(),
+                excluded: withheld,
+            })
+    }
+
+    pub fn matches(&self, tags: &[String]) -> bool {
+        self.required.iter().all(|t| tags.contains(t))
+            && !self.excluded.iter().any(|t| tags.contains(t))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionStrategy {
+    Quota,
+    Health,
+}
+
+impl SelectionStrategy {
+    pub fn parse(raw: &str) -> Self {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "health" => SelectionStrategy::Health,
+            _ => SelectionStrategy::Quota,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TokenRoute {
+    pub tags: TagFilter,
+    pub session: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenPoolStats {
     pub total: usize,
@@ -0,0 +1,59 @@
+use crate::services::grok::model::Tier;
+use crate::services::token::models::{TIER_RETRY_BASE_MS, TagFilter, TokenInfo};
+
+fn reserved() -> Vec<String> {
+    vec!["vip".to_string(), "test".to_string()]
+}
+
+fn tags(list: &[&str]) -> Vec<String> {
+    list.iter().map(|t| t.to_string()).collect()
+}
+
+#[test]
+fn reserved_tags_are_withheld_without_a_grant() {
+    let filter = TagFilter::for_request(None, None, &reserved());
+    assert!(filter.matches(&tags(&["basic"])));
+    assert!(!filter.matches(&tags(&["vip"])));
+    assert!(!filter.matches(&tags(&["test"])));
+}
+
+#[test]
+fn header_cannot_opt_into_reserved_tags() {
+    let filter = TagFilter::for_request(Some("-test"), Some("vip"), &reserved());
+    assert!(!filter.matches(&tags(&["vip"])));
+    assert!(!filter.matches(&tags(&["basic"])));
+}
+
+#[test]
+fn policy_grants_reserved_tags_and_header_narrows() {
+    let filter = TagFilter::for_request(Some("vip"), None, &reserved());
+    assert!(filter.matches(&tags(&["vip"])));
+    assert!(!filter.matches(&tags(&["vip", "test"])));
+
+    let narrowed = TagFilter::for_request(Some("vip"), Some("fast,-slow"), &reserved());
+    assert!(narrowed.matches(&tags(&["vip", "fast"])));
+    assert!(!narrowed.matches(&tags(&["vip", "fast", "slow"])));
+    assert!(!narrowed.matches(&tags(&["vip"])));
+}
+
+#[test]
+fn failed_tier_probes_back_off_until_a_success() {
+    let mut info = TokenInfo::new("raw".to_string());
+    assert!(info.need_tier_check(24));
+
+    info.record_tier_failure(24);
+    assert!(!info.need_tier_check(24));
+    let first = info.tier_retry_at.unwrap();
+    info.record_tier_failure(24);
+    let second = info.tier_retry_at.unwrap();
+    assert!(second - first >= TIER_RETRY_BASE_MS);
+    for _ in 0..20 {
+        info.record_tier_failure(1);
+    }
+    let now = chrono::Utc::now().timestamp_millis();
+    assert!(info.tier_retry_at.unwrap() <= now + 3_600_000);
+
+    info.record_tier_check(Tier::Basic);
+    assert_eq!(info.tier_failures, 0);
+    assert!(!info.need_tier_check(24));
+}
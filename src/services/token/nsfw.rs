@@ -0,0 +1,117 @@
+use std::time::Duration;
+
+use serde_json::{Value as JsonValue, json};
+use tokio::sync::Mutex;
+
+use crate::core::config::get_config;
+use crate::services::grok::batch::run_in_batches;
+use crate::services::grok::nsfw::NsfwService;
+use crate::services::token::manager::get_token_manager;
+use crate::services::token::models::NsfwStatus;
+
+const NSFW_POLL_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Age-verifies, enables NSFW, reads the feature controls back and records
+/// the outcome on the token.
+pub async fn enable_and_record(service: &NsfwService, token: &str) -> JsonValue {
+    let age = service.verify_age(token).await;
+    let result = service.enable(token).await;
+    let now = chrono::Utc::now().timestamp_millis();
+    let verified = if result.success {
+        service.verify(token).await.map_err(|e| e.to_string())
+    } else {
+        Ok(false)
+    };
+
+    let error = match (&result.error, &verified) {
+        (Some(err), _) => Some(err.clone()),
+        (None, Err(err)) => Some(format!("verify failed: {err}")),
+        (None, Ok(false)) => Some("feature controls do not report NSFW enabled".to_string()),
+        (None, Ok(true)) => None,
+    };
+    let is_verified = verified.as_ref().is_ok_and(|v| *v);
+    let status = NsfwStatus {
+        enabled: result.success,
+        verified: is_verified,
+        attempted_at: Some(now),
+        verified_at: verified.is_ok().then_some(now),
+        error: error.clone(),
+    };
+    get_token_manager()
+        .await
+        .record_nsfw(token, status, age.success)
+        .await;
+
+    json!({
+        "success": result.success,
+        "verified": is_verified,
+        "age_verified": age.success,
+        "http_status": result.http_status,
+        "grpc_status": result.grpc_status,
+        "grpc_message": result.grpc_message,
+        "error": error,
+    })
+}
+
+pub async fn enable_nsfw(tokens: Vec<String>) {
+    if tokens.is_empty() {
+        return;
+    }
+    let max_concurrent: usize = get_config("performance.nsfw_max_concurrent", 10usize).await;
+    let batch_size: usize = get_config("performance.nsfw_batch_size", 50usize).await;
+    let service = NsfwService::new().await;
+    let results = run_in_batches(
+        tokens,
+        move |token| {
+            let service = service.clone();
+            async move { Ok::<_, String>(enable_and_record(&service, &token).await) }
+        },
+        max_concurrent,
+        batch_size,
+        None,
+        None,
+    )
+    .await;
+
+    let ok = results
+        .values()
+        .filter(|r| {
+            r.as_ref()
+                .is_ok_and(|v| v.get("verified").and_then(|v| v.as_bool()) == Some(true))
+        })
+        .count();
+    tracing::info!("Auto NSFW enable: {}/{} verified", ok, results.len());
+}
+
+static PENDING_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// Concurrent triggers collapse into the running pass, which keeps going
+/// until no token is pending; every attempt stamps `attempted_at`, so it ends.
+pub async fn enable_pending_nsfw() {
+    let auto_nsfw: bool = get_config("token.auto_nsfw", false).await;
+    if !auto_nsfw {
+        return;
+    }
+    let Ok(_guard) = PENDING_LOCK.try_lock() else {
+        return;
+    };
+    let retry_hours: i64 = get_config("token.nsfw_retry_hours", 24i64).await;
+    loop {
+        let tokens = get_token_manager().await.tokens_needing_nsfw(retry_hours);
+        if tokens.is_empty() {
+            break;
+        }
+        enable_nsfw(tokens).await;
+    }
+}
+
+/// Retries pending tokens on their own timer so a slow pass never delays
+/// the recovery loop.
+pub fn start_nsfw_enabler() {
+    tokio::spawn(async {
+        loop {
+            enable_pending_nsfw().await;
+            tokio::time::sleep(NSFW_POLL_INTERVAL).await;
+        }
+    });
+}
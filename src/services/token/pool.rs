@@ -1,11 +1,76 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
 use rand::seq::SliceRandom;
 
-use crate::services::token::models::{TokenInfo, TokenPoolStats, TokenStatus};
+use crate::services::token::health::TokenHealth;
+use crate::services::token::models::{
+    SelectionStrategy, TagFilter, TokenInfo, TokenPoolStats, TokenStatus,
+};
+
+#[derive(Debug)]
+struct SlotState {
+    info: TokenInfo,
+    health: TokenHealth,
+}
+
+#[derive(Debug)]
+pub struct TokenSlot {
+    token: String,
+    state: Mutex<SlotState>,
+}
+
+impl TokenSlot {
+    pub fn new(info: TokenInfo) -> Self {
+        Self::with_health(info, TokenHealth::default())
+    }
+
+    pub fn with_health(info: TokenInfo, health: TokenHealth) -> Self {
+        Self {
+            token: info.token.clone(),
+            state: Mutex::new(SlotState { info, health }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SlotState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    pub fn info(&self) -> TokenInfo {
+        self.lock().info.clone()
+    }
+
+    pub fn health(&self) -> TokenHealth {
+        self.lock().health.clone()
+    }
+
+    pub fn read<R>(&self, f: impl FnOnce(&TokenInfo) -> R) -> R {
+        f(&self.lock().info)
+    }
+
+    pub fn update<R>(&self, f: impl FnOnce(&mut TokenInfo) -> R) -> R {
+        f(&mut self.lock().info)
+    }
+
+    pub fn update_health<R>(&self, f: impl FnOnce(&mut TokenHealth) -> R) -> R {
+        f(&mut self.lock().health)
+    }
+
+    pub fn is_usable(&self, tags: &TagFilter, now: i64) -> bool {
+        let state = self.lock();
+        state.info.is_available()
+            && tags.matches(&state.info.tags)
+            && !state.health.is_quarantined(now)
+    }
+}
 
 #[derive(Debug, Default, Clone)]
 pub struct TokenPool {
     pub name: String,
-    tokens: Vec<TokenInfo>,
+    tokens: Vec<Arc<TokenSlot>>,
 }
 
 impl TokenPool {
@@ -17,40 +82,64 @@ impl TokenPool {
     }
 
     pub fn add(&mut self, token: TokenInfo) {
-        self.tokens.push(token);
+        self.tokens.push(Arc::new(TokenSlot::new(token)));
     }
 
-    pub fn remove(&mut self, token: &str) -> bool {
-        let before = self.tokens.len();
-        self.tokens.retain(|t| t.token != token);
-        before != self.tokens.len()
+    pub fn add_slot(&mut self, slot: Arc<TokenSlot>) {
+        self.tokens.push(slot);
     }
 
-    pub fn get(&self, token: &str) -> Option<TokenInfo> {
-        self.tokens.iter().find(|t| t.token == token).cloned()
+    pub fn remove(&mut self, token: &str) -> Option<Arc<TokenSlot>> {
+        let idx = self.tokens.iter().position(|s| s.token() == token)?;
+        Some(self.tokens.remove(idx))
     }
 
-    pub fn get_mut(&mut self, token: &str) -> Option<&mut TokenInfo> {
-        self.tokens.iter_mut().find(|t| t.token == token)
+    pub fn slot(&self, token: &str) -> Option<&Arc<TokenSlot>> {
+        self.tokens.iter().find(|s| s.token() == token)
+    }
+
+    pub fn slots(&self) -> &[Arc<TokenSlot>] {
+        &self.tokens
+    }
+
+    pub fn get(&self, token: &str) -> Option<TokenInfo> {
+        self.slot(token).map(|s| s.info())
     }
 
     pub fn list(&self) -> Vec<TokenInfo> {
-        self.tokens.clone()
+        self.tokens.iter().map(|s| s.info()).collect()
     }
 
-    pub fn select(&self) -> Option<TokenInfo> {
-        let mut available: Vec<_> = self
-            .tokens
-            .iter()
-            .filter(|t| t.status == TokenStatus::Active && t.quota > 0)
-            .collect();
+    pub fn select(&self, tags: &TagFilter, strategy: SelectionStrategy) -> Option<String> {
+        let now = chrono::Utc::now().timestamp_millis();
+        let mut available: Vec<(&str, i32, f64)> = Vec::new();
+        for slot in &self.tokens {
+            let state = slot.lock();
+            if state.info.status != TokenStatus::Active
+                || state.info.quota <= 0
+                || state.info.is_past_expiry(now)
+            {
+                continue;
+            }
+            if !tags.matches(&state.info.tags) || state.health.is_quarantined(now) {
+                continue;
+            }
+            let score = state.health.score().max(1.0);
+            available.push((slot.token(), state.info.quota, score));
+        }
         if available.is_empty() {
             return None;
         }
-        let max_quota = available.iter().map(|t| t.quota).max().unwrap_or(0);
-        available.retain(|t| t.quota == max_quota);
         let mut rng = rand::thread_rng();
-        available.choose(&mut rng).cloned().cloned()
+        if strategy == SelectionStrategy::Health {
+            return available
+                .choose_weighted(&mut rng, |t| t.2)
+                .ok()
+                .map(|t| t.0.to_string());
+        }
+        let max_quota = available.iter().map(|t| t.1).max().unwrap_or(0);
+        available.retain(|t| t.1 == max_quota);
+        available.choose(&mut rng).map(|t| t.0.to_string())
     }
 
     pub fn count(&self) -> usize {
@@ -60,9 +149,10 @@ impl TokenPool {
     pub fn stats(&self) -> TokenPoolStats {
         let mut stats = TokenPoolStats::default();
         stats.total = self.tokens.len();
-        for t in &self.tokens {
-            stats.total_quota += t.quota;
-            match t.status {
+        for slot in &self.tokens {
+            let state = slot.lock();
+            stats.total_quota += state.info.quota;
+            match state.info.status {
                 TokenStatus::Active => stats.active += 1,
                 TokenStatus::Disabled => stats.disabled += 1,
                 TokenStatus::Expired => stats.expired += 1,
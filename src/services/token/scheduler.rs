@@ -1,10 +1,19 @@
 use std::sync::Arc;
 
+use tokio::sync::Notify;
 use tokio::task::JoinHandle;
 
-use crate::core::config::get_config;
+use crate::core::config::{get_config, on_change};
 use crate::services::token::manager::get_token_manager;
 
+const MIN_WAKE_INTERVAL_MS: i64 = 1000;
+
+static RECOVER_NOTIFY: Notify = Notify::const_new();
+
+pub fn notify_recover_scheduled() {
+    RECOVER_NOTIFY.notify_one();
+}
+
 pub struct TokenRefreshScheduler {
     interval_hours: i64,
     handle: Option<JoinHandle<()>>,
@@ -25,12 +34,21 @@ impl TokenRefreshScheduler {
             return;
         }
         self.running = true;
-        let interval_secs = self.interval_hours.max(1) as u64 * 3600;
+        let interval_ms = self.interval_hours.max(1) * 3600 * 1000;
         self.handle = Some(tokio::spawn(async move {
             loop {
-                tokio::time::sleep(std::time::Duration::from_secs(interval_secs)).await;
                 let mgr = get_token_manager().await;
-                let mut mgr = mgr.lock().await;
+                mgr.expire_by_claims();
+                let next_recover_at = mgr.next_recover_at();
+                let wait_ms = match next_recover_at {
+                    Some(at) => (at - chrono::Utc::now().timestamp_millis())
+                        .clamp(MIN_WAKE_INTERVAL_MS, interval_ms),
+                    None => interval_ms,
+                };
+                tokio::select! {
+                    _ = tokio::time::sleep(std::time::Duration::from_millis(wait_ms as u64)) => {}
+                    _ = RECOVER_NOTIFY.notified() => continue,
+                }
                 let _ = mgr.refresh_cooling_tokens().await;
             }
         }));
@@ -42,6 +60,19 @@ impl TokenRefreshScheduler {
             handle.abort();
         }
     }
+
+    /// Restarts a running loop so the new interval takes effect immediately.
+    pub fn set_interval(&mut self, interval_hours: i64) {
+        if interval_hours == self.interval_hours {
+            return;
+        }
+        self.interval_hours = interval_hours;
+        if self.running {
+            self.stop();
+            self.start();
+            tracing::info!("Token refresh scheduler restarted every {interval_hours}h");
+        }
+    }
 }
 
 static SCHEDULER: tokio::sync::OnceCell<Arc<tokio::sync::Mutex<TokenRefreshScheduler>>> =
@@ -49,13 +80,32 @@ static SCHEDULER: tokio::sync::OnceCell<Arc<tokio::sync::Mutex<TokenRefreshSched
 
 pub async fn get_scheduler() -> Arc<tokio::sync::Mutex<TokenRefreshScheduler>> {
     let interval: i64 = get_config("token.refresh_interval_hours", 8i64).await;
-    let scheduler = SCHEDULER
+    SCHEDULER
         .get_or_init(|| async {
             Arc::new(tokio::sync::Mutex::new(TokenRefreshScheduler::new(
                 interval,
             )))
         })
         .await
-        .clone();
-    scheduler
+        .clone()
+}
+
+/// Starts or stops the scheduler with `token.auto_refresh` and applies
+/// `token.refresh_interval_hours`, now and whenever either changes.
+pub fn start_scheduler_watcher() {
+    on_change(
+        &["token.auto_refresh", "token.refresh_interval_hours"],
+        || async {
+            let auto_refresh: bool = get_config("token.auto_refresh", true).await;
+            let interval: i64 = get_config("token.refresh_interval_hours", 8i64).await;
+            let scheduler = get_scheduler().await;
+            let mut scheduler = scheduler.lock().await;
+            scheduler.set_interval(interval);
+            if auto_refresh {
+                scheduler.start();
+            } else {
+                scheduler.stop();
+            }
+        },
+    );
 }
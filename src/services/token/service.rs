@@ -1,23 +1,138 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use axum::http::HeaderMap;
+use once_cell::sync::Lazy;
+use serde_json::Value as JsonValue;
+use sha1::{Digest, Sha1};
+use tokio::sync::Mutex;
+
+use crate::core::auth::extract_bearer;
+use crate::core::config::get_config;
 use crate::core::exceptions::ApiError;
 use crate::services::grok::model::ModelService;
+use crate::services::token::health::ErrorClass;
 use crate::services::token::manager::get_token_manager;
-use crate::services::token::models::EffortType;
+use crate::services::token::models::{EffortType, SelectionStrategy, TagFilter, TokenRoute};
+
+const TOKEN_TAGS_HEADER: &str = "x-grok-token-tags";
+const SESSION_HEADER: &str = "x-grok-session-id";
+
+struct StickyToken {
+    token: String,
+    expires_at: i64,
+}
+
+static SESSIONS: Lazy<Mutex<HashMap<String, StickyToken>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
 
 pub struct TokenService;
 
 impl TokenService {
-    pub async fn get_token_for_model(model: &str) -> Result<String, ApiError> {
+    pub async fn route_from_headers(headers: &HeaderMap) -> TokenRoute {
+        let policies: serde_json::Map<String, serde_json::Value> =
+            get_config("app.api_key_tags", serde_json::Map::new()).await;
+        let policy = extract_bearer(headers).and_then(|key| {
+            policies
+                .get(&key)
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+        });
+        let reserved: Vec<String> = get_config("token.reserved_tags", Vec::new()).await;
+        let header = headers.get(TOKEN_TAGS_HEADER).and_then(|v| v.to_str().ok());
+        let tags = TagFilter::for_request(policy.as_deref(), header, &reserved);
+        let session = headers
+            .get(SESSION_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+            .map(|v| format!("header:{v}"));
+        TokenRoute { tags, session }
+    }
+
+    pub fn bind_session(route: &mut TokenRoute, user: Option<&str>, messages: &[JsonValue]) {
+        if route.session.is_some() {
+            return;
+        }
+        if let Some(user) = user.map(|u| u.trim()).filter(|u| !u.is_empty()) {
+            route.session = Some(format!("user:{user}"));
+            return;
+        }
+        let prefix_len = messages
+            .iter()
+            .position(|m| m.get("role").and_then(|v| v.as_str()) == Some("user"))
+            .map(|idx| idx + 1)
+            .unwrap_or(0);
+        if prefix_len == 0 {
+            return;
+        }
+        let mut hasher = Sha1::new();
+        for msg in &messages[..prefix_len] {
+            hasher.update(msg.to_string().as_bytes());
+        }
+        let digest = hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<String>();
+        route.session = Some(format!("prefix:{digest}"));
+    }
+
+    pub async fn get_token_for_model(model: &str, route: &TokenRoute) -> Result<String, ApiError> {
         let pool = ModelService::pool_for_model(model);
+        let affinity: bool = get_config("token.session_affinity", false).await;
+        let session_key = route
+            .session
+            .as_ref()
+            .filter(|_| affinity)
+            .map(|session| format!("{pool}:{session}"));
+
         let mgr = get_token_manager().await;
-        let mut mgr = mgr.lock().await;
         mgr.reload_if_stale().await;
-        let token = mgr.get_token(&pool);
-        token.ok_or_else(|| ApiError::rate_limit("No available tokens. Please try again later."))
+
+        let now = chrono::Utc::now().timestamp_millis();
+        let ttl_sec: i64 = get_config("token.session_ttl_sec", 1800i64).await;
+        let strategy: String = get_config("token.selection_strategy", "quota".to_string()).await;
+        if let Some(key) = &session_key {
+            let mut sessions = SESSIONS.lock().await;
+            if let Some(sticky) = sessions.get_mut(key) {
+                if sticky.expires_at > now && mgr.can_use(&pool, &sticky.token, &route.tags) {
+                    sticky.expires_at = now + ttl_sec * 1000;
+                    return Ok(sticky.token.clone());
+                }
+            }
+        }
+
+        let token = mgr
+            .get_token(&pool, &route.tags, SelectionStrategy::parse(&strategy))
+            .ok_or_else(|| ApiError::rate_limit("No available tokens. Please try again later."))?;
+        if let Some(key) = session_key {
+            let mut sessions = SESSIONS.lock().await;
+            sessions.retain(|_, s| s.expires_at > now);
+            sessions.insert(
+                key,
+                StickyToken {
+                    token: token.clone(),
+                    expires_at: now + ttl_sec * 1000,
+                },
+            );
+        }
+        Ok(token)
     }
 
-    pub async fn consume(token: &str, effort: EffortType) -> bool {
+    pub async fn finish(token: &str, effort: EffortType, started_at: Instant) -> bool {
         let mgr = get_token_manager().await;
-        let mut mgr = mgr.lock().await;
+        mgr.record_latency(token, started_at.elapsed());
         mgr.consume(token, effort).await
     }
+
+    pub async fn record_ttfb(token: &str, ttfb: Duration) {
+        let mgr = get_token_manager().await;
+        mgr.record_ttfb(token, ttfb).await;
+    }
+
+    pub async fn record_error(token: &str, err: &ApiError) {
+        let mgr = get_token_manager().await;
+        mgr.record_error(token, ErrorClass::from_error(err)).await;
+    }
 }
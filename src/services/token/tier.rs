@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+use crate::core::config::get_config;
+use crate::services::grok::batch::run_in_batches;
+use crate::services::grok::usage::UsageService;
+use crate::services::token::manager::get_token_manager;
+
+const TIER_POLL_INTERVAL: Duration = Duration::from_secs(300);
+
+pub async fn detect_tiers(tokens: Vec<String>) {
+    if tokens.is_empty() {
+        return;
+    }
+    let max_concurrent: usize = get_config("performance.usage_max_concurrent", 25usize).await;
+    let batch_size: usize = get_config("performance.usage_batch_size", 50usize).await;
+    let results = run_in_batches(
+        tokens,
+        |token| async move {
+            let usage = UsageService::new().await;
+            usage.detect_tier(&token).await.map_err(|e| e.to_string())
+        },
+        max_concurrent,
+        batch_size,
+        None,
+        None,
+    )
+    .await;
+
+    let interval_hours: i64 = get_config("token.tier_check_interval_hours", 24i64).await;
+    let mgr = get_token_manager().await;
+    for (token, res) in results {
+        match res {
+            Ok(tier) => {
+                mgr.apply_tier(&token, tier).await;
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "Token {} tier probe failed: {}",
+                    crate::core::redact::fingerprint(&token),
+                    err
+                );
+                mgr.record_tier_failure(&token, interval_hours);
+            }
+        }
+    }
+}
+
+pub async fn detect_pending_tiers() {
+    let auto_tier: bool = get_config("token.auto_tier", true).await;
+    if !auto_tier {
+        return;
+    }
+    let interval_hours: i64 = get_config("token.tier_check_interval_hours", 24i64).await;
+    let tokens = get_token_manager()
+        .await
+        .tokens_needing_tier_check(interval_hours);
+    detect_tiers(tokens).await;
+}
+
+/// Probes run on their own timer, separate from the recovery loop; the
+/// per-token interval and failure backoff decide which tokens are due.
+pub fn start_tier_checker() {
+    tokio::spawn(async {
+        loop {
+            detect_pending_tiers().await;
+            tokio::time::sleep(TIER_POLL_INTERVAL).await;
+        }
+    });
+}
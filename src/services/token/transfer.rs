@@ -0,0 +1,273 @@
+use std::collections::HashSet;
+
+use serde_json::{Value as JsonValue, json};
+
+use crate::services::proxy::mask_proxy_url;
+use crate::services::token::models::TokenInfo;
+
+const SSO_COOKIE: &str = "sso";
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportedToken {
+    pub token: String,
+    pub pool: Option<String>,
+    pub note: Option<String>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct ImportParse {
+    pub tokens: Vec<ImportedToken>,
+    pub invalid: Vec<String>,
+}
+
+impl ImportParse {
+    fn push(&mut self, entry: ImportedToken, seen: &mut HashSet<String>) {
+        let token = entry.token.trim().trim_start_matches("sso=").to_string();
+        if !is_valid_token(&token) {
+            self.invalid.push(preview(&entry.token));
+            return;
+        }
+        if seen.insert(token.clone()) {
+            self.tokens.push(ImportedToken { token, ..entry });
+        }
+    }
+}
+
+pub fn parse_import(input: &str) -> ImportParse {
+    let mut parsed = ImportParse::default();
+    let mut seen = HashSet::new();
+    let trimmed = input.trim();
+    if trimmed.starts_with('[') || trimmed.starts_with('{') {
+        if let Ok(value) = serde_json::from_str::<JsonValue>(trimmed) {
+            parse_json(&value, None, &mut parsed, &mut seen);
+            return parsed;
+        }
+    }
+    for line in trimmed.lines() {
+        parse_line(line, None, &mut parsed, &mut seen);
+    }
+    parsed
+}
+
+fn parse_json(
+    value: &JsonValue,
+    pool: Option<&str>,
+    parsed: &mut ImportParse,
+    seen: &mut HashSet<String>,
+) {
+    match value {
+        JsonValue::String(s) => parse_line(s, pool, parsed, seen),
+        JsonValue::Array(arr) => {
+            for item in arr {
+                parse_json(item, pool, parsed, seen);
+            }
+        }
+        JsonValue::Object(obj) => {
+            if let (Some(name), Some(cookie)) = (
+                obj.get("name").and_then(|v| v.as_str()),
+                obj.get("value").and_then(|v| v.as_str()),
+            ) {
+                if name == SSO_COOKIE {
+                    parsed.push(
+                        ImportedToken {
+                            token: cookie.to_string(),
+                            pool: pool.map(|p| p.to_string()),
+                            ..Default::default()
+                        },
+                        seen,
+                    );
+                }
+                return;
+            }
+            if let Some(token) = obj.get("token").and_then(|v| v.as_str()) {
+                let tags = obj
+                    .get("tags")
+                    .and_then(|v| v.as_array())
+                    .map(|arr| {
+                        arr.iter()
+                            .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                parsed.push(
+                    ImportedToken {
+                        token: token.to_string(),
+                        pool: obj
+                            .get("pool")
+                            .and_then(|v| v.as_str())
+                            .or(pool)
+                            .map(|p| p.to_string()),
+                        note: obj
+                            .get("note")
+                            .and_then(|v| v.as_str())
+                            .map(|s| s.to_string()),
+                        tags,
+                    },
+                    seen,
+                );
+                return;
+            }
+            for (name, list) in obj {
+                parse_json(list, Some(name), parsed, seen);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn parse_line(
+    line: &str,
+    pool: Option<&str>,
+    parsed: &mut ImportParse,
+    seen: &mut HashSet<String>,
+) {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return;
+    }
+    let header = line
+        .get(..7)
+        .filter(|p| p.eq_ignore_ascii_case("cookie:"))
+        .map(|_| line[7..].trim());
+    let cookies = header.unwrap_or(line);
+    if header.is_some()
+        || (cookies.contains(';') && cookies.split(';').any(|p| p.trim().starts_with("sso=")))
+    {
+        let mut found = false;
+        for pair in cookies.split(';') {
+            if let Some((name, value)) = pair.trim().split_once('=') {
+                if name.trim() == SSO_COOKIE {
+                    found = true;
+                    parsed.push(
+                        ImportedToken {
+                            token: value.trim().to_string(),
+                            pool: pool.map(|p| p.to_string()),
+                            ..Default::default()
+                        },
+                        seen,
+                    );
+                }
+            }
+        }
+        if !found {
+            parsed.invalid.push(preview(line));
+        }
+        return;
+    }
+    let fields = split_csv(line);
+    let Some(token) = fields.first() else {
+        return;
+    };
+    if token.eq_ignore_ascii_case("token") {
+        return;
+    }
+    let note = fields.get(1).filter(|n| !n.is_empty()).cloned();
+    let tags = fields
+        .get(2)
+        .map(|t| {
+            t.split([';', '|'])
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    let pool = fields
+        .get(3)
+        .map(|p| p.as_str())
+        .filter(|p| !p.is_empty())
+        .or(pool);
+    parsed.push(
+        ImportedToken {
+            token: token.clone(),
+            pool: pool.map(|p| p.to_string()),
+            note,
+            tags,
+        },
+        seen,
+    );
+}
+
+pub(crate) fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                current.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut current).trim().to_string()),
+            _ => current.push(c),
+        }
+    }
+    fields.push(current.trim().to_string());
+    fields
+}
+
+pub(crate) fn is_valid_token(token: &str) -> bool {
+    token.len() >= 20
+        && token
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '='))
+}
+
+fn preview(raw: &str) -> String {
+    raw.trim().chars().take(32).collect()
+}
+
+pub fn mask_token(raw: &str) -> String {
+    crate::core::redact::fingerprint(raw)
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn export_json(pools: &[(String, Vec<TokenInfo>)], mask: bool) -> JsonValue {
+    let mut out = serde_json::Map::new();
+    for (pool, tokens) in pools {
+        let mut tokens = tokens.clone();
+        if mask {
+            for info in tokens.iter_mut() {
+                info.token = mask_token(&info.token);
+                info.cf_clearance = info.cf_clearance.as_deref().map(mask_token);
+                info.proxy_url = info.proxy_url.as_deref().map(mask_proxy_url);
+            }
+        }
+        out.insert(pool.clone(), json!(tokens));
+    }
+    JsonValue::Object(out)
+}
+
+pub fn export_csv(pools: &[(String, Vec<TokenInfo>)], mask: bool) -> String {
+    let mut out = String::from("token,note,tags,pool,status,quota\n");
+    for (pool, tokens) in pools {
+        for info in tokens {
+            let token = if mask {
+                mask_token(&info.token)
+            } else {
+                info.token.clone()
+            };
+            let status = format!("{:?}", info.status).to_lowercase();
+            let row = [
+                csv_field(&token),
+                csv_field(&info.note),
+                csv_field(&info.tags.join(";")),
+                csv_field(pool),
+                status,
+                info.quota.to_string(),
+            ];
+            out.push_str(&row.join(","));
+            out.push('\n');
+        }
+    }
+    out
+}
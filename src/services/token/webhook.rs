@@ -0,0 +1,155 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+use crate::core::config::get_config;
+use crate::services::token::events::{TokenEvent, subscribe};
+
+pub const SIGNATURE_HEADER: &str = "X-Grok2api-Signature";
+pub const EVENT_HEADER: &str = "X-Grok2api-Event";
+
+static DISPATCHER: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookSink {
+    pub url: String,
+    #[serde(default)]
+    pub secret: String,
+    /// Event names to deliver; empty means all.
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+impl WebhookSink {
+    pub fn accepts(&self, event: &str) -> bool {
+        self.events.is_empty() || self.events.iter().any(|e| e == event)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub timeout: Duration,
+    pub retry_base: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            timeout: Duration::from_secs(10),
+            retry_base: Duration::from_secs(1),
+        }
+    }
+}
+
+/// `sha256=<hex>` HMAC of the raw request body.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    let digest = mac.finalize().into_bytes();
+    let hex: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+    format!("sha256={hex}")
+}
+
+/// Retries non-2xx responses and transport errors with exponential back-off;
+/// returns the final status code.
+pub async fn deliver(
+    client: &reqwest::Client,
+    sink: &WebhookSink,
+    event: &TokenEvent,
+    policy: &RetryPolicy,
+) -> Result<u16, String> {
+    let body = serde_json::to_vec(event).map_err(|e| e.to_string())?;
+    let mut last_error = String::new();
+    for attempt in 0..=policy.max_retries {
+        if attempt > 0 {
+            tokio::time::sleep(policy.retry_base * 2u32.pow((attempt - 1).min(16))).await;
+        }
+        let mut request = client
+            .post(&sink.url)
+            .timeout(policy.timeout)
+            .header("Content-Type", "application/json")
+            .header(EVENT_HEADER, event.event.as_str())
+            .body(body.clone());
+        if !sink.secret.is_empty() {
+            request = request.header(SIGNATURE_HEADER, sign(&sink.secret, &body));
+        }
+        match request.send().await {
+            Ok(resp) if resp.status().is_success() => return Ok(resp.status().as_u16()),
+            Ok(resp) => last_error = format!("HTTP {}", resp.status().as_u16()),
+            Err(err) => last_error = err.to_string(),
+        }
+    }
+    Err(last_error)
+}
+
+async fn retry_policy() -> RetryPolicy {
+    let defaults = RetryPolicy::default();
+    let timeout: u64 = get_config("webhook.timeout_sec", defaults.timeout.as_secs()).await;
+    RetryPolicy {
+        max_retries: get_config("webhook.max_retries", defaults.max_retries).await,
+        timeout: Duration::from_secs(timeout.max(1)),
+        retry_base: defaults.retry_base,
+    }
+}
+
+async fn configured_sinks() -> Vec<WebhookSink> {
+    let sinks: Vec<serde_json::Value> = get_config("webhook.sinks", Vec::new()).await;
+    sinks
+        .into_iter()
+        .filter_map(|v| match serde_json::from_value::<WebhookSink>(v) {
+            Ok(sink) if !sink.url.trim().is_empty() => Some(sink),
+            Ok(_) => None,
+            Err(err) => {
+                tracing::warn!("Ignoring invalid webhook.sinks entry: {err}");
+                None
+            }
+        })
+        .collect()
+}
+
+pub fn start_webhook_dispatcher() {
+    let mut guard = DISPATCHER.lock().unwrap_or_else(|e| e.into_inner());
+    if guard.is_some() {
+        return;
+    }
+    let mut rx = subscribe();
+    *guard = Some(tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        loop {
+            let event = match rx.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Webhook dispatcher lagged, dropped {skipped} event(s)");
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            let policy = retry_policy().await;
+            for sink in configured_sinks().await {
+                if !sink.accepts(event.event.as_str()) {
+                    continue;
+                }
+                let client = client.clone();
+                let event = event.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = deliver(&client, &sink, &event, &policy).await {
+                        tracing::warn!(
+                            "Webhook {} delivery to {} failed: {}",
+                            event.event.as_str(),
+                            sink.url,
+                            err
+                        );
+                    }
+                });
+            }
+        }
+    }));
+}
@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::Router;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use bytes::Bytes;
+
+use crate::services::token::events::{self, TokenEvent, TokenEventKind};
+use crate::services::token::manager::TokenManager;
+use crate::services::token::models::{FAIL_THRESHOLD, TokenInfo};
+use crate::services::token::pool::TokenPool;
+use crate::services::token::webhook::{
+    EVENT_HEADER, RetryPolicy, SIGNATURE_HEADER, WebhookSink, deliver, sign,
+};
+
+#[derive(Default)]
+struct Received {
+    failures_left: usize,
+    requests: Vec<(HeaderMap, Bytes)>,
+}
+
+type Shared = Arc<Mutex<Received>>;
+
+async fn receive(State(state): State<Shared>, headers: HeaderMap, body: Bytes) -> StatusCode {
+    let mut state = state.lock().unwrap();
+    state.requests.push((headers, body));
+    if state.failures_left > 0 {
+        state.failures_left -= 1;
+        StatusCode::INTERNAL_SERVER_ERROR
+    } else {
+        StatusCode::OK
+    }
+}
+
+/// Local HTTP stand-in that fails the first `failures` requests.
+async fn stand_in(failures: usize) -> (String, Shared) {
+    let state: Shared = Arc::new(Mutex::new(Received {
+        failures_left: failures,
+        requests: Vec::new(),
+    }));
+    let app = Router::new()
+        .route("/hook", post(receive))
+        .with_state(state.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    (format!("http://{addr}/hook"), state)
+}
+
+fn sample_event() -> TokenEvent {
+    TokenEvent {
+        id: "evt-1".to_string(),
+        event: TokenEventKind::Cooling,
+        timestamp: 1_700_000_000_000,
+        pool: Some("ssoBasic".to_string()),
+        token: Some("abcdefgh...".to_string()),
+        data: serde_json::json!({"from": "active", "to": "cooling"}),
+    }
+}
+
+fn fast_policy(max_retries: u32) -> RetryPolicy {
+    RetryPolicy {
+        max_retries,
+        timeout: Duration::from_secs(5),
+        retry_base: Duration::from_millis(10),
+    }
+}
+
+#[tokio::test]
+async fn delivers_signed_event_after_retries() {
+    let (url, state) = stand_in(2).await;
+    let sink = WebhookSink {
+        url,
+        secret: "s3cret".to_string(),
+        events: Vec::new(),
+    };
+    let status = deliver(
+        &reqwest::Client::new(),
+        &sink,
+        &sample_event(),
+        &fast_policy(3),
+    )
+    .await;
+    assert_eq!(status, Ok(200));
+
+    let state = state.lock().unwrap();
+    assert_eq!(state.requests.len(), 3);
+    let (headers, body) = state.requests.last().unwrap();
+    assert_eq!(headers[EVENT_HEADER], "token.cooling");
+    assert_eq!(headers[SIGNATURE_HEADER], sign("s3cret", body).as_str());
+
+    let payload: serde_json::Value = serde_json::from_slice(body).unwrap();
+    assert_eq!(payload["event"], "token.cooling");
+    assert_eq!(payload["pool"], "ssoBasic");
+    assert_eq!(payload["data"]["to"], "cooling");
+}
+
+#[tokio::test]
+async fn gives_up_after_max_retries() {
+    let (url, state) = stand_in(usize::MAX).await;
+    let sink = WebhookSink {
+        url,
+        secret: String::new(),
+        events: Vec::new(),
+    };
+    let status = deliver(
+        &reqwest::Client::new(),
+        &sink,
+        &sample_event(),
+        &fast_policy(2),
+    )
+    .await;
+    assert_eq!(status, Err("HTTP 500".to_string()));
+
+    let state = state.lock().unwrap();
+    assert_eq!(state.requests.len(), 3);
+    assert!(!state.requests[0].0.contains_key(SIGNATURE_HEADER));
+}
+
+#[test]
+fn sink_filters_by_event_name() {
+    let sink = WebhookSink {
+        url: "http://127.0.0.1/hook".to_string(),
+        secret: String::new(),
+        events: vec!["token.expired".to_string()],
+    };
+    assert!(sink.accepts("token.expired"));
+    assert!(!sink.accepts("token.cooling"));
+}
+
+#[tokio::test]
+async fn record_fail_emits_only_threshold() {
+    let raw = "webhook-test-token-0123456789abcdef";
+    let mut pool = TokenPool::new("ssoBasic");
+    pool.add(TokenInfo::new(raw.to_string()));
+    let mgr = TokenManager::with_pools(HashMap::from([("ssoBasic".to_string(), pool)]));
+    let mut rx = events::subscribe();
+
+    for _ in 0..FAIL_THRESHOLD {
+        mgr.record_fail(raw, 401, "unauthorized").await;
+    }
+
+    let masked = crate::services::token::transfer::mask_token(raw);
+    let mut kinds = Vec::new();
+    while let Ok(Ok(event)) = tokio::time::timeout(Duration::from_millis(200), rx.recv()).await {
+        if event.token.as_deref() == Some(masked.as_str()) {
+            kinds.push(event.event);
+        }
+    }
+    assert_eq!(kinds, vec![TokenEventKind::FailedThreshold]);
+}
+
+#[tokio::test]
+async fn pool_low_active_fires_once_per_drop() {
+    let pool_name = "webhook-test-low-pool";
+    let mut pool = TokenPool::new(pool_name);
+    for i in 0..2 {
+        pool.add(TokenInfo::new(format!("webhook-low-token-{i}-0123456789")));
+    }
+    let mgr = TokenManager::with_pools(HashMap::from([(pool_name.to_string(), pool)]));
+    mgr.set_low_active_threshold(2);
+    let mut rx = events::subscribe();
+
+    for i in 0..2 {
+        let raw = format!("webhook-low-token-{i}-0123456789");
+        for _ in 0..FAIL_THRESHOLD {
+            mgr.record_fail(&raw, 401, "unauthorized").await;
+        }
+    }
+
+    let mut low = Vec::new();
+    while let Ok(Ok(event)) = tokio::time::timeout(Duration::from_millis(200), rx.recv()).await {
+        if event.event == TokenEventKind::PoolLowActive && event.pool.as_deref() == Some(pool_name)
+        {
+            low.push(event.data["active"].clone());
+        }
+    }
+    assert_eq!(low, vec![serde_json::json!(1)]);
+}
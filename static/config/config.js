@@ -1,5 +1,6 @@
 let apiKey = '';
 let currentConfig = {};
+let readonlyKeys = new Set();
 const byId = (id) => document.getElementById(id);
 const NUMERIC_FIELDS = new Set([
   'timeout',
@@ -224,6 +225,7 @@ async function loadData() {
       headers: buildAuthHeaders(apiKey)
     });
     if (res.ok) {
+      readonlyKeys = new Set((res.headers.get('X-Config-Readonly') || '').split(',').filter(Boolean));
       currentConfig = await res.json();
       renderConfig(currentConfig);
     } else if (res.status === 401) {
@@ -314,6 +316,10 @@ function renderConfig(data) {
       }
 
       if (built) {
+        if (readonlyKeys.has(`${section}.${key}`)) {
+          built.input.disabled = true;
+          descEl.textContent += '（由环境变量或启动参数覆盖，只读）';
+        }
         inputWrapper.appendChild(built.node);
       }
       fieldCard.appendChild(inputWrapper);
@@ -380,7 +386,9 @@ async function saveConfig() {
         btn.style.backgroundColor = '';
       }, 2000);
     } else {
-      showToast('保存失败', 'error');
+      const data = await res.json().catch(() => ({}));
+      const fields = (data.errors || []).map(e => `${e.field}: ${e.message}`).join('；');
+      showToast(fields ? '保存失败: ' + fields : '保存失败', 'error');
     }
   } catch (e) {
     showToast('错误: ' + e.message, 'error');
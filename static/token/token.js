@@ -240,7 +240,7 @@ function renderTable() {
     tdToken.innerHTML = `
                 <div class="flex items-center gap-2">
                     <span class="font-mono text-xs text-gray-500" title="${item.token}">${tokenShort}</span>
-                    <button class="text-gray-400 hover:text-black transition-colors" onclick="copyToClipboard('${item.token}', this)">
+                    <button class="text-gray-400 hover:text-black transition-colors" onclick="copyToken('${item.token}', this)">
                         <svg width="12" height="12" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"><rect x="9" y="9" width="13" height="13" rx="2" ry="2"></rect><path d="M5 15H4a2 2 0 0 1-2-2V4a2 2 0 0 1 2-2h9a2 2 0 0 1 2 2v1"></path></svg>
                     </button>
                 </div>
@@ -368,11 +368,15 @@ function addToken() {
 }
 
 // Batch export (Selected only)
-function batchExport() {
+async function batchExport() {
   const selected = getSelectedTokens();
   if (selected.length === 0) return showToast("未选择 Token", 'error');
-  const content = selected.map(t => t.token).join('\n') + '\n';
-  downloadTextFile(content, `tokens_export_selected_${new Date().toISOString().slice(0, 10)}.txt`);
+  try {
+    const raw = await revealTokens(selected.map(t => t.token));
+    downloadTextFile(raw.join('\n') + '\n', `tokens_export_selected_${new Date().toISOString().slice(0, 10)}.txt`);
+  } catch (e) {
+    showToast('导出失败: ' + e.message, 'error');
+  }
 }
 
 
@@ -547,10 +551,39 @@ async function submitImport() {
 }
 
 // Export Logic
-function exportTokens() {
+async function exportTokens() {
   if (flatTokens.length === 0) return showToast("列表为空", 'error');
-  const content = flatTokens.map(t => t.token).join('\n') + '\n';
-  downloadTextFile(content, `tokens_export_${new Date().toISOString().slice(0, 10)}.txt`);
+  try {
+    const raw = await revealTokens(flatTokens.map(t => t.token));
+    downloadTextFile(raw.join('\n') + '\n', `tokens_export_${new Date().toISOString().slice(0, 10)}.txt`);
+  } catch (e) {
+    showToast('导出失败: ' + e.message, 'error');
+  }
+}
+
+// The list only carries fingerprints; raw tokens come from the audited reveal endpoint
+async function revealTokens(tokens) {
+  const res = await fetch('/api/v1/admin/secrets/reveal', {
+    method: 'POST',
+    headers: {
+      'Content-Type': 'application/json',
+      ...buildAuthHeaders(apiKey)
+    },
+    body: JSON.stringify({ tokens })
+  });
+  if (res.status === 401) logout();
+  if (!res.ok) throw new Error(`HTTP ${res.status}`);
+  const data = await res.json();
+  return tokens.map(t => (data.tokens[t] || {}).token).filter(Boolean);
+}
+
+async function copyToken(token, btn) {
+  try {
+    const [raw] = await revealTokens([token]);
+    await copyToClipboard(raw, btn);
+  } catch (e) {
+    showToast('复制失败: ' + e.message, 'error');
+  }
 }
 
 async function copyToClipboard(text, btn) {